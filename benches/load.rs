@@ -0,0 +1,62 @@
+//! Measures how fast synthetic E57 files are decoded and logged to an in-memory recording.
+//! Run with `cargo bench --bench load`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::{write_synthetic_e57, Synthetic};
+use rerun::RecordingStreamBuilder;
+use rerun_loader_e57::{load_e57, LoadOptions};
+use std::time::{Duration, Instant};
+
+const CASES: [Synthetic; 3] = [
+    Synthetic {
+        scans: 1,
+        points: 1_000_000,
+        color: false,
+    },
+    Synthetic {
+        scans: 1,
+        points: 1_000_000,
+        color: true,
+    },
+    Synthetic {
+        scans: 4,
+        points: 250_000,
+        color: true,
+    },
+];
+
+fn main() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let options = LoadOptions {
+        quiet: true,
+        ..Default::default()
+    };
+
+    for (index, synthetic) in CASES.into_iter().enumerate() {
+        let path = dir.path().join(format!("synthetic_{index}.e57"));
+        write_synthetic_e57(&path, synthetic)?;
+
+        let mut fastest = Duration::MAX;
+        for _ in 0..3 {
+            let (rec, storage) = RecordingStreamBuilder::new("rerun_e57_bench").memory()?;
+            let start = Instant::now();
+            load_e57(&path, &options, &rec)?;
+            rec.flush_blocking();
+            fastest = fastest.min(start.elapsed());
+            drop(storage.take());
+        }
+
+        let points = (synthetic.scans * synthetic.points) as f64;
+        println!(
+            "{} scans x {} points, {}: {:>7.1} ms, {:>6.2} Mpoints/s",
+            synthetic.scans,
+            synthetic.points,
+            if synthetic.color { "rgb" } else { "intensity" },
+            fastest.as_secs_f64() * 1e3,
+            points / fastest.as_secs_f64() / 1e6
+        );
+    }
+    Ok(())
+}
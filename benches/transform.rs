@@ -0,0 +1,72 @@
+//! Compares the batched affine transform of the scan pipeline with transforming each point by
+//! scaling it and rotating it by a quaternion. Run with `cargo bench --bench transform`.
+
+use glam::{DAffine3, DQuat, DVec3};
+use rerun::Vec3D;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const POINTS: usize = 4096;
+const ROUNDS: usize = 2000;
+
+fn main() {
+    let positions: Vec<DVec3> = (0..POINTS)
+        .map(|i| {
+            let i = i as f64;
+            DVec3::new(i.sin() * 20.0, i.cos() * 20.0, (i * 0.01) % 5.0)
+        })
+        .collect();
+    let (scale, rotation, translation) = (
+        0.001,
+        DQuat::from_rotation_z(0.7) * DQuat::from_rotation_x(0.1),
+        DVec3::new(1200.5, -340.25, 12.0),
+    );
+    let affine = DAffine3::from_rotation_translation(rotation, translation)
+        * DAffine3::from_scale(DVec3::splat(scale));
+
+    let mut logged = Vec::with_capacity(POINTS);
+    let per_point = measure(|| {
+        logged.clear();
+        for &position in black_box(&positions) {
+            let meters = position * black_box(scale);
+            logged.push(Vec3D::from(
+                (rotation * meters + translation).as_vec3().to_array(),
+            ));
+        }
+        black_box(&logged);
+    });
+    let batched = measure(|| {
+        logged.clear();
+        rerun_loader_e57::transform_points(
+            black_box(&affine),
+            black_box(&positions).iter().copied(),
+            &mut logged,
+        );
+        black_box(&logged);
+    });
+
+    let points_per_second = |time: Duration| (POINTS * ROUNDS) as f64 / time.as_secs_f64();
+    println!(
+        "per point: {:>8.1} Mpoints/s",
+        points_per_second(per_point) / 1e6
+    );
+    println!(
+        "batched:   {:>8.1} Mpoints/s ({:.2}x)",
+        points_per_second(batched) / 1e6,
+        per_point.as_secs_f64() / batched.as_secs_f64()
+    );
+}
+
+/// Returns the fastest of three runs of `ROUNDS` calls.
+fn measure(mut f: impl FnMut()) -> Duration {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..ROUNDS {
+                f();
+            }
+            start.elapsed()
+        })
+        .min()
+        .expect("three runs")
+}
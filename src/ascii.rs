@@ -0,0 +1,571 @@
+//! Loads the ASCII point formats PTS and PTX, which many scanners export alongside E57.
+//!
+//! PTS files are a list of points, optionally split into scans by lines holding a point count.
+//! PTX files are a sequence of structured scans, each with a header giving its grid size and
+//! its pose. Every scan is logged like an E57 scan as `{prefix}/scan_{index}`.
+
+use anyhow::{Context, Result};
+use glam::{DMat3, DQuat, DVec3};
+use rerun::RecordingStream;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+use std::path::Path;
+
+use crate::pipeline::{Loader, ScanInfo, ScanPipeline, SourcePoint};
+use crate::{
+    bounds_color_range, center_offset, geometric_color_by, scan_color_by, validate_options, Center,
+    IntensityRange, LoadOptions, Stats,
+};
+
+/// ASCII point format of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Pts,
+    Ptx,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Pts => "PTS",
+            Self::Ptx => "PTX",
+        }
+    }
+
+    /// Intensity range of the format, used for `IntensityRange::Limits`.
+    fn intensity_limits(self) -> (f64, f64) {
+        match self {
+            // Leica scanners write PTS intensities as 12-bit signed integers.
+            Self::Pts => (-2048.0, 2047.0),
+            Self::Ptx => (0.0, 1.0),
+        }
+    }
+}
+
+/// Header of a scan within an ASCII file.
+#[derive(Debug, Clone, PartialEq)]
+struct ScanHeader {
+    /// Number of rows of a structured PTX scan.
+    rows: Option<usize>,
+    /// Pose of the scan in file-level coordinates.
+    world: Option<(DVec3, DQuat)>,
+}
+
+#[derive(Debug, Clone)]
+enum Record {
+    Scan(ScanHeader),
+    Point(SourcePoint),
+    /// A PTX grid cell without a return, written as the origin.
+    Missing(SourcePoint),
+}
+
+/// Reads the scans and points of an ASCII file line by line.
+struct AsciiReader<R> {
+    format: Format,
+    lines: Lines<R>,
+    line_number: usize,
+    /// A PTS point line read before the first scan header, to be returned after it.
+    pending: Option<String>,
+    started: bool,
+    /// Points left in the current PTX scan.
+    remaining: usize,
+    rows: usize,
+    index: usize,
+}
+
+impl<R: BufRead> AsciiReader<R> {
+    fn new(format: Format, reader: R) -> Self {
+        Self {
+            format,
+            lines: reader.lines(),
+            line_number: 0,
+            pending: None,
+            started: false,
+            remaining: 0,
+            rows: 0,
+            index: 0,
+        }
+    }
+
+    /// Returns the next non-empty line.
+    fn next_line(&mut self) -> Result<Option<String>> {
+        if let Some(line) = self.pending.take() {
+            return Ok(Some(line));
+        }
+        for line in self.lines.by_ref() {
+            self.line_number += 1;
+            let line = line.with_context(|| format!("Failed to read line {}", self.line_number))?;
+            if !line.trim().is_empty() {
+                return Ok(Some(line));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads a header line of exactly `N` numbers.
+    fn header_values<const N: usize>(&mut self) -> Result<[f64; N]> {
+        let line = self
+            .next_line()?
+            .with_context(|| format!("PTX scan header ends at line {}", self.line_number))?;
+        let values = parse_numbers(&line)
+            .with_context(|| format!("Invalid PTX header on line {}", self.line_number))?;
+        values.try_into().map_err(|values: Vec<f64>| {
+            anyhow::anyhow!(
+                "Expected {N} values on PTX header line {}, found {}",
+                self.line_number,
+                values.len()
+            )
+        })
+    }
+
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        match self.format {
+            Format::Pts => self.next_pts_record(),
+            Format::Ptx => self.next_ptx_record(),
+        }
+    }
+
+    fn next_pts_record(&mut self) -> Result<Option<Record>> {
+        let Some(line) = self.next_line()? else {
+            return Ok(None);
+        };
+
+        // A line with a single number is the point count of the following scan.
+        if line.split_whitespace().count() == 1 {
+            line.trim()
+                .parse::<u64>()
+                .with_context(|| format!("Invalid PTS point count on line {}", self.line_number))?;
+            self.started = true;
+            return Ok(Some(Record::Scan(ScanHeader {
+                rows: None,
+                world: None,
+            })));
+        }
+        if !self.started {
+            self.started = true;
+            self.pending = Some(line);
+            return Ok(Some(Record::Scan(ScanHeader {
+                rows: None,
+                world: None,
+            })));
+        }
+
+        self.parse_point(&line).map(Some)
+    }
+
+    fn next_ptx_record(&mut self) -> Result<Option<Record>> {
+        if self.remaining == 0 {
+            let Some(line) = self.next_line()? else {
+                return Ok(None);
+            };
+            let columns = line.trim().parse::<usize>().with_context(|| {
+                format!("Invalid PTX column count on line {}", self.line_number)
+            })?;
+            let [rows] = self.header_values::<1>()?;
+
+            // The scanner position and axes repeat the pose of the transformation matrix.
+            for _ in 0..4 {
+                self.header_values::<3>()?;
+            }
+            // The matrix is stored transposed, with the translation in the last row.
+            let m = [(); 4].map(|_| self.header_values::<4>());
+            let [r0, r1, r2, t] = m.map(|row| row.map(|[a, b, c, _]| DVec3::new(a, b, c)));
+            let rotation = DMat3::from_cols(r0?, r1?, r2?);
+
+            self.rows = rows as usize;
+            self.remaining = columns * self.rows;
+            self.index = 0;
+            return Ok(Some(Record::Scan(ScanHeader {
+                rows: Some(self.rows),
+                world: Some((t?, DQuat::from_mat3(&rotation).normalize())),
+            })));
+        }
+
+        let line = self
+            .next_line()?
+            .with_context(|| format!("PTX scan ends early at line {}", self.line_number))?;
+        self.remaining -= 1;
+        let record = self.parse_point(&line)?;
+        self.index += 1;
+        Ok(Some(record))
+    }
+
+    /// Parses `x y z [intensity] [r g b]`.
+    fn parse_point(&self, line: &str) -> Result<Record> {
+        let values = parse_numbers(line).with_context(|| {
+            format!(
+                "Invalid {} point on line {}",
+                self.format.name(),
+                self.line_number
+            )
+        })?;
+        let (intensity, rgb) = match values.len() {
+            3 => (None, None),
+            4 => (Some(values[3]), None),
+            6 => (None, Some(&values[3..6])),
+            7 => (Some(values[3]), Some(&values[4..7])),
+            n => anyhow::bail!(
+                "Expected 3, 4, 6 or 7 values on line {}, found {n}",
+                self.line_number
+            ),
+        };
+
+        let position = DVec3::new(values[0], values[1], values[2]);
+
+        // PTX scans are stored column by column.
+        let (row, column) = match self.format {
+            Format::Pts => (-1, -1),
+            Format::Ptx => (
+                (self.index % self.rows) as i64,
+                (self.index / self.rows) as i64,
+            ),
+        };
+
+        let point = SourcePoint {
+            position,
+            color: rgb.map(|rgb| {
+                let [r, g, b] = [0, 1, 2].map(|i| rgb[i].clamp(0.0, 255.0) as u8);
+                rerun::Color::from_rgb(r, g, b)
+            }),
+            intensity: intensity.map(|i| i as f32),
+            row,
+            column,
+            classification: None,
+            normal: None,
+            time: None,
+            returns: None,
+            extras: Default::default(),
+        };
+        if self.format == Format::Ptx && values[..3] == [0.0; 3] {
+            Ok(Record::Missing(point))
+        } else {
+            Ok(Record::Point(point))
+        }
+    }
+}
+
+fn parse_numbers(line: &str) -> Result<Vec<f64>> {
+    line.split_whitespace()
+        .map(|value| value.parse::<f64>().map_err(Into::into))
+        .collect()
+}
+
+fn open(path: &Path, format: Format) -> Result<AsciiReader<BufReader<File>>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to read {} file: {path:?}", format.name()))?;
+    Ok(AsciiReader::new(format, BufReader::new(file)))
+}
+
+/// Bounds, intensity range and number of records of a scan, gathered in a first pass.
+#[derive(Debug, Default)]
+struct ScanSummary {
+    world: Option<(DVec3, DQuat)>,
+    bounds: Option<([f64; 3], [f64; 3])>,
+    intensity_range: Option<(f64, f64)>,
+    has_color: bool,
+    records: u64,
+}
+
+fn summarize(path: &Path, format: Format) -> Result<Vec<ScanSummary>> {
+    let mut reader = open(path, format)?;
+    let mut scans: Vec<ScanSummary> = Vec::new();
+
+    while let Some(record) = reader.next_record()? {
+        let point = match record {
+            Record::Scan(header) => {
+                scans.push(ScanSummary {
+                    world: header.world,
+                    ..Default::default()
+                });
+                continue;
+            }
+            Record::Missing(_) => None,
+            Record::Point(point) => Some(point),
+        };
+        let Some(scan) = scans.last_mut() else {
+            continue;
+        };
+        scan.records += 1;
+        let Some(point) = point else {
+            continue;
+        };
+
+        let position = point.position.to_array();
+        scan.bounds = Some(match scan.bounds {
+            Some((min, max)) => (
+                [0, 1, 2].map(|i| min[i].min(position[i])),
+                [0, 1, 2].map(|i| max[i].max(position[i])),
+            ),
+            None => (position, position),
+        });
+        scan.has_color |= point.color.is_some();
+        if let Some(intensity) = point.intensity.map(f64::from) {
+            scan.intensity_range = Some(match scan.intensity_range {
+                Some((lo, hi)) => (lo.min(intensity), hi.max(intensity)),
+                None => (intensity, intensity),
+            });
+        }
+    }
+
+    Ok(scans)
+}
+
+/// Loads the PTS file at `path` and logs its scans to `rec`.
+pub fn load_pts(path: &Path, options: &LoadOptions, rec: &RecordingStream) -> Result<Stats> {
+    load_ascii(path, Format::Pts, options, rec)
+}
+
+/// Loads the PTX file at `path` and logs its scans, posed by their header transforms, to `rec`.
+pub fn load_ptx(path: &Path, options: &LoadOptions, rec: &RecordingStream) -> Result<Stats> {
+    load_ascii(path, Format::Ptx, options, rec)
+}
+
+fn load_ascii(
+    path: &Path,
+    format: Format,
+    options: &LoadOptions,
+    rec: &RecordingStream,
+) -> Result<Stats> {
+    validate_options(options)?;
+
+    let entity_path_prefix: &str = &crate::entity_path_prefix(
+        options,
+        path,
+        match format {
+            Format::Pts => "pts_pointcloud",
+            Format::Ptx => "ptx_pointcloud",
+        },
+    );
+
+    // Unlike E57 files, ASCII files have no per-scan bounds or intensity limits, so
+    // they are computed in a first pass over the file when needed.
+    let needs_summary = options.intensity_range == IntensityRange::Auto
+        || options.bounds
+        || options.lod_levels.is_some()
+        || (options.send_blueprint && (format == Format::Ptx || options.visible_scans.is_some()))
+        || options.center == Some(Center::Auto)
+        || options.color_priority.is_some()
+        || options.min_points.is_some()
+        || options.max_points.is_some()
+        || (options.color_range.is_none() && geometric_color_by(options).is_some());
+    let summaries = if needs_summary {
+        Some(summarize(path, format)?)
+    } else {
+        None
+    };
+    // The scans of PTS and PTX files have neither names nor GUIDs. Their number of records is
+    // only known from the summary, which is read for `--min-points`.
+    let is_large_enough = |index: usize| {
+        summaries
+            .as_ref()
+            .and_then(|summaries| summaries.get(index))
+            .is_none_or(|scan| scan.records >= crate::min_points(options))
+    };
+    let selected = |index: usize| {
+        crate::is_scan_selected(options, index, None, None) && is_large_enough(index)
+    };
+
+    let scan_bounds = summaries
+        .iter()
+        .flatten()
+        .enumerate()
+        .filter(|(index, _)| selected(*index))
+        .filter_map(|(_, scan)| Some((scan.bounds?, scan.world.filter(|_| !options.no_transform))));
+    let color_range = match (options.color_range, geometric_color_by(options)) {
+        (Some(range), _) => Some(range),
+        (None, Some(_)) => bounds_color_range(options, scan_bounds.clone()),
+        (None, None) => None,
+    };
+    let center = center_offset(options, scan_bounds);
+
+    let total = summaries.as_ref().map_or(0, |summaries| {
+        summaries
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| selected(*index))
+            .map(|(_, scan)| scan.records)
+            .sum()
+    });
+    let loader = Loader::new(
+        rec,
+        path,
+        options,
+        entity_path_prefix,
+        total,
+        color_range,
+        center,
+    )?;
+    loader.send_blueprint(
+        summaries
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(index, _)| index)
+            .filter(|index| selected(*index)),
+    )?;
+
+    let mut reader = open(path, format)?;
+    let mut scan_count = 0;
+    let mut resumed_scans = 0;
+    let mut points = 0;
+    let mut pipeline: Option<ScanPipeline> = None;
+
+    while let Some(record) = reader.next_record()? {
+        if crate::is_cancelled(options) {
+            break;
+        }
+        let header = match record {
+            Record::Scan(header) => header,
+            Record::Point(point) => {
+                if let Some(pipeline) = &mut pipeline {
+                    pipeline.push(point)?;
+                }
+                continue;
+            }
+            Record::Missing(point) => {
+                if let Some(pipeline) = &mut pipeline {
+                    pipeline.push_invalid(point)?;
+                }
+                continue;
+            }
+        };
+
+        if let Some(pipeline) = pipeline.take() {
+            points += pipeline.finish()?;
+        }
+        let index = scan_count;
+        scan_count += 1;
+        if !selected(index) {
+            continue;
+        }
+        if loader.was_logged(index) {
+            resumed_scans += 1;
+            continue;
+        }
+
+        let summary = summaries
+            .as_ref()
+            .and_then(|summaries| summaries.get(index));
+        let intensity_range = match options.intensity_range {
+            IntensityRange::Limits => Some(format.intensity_limits()),
+            IntensityRange::Auto => summary.and_then(|scan| scan.intensity_range),
+            IntensityRange::Fixed(min, max) => Some((min, max)),
+        };
+        // ASCII files have no classification, and the summary is read for a color priority.
+        let color_by = scan_color_by(
+            options,
+            summary.is_some_and(|scan| scan.has_color),
+            summary.is_some_and(|scan| scan.intensity_range.is_some()),
+            false,
+        );
+        let world = header.world.filter(|_| !options.no_transform);
+        let entity_path = loader.scan_path(index);
+        let scan_pipeline = ScanPipeline::new(
+            &loader,
+            ScanInfo {
+                index,
+                entity_path: entity_path.clone(),
+                color_by,
+                log_intensity: options.log_intensity,
+                intensity_range,
+                normals: false,
+                extra_fields: Vec::new(),
+                bounds: summary.and_then(|scan| scan.bounds),
+                world,
+                structured: header.rows.is_some(),
+                classified: false,
+                records: summary.map_or(0, |scan| scan.records),
+                acquisition_time: None,
+                preview: false,
+                shading: None,
+            },
+        )?;
+
+        // Like for E57 scans, a marker shows the scanner position of posed scans.
+        if header.world.is_some() {
+            scan_pipeline.log_scan_origin()?;
+        }
+        pipeline = Some(scan_pipeline);
+    }
+
+    if let Some(pipeline) = pipeline.take() {
+        points += pipeline.finish()?;
+    }
+    let (issues, reports) = loader.finish()?;
+
+    Ok(Stats {
+        scans: (0..scan_count).filter(|index| selected(*index)).count() - resumed_scans,
+        skipped_scans: (0..scan_count)
+            .filter(|&index| {
+                crate::is_scan_selected(options, index, None, None) && !is_large_enough(index)
+            })
+            .count(),
+        points,
+        invalid_points: issues.invalid_points,
+        decode_errors: issues.decode_errors,
+        reports,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(format: Format, text: &str) -> Vec<Record> {
+        let mut reader = AsciiReader::new(format, text.as_bytes());
+        let mut records = Vec::new();
+        while let Some(record) = reader.next_record().unwrap() {
+            records.push(record);
+        }
+        records
+    }
+
+    #[test]
+    fn reads_pts_scans_with_and_without_counts() {
+        let records = records(
+            Format::Pts,
+            "1.0 2.0 3.0 -100 255 0 0\n\n2\n0 0 0 5\n1 1 1 0 0 255\n",
+        );
+        assert_eq!(records.len(), 5);
+        assert!(matches!(records[0], Record::Scan(_)));
+        let Record::Point(point) = &records[1] else {
+            panic!("expected a point");
+        };
+        assert_eq!(point.position, DVec3::new(1.0, 2.0, 3.0));
+        assert_eq!(point.intensity, Some(-100.0));
+        assert_eq!(point.color, Some(rerun::Color::from_rgb(255, 0, 0)));
+        assert!(matches!(records[2], Record::Scan(_)));
+        assert!(matches!(records[3], Record::Point(_)));
+        let Record::Point(point) = &records[4] else {
+            panic!("expected a point");
+        };
+        assert_eq!(point.intensity, None);
+        assert_eq!(point.color, Some(rerun::Color::from_rgb(0, 0, 255)));
+    }
+
+    #[test]
+    fn reads_ptx_header_and_grid_indices() {
+        let text = "2\n1\n1 2 3\n1 0 0\n0 1 0\n0 0 1\n\
+                    0 1 0 0\n-1 0 0 0\n0 0 1 0\n1 2 3 1\n\
+                    0 0 0 0.5\n1 0 0 0.25\n";
+        let records = records(Format::Ptx, text);
+        assert_eq!(records.len(), 3);
+
+        let Record::Scan(header) = &records[0] else {
+            panic!("expected a scan header");
+        };
+        assert_eq!(header.rows, Some(1));
+        let (translation, rotation) = header.world.unwrap();
+        assert_eq!(translation, DVec3::new(1.0, 2.0, 3.0));
+        // The first matrix row is the image of the x axis.
+        assert!((rotation * DVec3::X - DVec3::Y).length() < 1e-9);
+
+        assert!(matches!(records[1], Record::Missing(_)));
+        let Record::Point(point) = &records[2] else {
+            panic!("expected a point");
+        };
+        assert_eq!((point.row, point.column), (0, 1));
+        assert_eq!(point.intensity, Some(0.25));
+    }
+}
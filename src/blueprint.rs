@@ -0,0 +1,134 @@
+//! A default layout for the viewer: a single 3D view of all loaded scans.
+
+use anyhow::Result;
+use rerun::external::re_log_types::BlueprintActivationCommand;
+use rerun::external::re_types::blueprint::archetypes::{
+    ContainerBlueprint, ViewBlueprint, ViewContents, ViewportBlueprint, VisibleTimeRanges,
+};
+use rerun::external::re_types::blueprint::components::{ContainerKind, Visible, VisibleTimeRange};
+use rerun::external::re_types::datatypes::{self, TimeRange, TimeRangeBoundary};
+use rerun::{ComponentBatch as _, Points3D, RecordingStream, RecordingStreamBuilder};
+
+use crate::pipeline::ACQUISITION_TIMELINE;
+use crate::{EntityLayout, LoadOptions, TimeSource};
+
+/// Fixed blueprint ids, since the loader only ever sends one layout per recording.
+const CONTAINER_ID: [u8; 16] = *b"rerun-e57-layout";
+const VIEW_ID: [u8; 16] = *b"rerun-e57-3dview";
+
+/// Default radius of all points in the view, in UI points, which keeps sparse scans visible
+/// at any zoom level.
+const DEFAULT_POINT_RADIUS: f32 = 2.0;
+
+/// Sends a blueprint with a 3D view rooted at `entity_path_prefix` and makes it active.
+/// The entities in `hidden` and their children start hidden.
+pub(crate) fn send_blueprint(
+    rec: &RecordingStream,
+    options: &LoadOptions,
+    entity_path_prefix: &str,
+    hidden: &[String],
+) -> Result<()> {
+    // A disabled recording, e.g. for an export, has nothing to show.
+    let Some(info) = rec.store_info() else {
+        return Ok(());
+    };
+    let (blueprint, storage) = RecordingStreamBuilder::new(info.application_id)
+        .blueprint()
+        .memory()?;
+    blueprint.set_time_sequence("blueprint", 0);
+
+    let uuid = |id: [u8; 16]| uuid_string(&id);
+    let view_path = format!("view/{}", uuid(VIEW_ID));
+    blueprint.log(
+        view_path.as_str(),
+        &ViewBlueprint::new("3D")
+            .with_display_name(match entity_path_prefix {
+                "" => "/",
+                prefix => prefix,
+            })
+            .with_space_origin(format!("/{entity_path_prefix}")),
+    )?;
+    blueprint.log(
+        format!("{view_path}/ViewContents"),
+        &ViewContents::new(["+ $origin/**"]),
+    )?;
+    // Hidden entities are still part of the view, so they can be shown in the blueprint panel.
+    for path in hidden {
+        blueprint.log(
+            format!("{view_path}/ViewContents/recursive_overrides/{path}"),
+            &Visible::from(false).try_serialized()?,
+        )?;
+    }
+    // The viewer shows the latest row of an entity, while the flat layout logs every chunk as
+    // a row of its own, so the view shows all rows up to the time cursor instead.
+    if options.entity_layout == EntityLayout::Flat {
+        blueprint.log(
+            format!("{view_path}/VisibleTimeRanges"),
+            &VisibleTimeRanges::new(logged_timelines(options).into_iter().map(|timeline| {
+                VisibleTimeRange(datatypes::VisibleTimeRange {
+                    timeline: timeline.into(),
+                    range: TimeRange {
+                        start: TimeRangeBoundary::Infinite,
+                        end: TimeRangeBoundary::AT_CURSOR,
+                    },
+                })
+            })),
+        )?;
+    }
+
+    let radius = rerun::Radius::new_ui_points(DEFAULT_POINT_RADIUS);
+    blueprint.log(
+        format!("{view_path}/defaults"),
+        &Points3D::update_fields().with_radii([radius]),
+    )?;
+
+    blueprint.log(
+        format!("container/{}", uuid(CONTAINER_ID)),
+        &ContainerBlueprint::new(ContainerKind::Tabs).with_contents([view_path.as_str()]),
+    )?;
+    blueprint.log(
+        "viewport",
+        &ViewportBlueprint::new()
+            .with_root_container(CONTAINER_ID)
+            .with_auto_layout(false)
+            .with_auto_views(false),
+    )?;
+
+    blueprint.flush_blocking();
+    if let Some(blueprint_info) = blueprint.store_info() {
+        rec.send_blueprint(
+            storage.take(),
+            BlueprintActivationCommand::make_active(blueprint_info.store_id),
+        );
+    }
+    Ok(())
+}
+
+/// Returns the names of all timelines the points are logged on.
+fn logged_timelines(options: &LoadOptions) -> Vec<String> {
+    let mut timelines = vec!["log_tick".to_owned(), "log_time".to_owned()];
+    if let Some(timepoint) = &options.timepoint {
+        timelines.extend(
+            timepoint
+                .iter()
+                .map(|(timeline, _)| timeline.name().to_string()),
+        );
+    }
+    if options.timeline == Some(TimeSource::Acquisition) {
+        timelines.push(ACQUISITION_TIMELINE.to_owned());
+    }
+    timelines
+}
+
+/// Formats 16 bytes as a hyphenated UUID, the form the viewer expects in entity paths.
+fn uuid_string(bytes: &[u8; 16]) -> String {
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
@@ -0,0 +1,179 @@
+//! Disk cache of what was logged of a file, for `--cache`.
+
+use crate::{LoadOptions, Stats, VisibleScans};
+use anyhow::{Context, Result};
+use rerun::external::re_log_encoding::decoder::Decoder;
+use rerun::log::{Chunk, LogMsg};
+use rerun::sink::{FileSink, LogSink};
+use rerun::{RecordingStream, RecordingStreamBuilder, StoreKind, VersionPolicy};
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// Name of the recording of the chunks in every entry of the cache, next to their stats.
+const RECORDING_FILE: &str = "recording.rrd";
+const STATS_FILE: &str = "stats.json";
+
+/// Logs the chunks that an earlier load of the file with the same options logged, from the
+/// entry `{cache_dir}/{key}` of the cache, or loads the file with `load` and stores what is
+/// logged in a new entry while it is forwarded to `rec`.
+///
+/// Cancelled loads are not stored, since they are missing points.
+pub(crate) fn load_cached(
+    path: &Path,
+    options: &LoadOptions,
+    rec: &RecordingStream,
+    cache_dir: &Path,
+    load: impl FnOnce(&RecordingStream) -> Result<Stats>,
+) -> Result<Stats> {
+    let entry = cache_dir.join(cache_key(path, options)?);
+    if entry.join(STATS_FILE).exists() {
+        let stats = std::fs::read(entry.join(STATS_FILE))
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(serde_json::from_slice::<Stats>(&bytes)?));
+        match stats {
+            Ok(stats) => {
+                replay(&entry.join(RECORDING_FILE), rec)?;
+                if !options.quiet {
+                    eprintln!("Loaded {path:?} from the cache in {entry:?}");
+                }
+                return Ok(stats);
+            }
+            Err(e) => eprintln!("Ignoring the cache entry {entry:?}: {e:#}"),
+        }
+    }
+
+    std::fs::create_dir_all(cache_dir)
+        .with_context(|| format!("Failed to create the cache directory: {cache_dir:?}"))?;
+    let staging = tempfile::tempdir_in(cache_dir)
+        .with_context(|| format!("Failed to create a cache entry in {cache_dir:?}"))?;
+    // Blueprints only apply to recordings of their application.
+    let application_id = rec
+        .store_info()
+        .map_or_else(|| "rerun_e57_loader".into(), |info| info.application_id);
+    let (enabled, store_info, batcher_config) =
+        RecordingStreamBuilder::new(application_id).into_args();
+    if !enabled {
+        return load(rec);
+    }
+    let sink = TeeSink {
+        file: FileSink::new(staging.path().join(RECORDING_FILE))?,
+        rec: rec.clone(),
+        blueprint: Mutex::default(),
+    };
+    let cached = RecordingStream::new(store_info, batcher_config, Box::new(sink))?;
+    let stats = load(&cached);
+    // Dropping the last handle of the recording closes its file.
+    cached.flush_blocking();
+    drop(cached);
+    let stats = stats?;
+
+    if !crate::is_cancelled(options) {
+        std::fs::write(staging.path().join(STATS_FILE), serde_json::to_vec(&stats)?)?;
+        // Another load may have stored the same entry in the meantime, which is kept.
+        let staging = staging.into_path();
+        if std::fs::rename(&staging, &entry).is_err() {
+            std::fs::remove_dir_all(&staging)?;
+        }
+    }
+    Ok(stats)
+}
+
+/// Returns the key of the cache entry of a file, which changes with its path, size and
+/// modification time, with the version of the loader, and with every option that changes
+/// what is logged.
+fn cache_key(path: &Path, options: &LoadOptions) -> Result<String> {
+    let metadata = std::fs::metadata(path).with_context(|| format!("Failed to read {path:?}"))?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+
+    // The debug output of sets depends on the order of their elements.
+    let sorted = |set: &HashSet<usize>| {
+        let mut indices: Vec<usize> = set.iter().copied().collect();
+        indices.sort_unstable();
+        indices
+    };
+    let scans = options.scans.as_ref().map(sorted);
+    let visible_scans = match &options.visible_scans {
+        Some(VisibleScans::Indices(indices)) => Some(sorted(indices)),
+        _ => None,
+    };
+    let logged = LoadOptions {
+        scans: None,
+        visible_scans: match options.visible_scans {
+            Some(VisibleScans::First(n)) => Some(VisibleScans::First(n)),
+            _ => None,
+        },
+        quiet: false,
+        cancel: None,
+        flush_every: None,
+        cache: None,
+        ..options.clone()
+    };
+
+    let mut hasher = DefaultHasher::new();
+    (
+        env!("CARGO_PKG_VERSION"),
+        path.canonicalize()?,
+        metadata.len(),
+        modified.as_nanos(),
+        scans,
+        visible_scans,
+        format!("{logged:?}"),
+    )
+        .hash(&mut hasher);
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+/// Logs the chunks of a recording file to `rec`, and sends its blueprint.
+fn replay(path: &Path, rec: &RecordingStream) -> Result<()> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open the cached recording: {path:?}"))?;
+    let decoder = Decoder::new(VersionPolicy::Error, BufReader::new(file))?;
+    let mut blueprint = Vec::new();
+    for msg in decoder {
+        forward(msg?, rec, &mut blueprint)?;
+    }
+    Ok(())
+}
+
+/// Logs a message of another recording to `rec`, where the messages of a blueprint are
+/// collected in `blueprint` until it is activated.
+fn forward(msg: LogMsg, rec: &RecordingStream, blueprint: &mut Vec<LogMsg>) -> Result<()> {
+    match msg {
+        LogMsg::BlueprintActivationCommand(activation) => {
+            rec.send_blueprint(std::mem::take(blueprint), activation);
+        }
+        msg if msg.store_id().kind == StoreKind::Blueprint => blueprint.push(msg),
+        LogMsg::ArrowMsg(_, msg) => rec.send_chunk(Chunk::from_arrow_msg(&msg)?),
+        // The recording of `rec` is described by its own store info.
+        LogMsg::SetStoreInfo(_) => {}
+    }
+    Ok(())
+}
+
+/// Sink of a new cache entry, which writes the messages to its file and forwards them to the
+/// recording the file is loaded for as they are logged, so the viewer shows the first scans
+/// before the whole file is decoded.
+struct TeeSink {
+    file: FileSink,
+    rec: RecordingStream,
+    blueprint: Mutex<Vec<LogMsg>>,
+}
+
+impl LogSink for TeeSink {
+    fn send(&self, msg: LogMsg) {
+        let mut blueprint = self.blueprint.lock().expect("blueprint poisoned");
+        if let Err(e) = forward(msg.clone(), &self.rec, &mut blueprint) {
+            eprintln!("Failed to forward a cached chunk: {e:#}");
+        }
+        self.file.send(msg);
+    }
+
+    fn flush_blocking(&self) {
+        self.file.flush_blocking();
+        self.rec.flush_blocking();
+    }
+}
@@ -0,0 +1,133 @@
+//! Colors for point classifications, following the ASPRS LAS class codes.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::Colormap;
+
+/// Name and color of the standard ASPRS classes 0 to 18.
+const CLASSES: [(&str, [u8; 3]); 19] = [
+    ("Never classified", [128, 128, 128]),
+    ("Unclassified", [192, 192, 192]),
+    ("Ground", [153, 102, 51]),
+    ("Low vegetation", [144, 238, 144]),
+    ("Medium vegetation", [34, 170, 34]),
+    ("High vegetation", [0, 100, 0]),
+    ("Building", [230, 80, 40]),
+    ("Low point (noise)", [255, 0, 255]),
+    ("Reserved", [96, 96, 96]),
+    ("Water", [30, 100, 230]),
+    ("Rail", [110, 70, 40]),
+    ("Road surface", [70, 70, 70]),
+    ("Reserved", [96, 96, 96]),
+    ("Wire guard", [255, 230, 0]),
+    ("Wire conductor", [255, 180, 0]),
+    ("Transmission tower", [200, 0, 0]),
+    ("Wire connector", [255, 140, 200]),
+    ("Bridge deck", [160, 120, 200]),
+    ("High noise", [255, 0, 128]),
+];
+
+/// Name and color of a class in a `--class-map` file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClassLabel {
+    name: String,
+    color: Option<[u8; 3]>,
+}
+
+/// Names and colors of the classes: the ASPRS classes, overridden by a JSON class map of the
+/// form `{"2": {"name": "Ground", "color": [153, 102, 51]}}`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ClassMap {
+    labels: BTreeMap<u8, ClassLabel>,
+}
+
+impl ClassMap {
+    /// Reads the class map at `path`, or returns the ASPRS classes if there is none.
+    pub fn read(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read class map: {path:?}"))?;
+        let labels =
+            serde_json::from_str(&json).with_context(|| format!("Invalid class map: {path:?}"))?;
+        Ok(Self { labels })
+    }
+
+    /// Returns the color of a class from the class map, or else from the colormap if given.
+    pub fn color(&self, class: u8, colormap: Option<Colormap>) -> rerun::Color {
+        match (
+            self.labels.get(&class).and_then(|label| label.color),
+            colormap,
+        ) {
+            (Some([r, g, b]), _) => rerun::Color::from_rgb(r, g, b),
+            (None, Some(colormap)) => colormap.label_color(class as u32),
+            (None, None) => class_color(class),
+        }
+    }
+
+    /// Returns the annotation context that names the ASPRS classes and those of the class map,
+    /// with the colors the points are logged with.
+    pub fn annotation_context(&self, colormap: Option<Colormap>) -> rerun::AnnotationContext {
+        let mut names: BTreeMap<u8, &str> = (0..)
+            .zip(CLASSES)
+            .map(|(class, (name, _))| (class, name))
+            .collect();
+        names.extend(
+            self.labels
+                .iter()
+                .map(|(class, label)| (*class, label.name.as_str())),
+        );
+        rerun::AnnotationContext::new(names.into_iter().map(|(class, name)| {
+            let color = self.color(class, colormap);
+            (class as u16, name, color.0)
+        }))
+    }
+}
+
+/// Returns the color of a class, spreading non-standard classes over the hue circle.
+fn class_color(class: u8) -> rerun::Color {
+    if let Some((_, [r, g, b])) = CLASSES.get(class as usize) {
+        return rerun::Color::from_rgb(*r, *g, *b);
+    }
+
+    // Golden-ratio steps keep neighboring class codes visually distinct.
+    let hue = (class as f32 * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    rerun::Color::from_rgb((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn overrides_classes_with_class_map() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            file,
+            r#"{{"2": {{"name": "Floor", "color": [1, 2, 3]}}, "40": {{"name": "Car"}}}}"#
+        )
+        .unwrap();
+        let classes = ClassMap::read(Some(file.path())).unwrap();
+
+        assert_eq!(classes.color(2, None), rerun::Color::from_rgb(1, 2, 3));
+        assert_eq!(classes.color(6, None), class_color(6));
+        assert_eq!(classes.color(40, None), class_color(40));
+        assert_eq!(classes.labels.len(), 2);
+        assert_eq!(classes.labels[&40].name, "Car");
+    }
+}
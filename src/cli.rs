@@ -0,0 +1,991 @@
+//! Command line arguments of the loader, with the defaults of the config files and the
+//! environment variables, and their conversion to `LoadOptions`.
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::{
+    parse_color_priority, parse_crop_box, parse_index_range, parse_min_max, parse_scan_selection,
+    parse_units, Center, Clustering, ColorBy, Colormap, CropFrame, EntityLayout, EntityTemplate,
+    FlushEvery, Handedness, InfoFormat, IntensityRange, InvalidPoints, LoadOptions,
+    NormalEstimation, NormalsMode, PointRadius, Precision, Returns, ScanOrigins, Section, Shade,
+    TimeSource, UpAxis, VisibleScans,
+};
+
+/// Command line arguments for the E57 Rerun data loader.
+#[derive(FromArgs, Debug)]
+#[argh(description = "Load E57 point clouds and stream them to Rerun")]
+pub struct Args {
+    #[argh(
+        positional,
+        description = "paths to the E57, PTS or PTX (or, with the las and ply features, LAS or PLY) files, or to a directory of E57 files"
+    )]
+    pub filepaths: Vec<PathBuf>,
+
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+
+    #[argh(
+        switch,
+        description = "also load the E57 files in subdirectories of a directory"
+    )]
+    pub recursive: bool,
+
+    #[argh(switch, description = "read the E57 file from stdin instead of a path")]
+    pub stdin: bool,
+
+    #[argh(option, description = "optional recommended ID for the application")]
+    pub application_id: Option<String>,
+
+    #[argh(
+        option,
+        description = "optional recommended ID for the application for existing applications"
+    )]
+    pub opened_application_id: Option<String>,
+
+    #[argh(option, description = "optional recommended ID for the recording")]
+    pub recording_id: Option<String>,
+
+    #[argh(
+        option,
+        description = "optional recommended ID for the recording for existing applications"
+    )]
+    pub opened_recording_id: Option<String>,
+
+    #[argh(option, description = "optional prefix for all entity paths")]
+    pub entity_path_prefix: Option<String>,
+
+    #[argh(
+        option,
+        description = "entities of the points: hierarchical (scan_N/chunk_M), flat (one scan_N/points entity with a row per chunk) or per-file (hierarchical, with every file at a root named after it) (default: hierarchical)"
+    )]
+    pub entity_layout: Option<EntityLayout>,
+
+    #[argh(
+        option,
+        description = "scans to load, as indices and ranges (e.g. --scans 0,2,5-9)"
+    )]
+    pub scans: Option<String>,
+
+    #[argh(
+        option,
+        description = "only load E57 scans whose name matches this regular expression, or contains this text (e.g. --scan-name '^Station [AB]$')"
+    )]
+    pub scan_name: Option<regex::Regex>,
+
+    #[argh(
+        option,
+        description = "only load the E57 scans with this GUID (can be repeated)"
+    )]
+    pub scan_guid: Vec<String>,
+
+    #[argh(
+        option,
+        description = "skip scans with fewer points than this, e.g. calibration targets or aborted scans (empty scans are always skipped)"
+    )]
+    pub min_points: Option<u64>,
+
+    #[argh(
+        option,
+        description = "log at most this many points of each file, taking a random subset of every scan in proportion to its size"
+    )]
+    pub max_points: Option<u64>,
+
+    #[argh(
+        switch,
+        description = "stream to a running Rerun viewer instead of stdout, over TCP, since this version of the Rerun SDK has no gRPC client"
+    )]
+    pub connect: bool,
+
+    #[argh(
+        option,
+        description = "address of the Rerun viewer to connect to over TCP, which implies --connect (default: 127.0.0.1:9876)"
+    )]
+    pub addr: Option<std::net::SocketAddr>,
+
+    #[argh(
+        option,
+        description = "write the recording to an .rrd file instead of stdout"
+    )]
+    pub save: Option<PathBuf>,
+
+    #[argh(
+        switch,
+        description = "color points by their intensity in grayscale instead of RGB (same as --color-by intensity)"
+    )]
+    pub intensity: bool,
+
+    #[argh(
+        option,
+        description = "point attribute used for coloring: rgb, intensity, classification, height, range, scan or return (default: rgb)"
+    )]
+    pub color_by: Option<ColorBy>,
+
+    #[argh(
+        option,
+        description = "attributes tried in order per scan, e.g. rgb,intensity,height, coloring every scan by the first one it has",
+        from_str_fn(parse_color_priority)
+    )]
+    pub color_priority: Option<Vec<ColorBy>>,
+
+    #[argh(
+        option,
+        description = "colormap for scalar attributes: grayscale, turbo, viridis or plasma (default: grayscale)"
+    )]
+    pub colormap: Option<Colormap>,
+
+    #[argh(
+        option,
+        from_str_fn(parse_min_max),
+        description = "height or range as min,max mapped to the ends of the colormap (default: scan bounds)"
+    )]
+    pub color_range: Option<(f64, f64)>,
+
+    #[argh(
+        switch,
+        description = "log every class of a classified scan as its own scan_N/class_K entity"
+    )]
+    pub split_by_class: bool,
+
+    #[argh(
+        switch,
+        description = "split the points of E57, LAS and PLY scans into scan_N/ground and scan_N/non_ground entities"
+    )]
+    pub segment_ground: bool,
+
+    #[argh(
+        option,
+        description = "detect the N dominant planes of every scan with RANSAC, and log them as thin boxes on scan_N/planes"
+    )]
+    pub detect_planes: Option<usize>,
+
+    #[argh(
+        option,
+        description = "split the points of every scan into scan_N/cluster_K entities with DBSCAN, given as <eps>:<min-pts>, e.g. 0.1:10"
+    )]
+    pub cluster: Option<Clustering>,
+
+    #[argh(
+        switch,
+        description = "log structured scans as a surface mesh on scan_N/mesh instead of their points (with the mesh feature)"
+    )]
+    pub mesh: bool,
+
+    #[argh(
+        switch,
+        description = "refine the poses of the scans of E57 files with point-to-plane ICP between overlapping scans, and log the residuals"
+    )]
+    pub refine_registration: bool,
+
+    #[argh(
+        switch,
+        description = "log a heatmap of how many scans have points in every 25 cm voxel, to find under-scanned areas"
+    )]
+    pub coverage_heatmap: bool,
+
+    #[argh(
+        option,
+        description = "only log a thin slab of file-level coordinates, and also log it projected to 2D on section_2d, given as plane=<x|y|z>:<offset>[,thickness=<m>] (default thickness: 0.05)"
+    )]
+    pub section: Option<Section>,
+
+    #[argh(
+        switch,
+        description = "log the oriented bounding boxes of the scans, or of their clusters with --cluster, labeled with their dimensions"
+    )]
+    pub measure: bool,
+
+    #[argh(
+        option,
+        description = "log a digital elevation model of the ground points of E57, LAS and PLY scans as a heightmap on dem/<prefix>, with cells of the given size in meters"
+    )]
+    pub dem: Option<f64>,
+
+    #[argh(
+        option,
+        description = "entity paths of the scans and their chunks, with the placeholders {{prefix}}, {{file}}, {{index}}, {{scan_name}}, {{guid}} and {{chunk}}, e.g. \"{{prefix}}/{{file}}/{{scan_name}}/{{chunk}}\" (default: {{prefix}}/scan_{{index}}/chunk_{{chunk}})"
+    )]
+    pub entity_template: Option<EntityTemplate>,
+
+    #[argh(
+        option,
+        description = "JSON file with the names and optional colors of class codes, used instead of the ASPRS classes"
+    )]
+    pub class_map: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "log horizontal slices of this thickness in meters as scan_N/slice_K entities, e.g. to show a building floor by floor"
+    )]
+    pub slice_z: Option<f64>,
+
+    #[argh(
+        option,
+        description = "log the merged points in square tiles of this size in meters as merged/tile_X_Y entities, so distant tiles can be hidden"
+    )]
+    pub tile_size: Option<f64>,
+
+    #[argh(
+        option,
+        description = "intensity normalization range: limits (from the E57 file), auto (computed from the points) or min,max (default: limits)"
+    )]
+    pub intensity_range: Option<IntensityRange>,
+
+    #[argh(
+        switch,
+        description = "log the raw intensity values as a custom component"
+    )]
+    pub log_intensity: bool,
+
+    #[argh(
+        switch,
+        description = "log the row and column indices of structured scans as custom components, to trace points back to the scanner grid"
+    )]
+    pub log_row_col: bool,
+
+    #[argh(
+        option,
+        description = "how to log per-point normals: skip, log (as a custom component) or arrows (default: skip)"
+    )]
+    pub normals: Option<NormalsMode>,
+
+    #[argh(
+        option,
+        description = "vendor-specific E57 attribute to log as a custom rerun_e57.<name> component, optionally prefixed with its namespace (e.g. riegl:reflectance), can be repeated"
+    )]
+    pub extra_field: Vec<String>,
+
+    #[argh(
+        option,
+        description = "estimate missing normals from the k nearest neighbors (an integer) or from the neighbors within a radius in meters (e.g. 0.05), implies --normals log"
+    )]
+    pub estimate_normals: Option<NormalEstimation>,
+
+    #[argh(
+        switch,
+        description = "ignore the scan poses and show every scan in its local scanner frame"
+    )]
+    pub no_transform: bool,
+
+    #[argh(
+        switch,
+        description = "apply the scan poses to the points and log the points of all scans together as the merged entity"
+    )]
+    pub merge_scans: bool,
+
+    #[argh(
+        option,
+        description = "vertical axis of the coordinates in the file: z or y (default: z)"
+    )]
+    pub up_axis: Option<UpAxis>,
+
+    #[argh(
+        option,
+        description = "handedness of the coordinates in the file: right or left (default: right)"
+    )]
+    pub handedness: Option<Handedness>,
+
+    #[argh(
+        option,
+        description = "multiply all coordinates of the file by this factor, for files not in meters"
+    )]
+    pub scale: Option<f64>,
+
+    #[argh(
+        option,
+        from_str_fn(parse_units),
+        description = "units of the coordinates in the file: mm, cm, m or ft (default: m)"
+    )]
+    pub units: Option<f64>,
+
+    #[argh(
+        option,
+        description = "subtract this offset in meters from all coordinates, or the center of the bounds with auto, to keep the precision of geo-referenced files"
+    )]
+    pub center: Option<Center>,
+
+    #[argh(switch, description = "do not log the images embedded in the E57 file")]
+    pub no_images: bool,
+
+    #[argh(
+        switch,
+        description = "log structured scans with row/column indices as a depth image"
+    )]
+    pub as_depth_image: bool,
+
+    #[argh(
+        switch,
+        description = "log the colors of structured scans as an equirectangular panorama image"
+    )]
+    pub reconstruct_panorama: bool,
+
+    #[argh(
+        option,
+        description = "bake shading into the point colors of structured E57 scans: range-gradient, lit from the scanner with normals from the range image"
+    )]
+    pub shade: Option<Shade>,
+
+    #[argh(
+        switch,
+        description = "do not log the points, e.g. together with --as-depth-image"
+    )]
+    pub no_points: bool,
+
+    #[argh(
+        option,
+        description = "returns of multi-return scans that are loaded: all, first or last (default: all)"
+    )]
+    pub returns: Option<Returns>,
+
+    #[argh(switch, description = "log the bounding box of every scan")]
+    pub bounds: bool,
+
+    #[argh(
+        switch,
+        description = "log histograms of the ranges and intensities of every scan as bar charts, to assess the data quality"
+    )]
+    pub stats: bool,
+
+    #[argh(
+        option,
+        description = "how the scanner position of posed scans is shown: point (a red point), axes (arrows along the axes of the scan) or none (default: point)"
+    )]
+    pub show_scan_origins: Option<ScanOrigins>,
+
+    #[argh(
+        switch,
+        description = "connect the scanner positions of the posed scans in the order of their acquisition, e.g. to show the path of a mobile capture"
+    )]
+    pub show_trajectory: bool,
+
+    #[argh(
+        option,
+        description = "radius of the logged points in meters (e.g. 0.02) or in UI points (e.g. 2ui)"
+    )]
+    pub point_radius: Option<PointRadius>,
+
+    #[argh(
+        switch,
+        description = "color every scan with a distinct color of a fixed palette, with --color-by scan or when it has neither colors nor intensities"
+    )]
+    pub scan_palette: bool,
+
+    #[argh(
+        switch,
+        description = "round the logged colors to 32 levels per channel, for smaller saved recordings"
+    )]
+    pub quantize_colors: bool,
+
+    #[argh(
+        option,
+        description = "precision of the logged intensities and extra fields: f32 (default) or f16, for smaller saved recordings"
+    )]
+    pub precision: Option<Precision>,
+
+    #[argh(
+        option,
+        from_str_fn(parse_crop_box),
+        description = "keep only the points inside the box xmin,ymin,zmin,xmax,ymax,zmax in meters"
+    )]
+    pub crop_box: Option<([f64; 3], [f64; 3])>,
+
+    #[argh(
+        option,
+        description = "drop points closer than this distance in meters"
+    )]
+    pub min_range: Option<f64>,
+
+    #[argh(
+        option,
+        description = "drop points farther than this distance in meters"
+    )]
+    pub max_range: Option<f64>,
+
+    #[argh(
+        option,
+        description = "frame of --crop-box, --min-range and --max-range: world (file-level coordinates) or scanner (default: world)"
+    )]
+    pub crop_frame: Option<CropFrame>,
+
+    #[argh(
+        option,
+        from_str_fn(parse_index_range),
+        description = "only log the rows from a to b (exclusive) of structured scans, as a..b, a.. or ..b"
+    )]
+    pub rows: Option<std::ops::Range<i64>>,
+
+    #[argh(
+        option,
+        from_str_fn(parse_index_range),
+        description = "only log the columns from c to d (exclusive) of structured scans, as c..d, c.. or ..d"
+    )]
+    pub cols: Option<std::ops::Range<i64>>,
+
+    #[argh(
+        option,
+        description = "what to do with points without a valid position: skip, zero (log them at the scanner) or log-separately (as scan_N/invalid) (default: skip)"
+    )]
+    pub invalid_points: Option<InvalidPoints>,
+
+    #[argh(
+        option,
+        description = "number of scans to decode in parallel (default: number of CPU cores)"
+    )]
+    pub threads: Option<usize>,
+
+    #[argh(
+        option,
+        description = "downsample each scan to one point per voxel of the given size in meters"
+    )]
+    pub voxel_size: Option<f64>,
+
+    #[argh(
+        option,
+        description = "randomly keep only the given fraction of points (0..1)"
+    )]
+    pub subsample: Option<f64>,
+
+    #[argh(option, description = "keep only every n-th point of each scan")]
+    pub every_nth: Option<usize>,
+
+    #[argh(
+        option,
+        description = "seed for --subsample to get reproducible results"
+    )]
+    pub seed: Option<u64>,
+
+    #[argh(
+        option,
+        description = "log an octree level-of-detail pyramid with the given number of levels as scan_N/lod_K"
+    )]
+    pub lod_levels: Option<u32>,
+
+    #[argh(
+        option,
+        description = "number of points per logged chunk (default: 1000000)"
+    )]
+    pub chunk_size: Option<usize>,
+
+    #[argh(
+        option,
+        description = "approximate size of each logged chunk in megabytes, instead of --chunk-size"
+    )]
+    pub chunk_mb: Option<usize>,
+
+    #[argh(
+        option,
+        description = "upper bound in megabytes for the buffered points of all workers"
+    )]
+    pub max_memory: Option<usize>,
+
+    #[argh(
+        option,
+        description = "write the points to a .pcd or .las file instead of streaming them to Rerun"
+    )]
+    pub export: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "print the scans, attributes, bounds, poses and images of the E57 files as a table or json instead of loading them"
+    )]
+    pub info: Option<InfoFormat>,
+
+    #[argh(
+        switch,
+        description = "check the checksums of all pages of the E57 files first and report the corrupt sections with their byte offsets"
+    )]
+    pub verify: bool,
+
+    #[argh(
+        switch,
+        description = "read the E57 files through a memory map instead of buffered reads, which is faster for large files on SSDs"
+    )]
+    pub mmap: bool,
+
+    #[argh(switch, description = "do not print progress to stderr")]
+    pub quiet: bool,
+
+    #[argh(
+        option,
+        description = "write a JSON report of the loaded scans, with their logged and skipped points, bounds and durations, to a file or to stdout with -"
+    )]
+    pub summary_json: Option<PathBuf>,
+
+    #[argh(
+        switch,
+        description = "send a blueprint with a 3D view of all scans and larger default points"
+    )]
+    pub send_blueprint: bool,
+
+    #[argh(
+        option,
+        description = "scans that are visible in the blueprint of --send-blueprint, as a number of first scans (e.g. 3) or a selection (e.g. 0,2,5-9), while the others load hidden"
+    )]
+    pub visible_scans: Option<VisibleScans>,
+
+    #[argh(
+        switch,
+        description = "log every 100th point of all E57 scans first, as a preview that the full resolution points replace"
+    )]
+    pub preview_first: bool,
+
+    #[argh(
+        switch,
+        description = "log the decoded records, logged points and logged bytes per second as scalars, to find the bottleneck of a load"
+    )]
+    pub log_perf: bool,
+
+    #[argh(
+        switch,
+        description = "skip the scans that a load with the same --recording-id already logged, as recorded in a .resume.json file next to every input file"
+    )]
+    pub resume: bool,
+
+    #[argh(
+        switch,
+        description = "store what is logged of every file in ~/.cache/rerun-e57-loader, and replay it from there when the file is loaded again with the same options"
+    )]
+    pub cache: bool,
+
+    #[argh(
+        switch,
+        description = "skip the E57 scans of a directory or of several files that were already logged from another file, by their GUID"
+    )]
+    pub dedup_scans: bool,
+
+    #[argh(
+        switch,
+        description = "log the E57 scans taken from the same scanner position under a common station_N entity"
+    )]
+    pub group_stations: bool,
+
+    #[argh(
+        option,
+        description = "flush the recording every given number of chunks (e.g. 10) or seconds (e.g. 2s), for a lower latency when streaming over a network"
+    )]
+    pub flush_every: Option<FlushEvery>,
+
+    #[argh(
+        option,
+        description = "milliseconds after which the batcher of the recording sends the logged data at the latest (default: 8)"
+    )]
+    pub batch_flush_ms: Option<u64>,
+
+    #[argh(
+        option,
+        description = "size in MiB at which the batcher of the recording sends the logged data (default: 1)"
+    )]
+    pub batch_max_mb: Option<u64>,
+
+    #[argh(
+        switch,
+        description = "exit with an error if any scan or point failed to decode"
+    )]
+    pub strict: bool,
+
+    #[argh(
+        switch,
+        description = "keep running and load the files again whenever they change"
+    )]
+    pub watch: bool,
+
+    #[argh(
+        arg_name = "static",
+        switch,
+        description = "optionally mark data to be logged statically"
+    )]
+    pub static_: bool,
+
+    #[argh(
+        option,
+        description = "optional timestamps to log at (e.g. --time sim_time=1709203426)"
+    )]
+    pub time: Vec<String>,
+
+    #[argh(
+        option,
+        description = "optional sequences to log at (e.g. --sequence sim_frame=42)"
+    )]
+    pub sequence: Vec<String>,
+
+    #[argh(
+        option,
+        description = "also log each scan at a time from the file: acquisition (the acquisition start of E57 scans and images)"
+    )]
+    pub timeline: Option<TimeSource>,
+
+    #[argh(
+        option,
+        description = "optionally log the points of E57 scans with time stamps in time windows of this many seconds on the acquisition timeline"
+    )]
+    pub per_point_time: Option<f64>,
+
+    #[argh(
+        switch,
+        description = "ignore the rerun-e57-loader.toml config files next to the files and in the XDG config directory"
+    )]
+    pub no_config: bool,
+}
+
+impl Args {
+    /// Parses the command line, with the options of the environment variables and then of the
+    /// config files as defaults for those it does not set.
+    pub fn parse() -> Result<Self> {
+        let args: Self = argh::from_env();
+        let command_line: Vec<String> = env::args().collect();
+        let (command, options) = command_line.split_first().context("Missing command name")?;
+        let mut defaults = toml::Table::new();
+        if !args.no_config {
+            for path in config_paths(&args.paths()) {
+                let text = match std::fs::read_to_string(&path) {
+                    Ok(text) => text,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e).with_context(|| format!("Failed to read {path:?}")),
+                };
+                let config: toml::Table = text
+                    .parse()
+                    .with_context(|| format!("Failed to parse {path:?}"))?;
+                defaults.extend(config);
+            }
+        }
+        defaults.extend(env_options());
+        if defaults.is_empty() {
+            return Ok(args);
+        }
+
+        let mut with_defaults = config_args(&defaults, options)?;
+        with_defaults.extend(options.iter().cloned());
+        let with_defaults: Vec<&str> = with_defaults.iter().map(String::as_str).collect();
+        let with_defaults = Self::from_args(&[command], &with_defaults).map_err(|exit| {
+            anyhow::anyhow!(
+                "Invalid option in {ENV_PREFIX}* or {CONFIG_FILE_NAME}: {}",
+                exit.output
+            )
+        })?;
+        // A value of a switch would be taken as another file.
+        anyhow::ensure!(
+            with_defaults.filepaths == args.filepaths && with_defaults.command == args.command,
+            "Switches in {ENV_PREFIX}* or {CONFIG_FILE_NAME} must be true or false"
+        );
+        Ok(with_defaults)
+    }
+
+    /// Returns the paths of the files to load, or to compare with `diff`.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        match &self.command {
+            Some(Command::Diff(diff)) => vec![diff.before.clone(), diff.after.clone()],
+            None => self.filepaths.clone(),
+        }
+    }
+
+    /// Returns the options of a load that `cancel` cancels, from the arguments that are not
+    /// about the recording stream.
+    pub fn load_options(&self, cancel: Option<Arc<AtomicBool>>) -> Result<LoadOptions> {
+        Ok(LoadOptions {
+            entity_path_prefix: self.entity_path_prefix.clone(),
+            entity_layout: match (self.entity_layout, &self.entity_path_prefix) {
+                (Some(EntityLayout::PerFile), Some(_)) => anyhow::bail!(
+                    "--entity-layout per-file cannot be combined with --entity-path-prefix"
+                ),
+                (layout, _) => layout.unwrap_or_default(),
+            },
+            scans: self
+                .scans
+                .as_deref()
+                .map(|scans| parse_scan_selection(scans).context("Invalid scan selection"))
+                .transpose()?,
+            scan_name: self.scan_name.clone(),
+            scan_guids: self.scan_guid.clone(),
+            min_points: self.min_points,
+            max_points: self.max_points,
+            color_by: match (self.color_by, self.intensity) {
+                (Some(_), true) => {
+                    anyhow::bail!("--intensity and --color-by cannot be used together")
+                }
+                (Some(color_by), false) => color_by,
+                (None, true) => ColorBy::Intensity,
+                (None, false) => ColorBy::Rgb,
+            },
+            color_priority: match self.color_priority.clone() {
+                Some(_) if self.color_by.is_some() || self.intensity => {
+                    anyhow::bail!(
+                        "--color-priority cannot be combined with --color-by or --intensity"
+                    )
+                }
+                priority => priority,
+            },
+            colormap: self.colormap,
+            color_range: self.color_range,
+            split_by_class: self.split_by_class,
+            segment_ground: self.segment_ground,
+            detect_planes: self.detect_planes,
+            cluster: self.cluster,
+            mesh: self.mesh,
+            refine_registration: self.refine_registration,
+            coverage_heatmap: self.coverage_heatmap,
+            section: self.section,
+            measure: self.measure,
+            dem: self.dem,
+            entity_template: self.entity_template.clone(),
+            class_map: self.class_map.clone(),
+            slice_z: self.slice_z,
+            tile_size: self.tile_size,
+            intensity_range: self.intensity_range.unwrap_or_default(),
+            log_intensity: self.log_intensity,
+            log_row_col: self.log_row_col,
+            normals: self.normals.unwrap_or(if self.estimate_normals.is_some() {
+                NormalsMode::Log
+            } else {
+                NormalsMode::Skip
+            }),
+            extra_fields: self.extra_field.clone(),
+            estimate_normals: self.estimate_normals,
+            no_transform: self.no_transform,
+            merge_scans: self.merge_scans,
+            up_axis: self.up_axis.unwrap_or_default(),
+            handedness: self.handedness.unwrap_or_default(),
+            scale: match (self.scale, self.units) {
+                (Some(_), Some(_)) => anyhow::bail!("--scale and --units cannot be used together"),
+                (scale, units) => scale.or(units),
+            },
+            center: self.center,
+            no_images: self.no_images,
+            depth_image: self.as_depth_image,
+            reconstruct_panorama: self.reconstruct_panorama,
+            shade: self.shade,
+            no_points: self.no_points,
+            returns: self.returns.unwrap_or_default(),
+            bounds: self.bounds,
+            stats: self.stats,
+            show_scan_origins: self.show_scan_origins.unwrap_or_default(),
+            show_trajectory: self.show_trajectory,
+            point_radius: self.point_radius,
+            scan_palette: self.scan_palette,
+            quantize_colors: self.quantize_colors,
+            precision: self.precision.unwrap_or_default(),
+            crop_box: self.crop_box,
+            min_range: self.min_range,
+            max_range: self.max_range,
+            crop_frame: self.crop_frame.unwrap_or_default(),
+            rows: self.rows.clone(),
+            columns: self.cols.clone(),
+            invalid_points: self.invalid_points.unwrap_or_default(),
+            voxel_size: self.voxel_size,
+            subsample: self.subsample,
+            every_nth: self.every_nth,
+            seed: self.seed,
+            lod_levels: self.lod_levels,
+            chunk_size: self.chunk_size,
+            chunk_bytes: self.chunk_mb.map(|mb| mb * 1024 * 1024),
+            max_memory: self.max_memory.map(|mb| mb * 1024 * 1024),
+            static_: self.static_,
+            timepoint: timepoint(&self.time, &self.sequence)?,
+            // Per-point time windows are shown on the acquisition timeline, next to their scans.
+            timeline: self
+                .timeline
+                .or(self.per_point_time.map(|_| TimeSource::Acquisition)),
+            per_point_time: self.per_point_time,
+            verify: self.verify,
+            mmap: match (self.mmap, self.watch) {
+                (true, true) => anyhow::bail!("--mmap and --watch cannot be used together"),
+                (mmap, _) => mmap,
+            },
+            quiet: self.quiet,
+            send_blueprint: self.send_blueprint,
+            visible_scans: self.visible_scans.clone(),
+            preview_first: self.preview_first,
+            log_perf: self.log_perf,
+            cancel,
+            resume: self.resume,
+            dedup_scans: self.dedup_scans,
+            group_stations: self.group_stations,
+            flush_every: self.flush_every,
+            cache: match self.cache {
+                true => Some(cache_dir().context("No cache directory, since $HOME is not set")?),
+                false => None,
+            },
+            export: self.export.clone(),
+        })
+    }
+}
+
+/// Commands that do something else than loading the files.
+#[derive(FromArgs, Debug, PartialEq)]
+#[argh(subcommand)]
+pub enum Command {
+    Diff(DiffArgs),
+}
+
+/// Arguments of `diff`, after the loading options that apply to both files.
+#[derive(FromArgs, Debug, PartialEq)]
+#[argh(
+    subcommand,
+    name = "diff",
+    description = "log the changes between two point cloud files instead of loading them, with the loading options before diff"
+)]
+pub struct DiffArgs {
+    #[argh(positional, description = "path of the file before the changes")]
+    pub before: PathBuf,
+
+    #[argh(positional, description = "path of the file after the changes")]
+    pub after: PathBuf,
+}
+
+/// Name of the config files with default options, next to the loaded files or in the XDG
+/// config directory.
+const CONFIG_FILE_NAME: &str = "rerun-e57-loader.toml";
+
+/// Prefix of the environment variables that set options, e.g. `RERUN_E57_VOXEL_SIZE=0.05` for
+/// `--voxel-size 0.05`.
+const ENV_PREFIX: &str = "RERUN_E57_";
+
+/// Returns the options set with `RERUN_E57_*` environment variables, keyed like the config
+/// files, e.g. `chunk-size` for `RERUN_E57_CHUNK_SIZE`. Switches are set with `true`.
+fn env_options() -> toml::Table {
+    env::vars_os()
+        .filter_map(|(name, value)| {
+            let option = name
+                .to_str()?
+                .strip_prefix(ENV_PREFIX)?
+                .to_lowercase()
+                .replace('_', "-");
+            // The variable that predates the others is named after the viewer.
+            let option = match option.as_str() {
+                "display-scans" => "scans".to_owned(),
+                _ => option,
+            };
+            let value = match value.into_string().ok()?.as_str() {
+                "true" => toml::Value::Boolean(true),
+                "false" => toml::Value::Boolean(false),
+                value => toml::Value::String(value.to_owned()),
+            };
+            Some((option, value))
+        })
+        .collect()
+}
+
+/// Returns the config files in increasing priority: the one in the XDG config directory, and
+/// the one next to the first file, or in the first directory.
+fn config_paths(filepaths: &[PathBuf]) -> Vec<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    let file_dir = filepaths.first().and_then(|path| match path.is_dir() {
+        true => Some(path.as_path()),
+        false => path.parent(),
+    });
+    config_dir
+        .into_iter()
+        .chain(file_dir.map(Path::to_owned))
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .collect()
+}
+
+/// Returns the directory of `--cache`, in the XDG cache directory.
+fn cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .map(|dir| dir.join("rerun-e57-loader"))
+}
+
+/// Converts the keys of a config file to command line options, e.g. `chunk-size = 500000` to
+/// `--chunk-size 500000`, skipping those given on the command line. Switches are set with
+/// `true`, and repeated options with arrays.
+fn config_args(table: &toml::Table, command_line: &[String]) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    for (key, value) in table {
+        let option = format!("--{key}");
+        if command_line.contains(&option) {
+            continue;
+        }
+        let values = match value {
+            toml::Value::Array(values) => values.as_slice(),
+            value => std::slice::from_ref(value),
+        };
+        for value in values {
+            match value {
+                toml::Value::Boolean(true) => args.push(option.clone()),
+                toml::Value::Boolean(false) => {}
+                toml::Value::String(value) => args.extend([option.clone(), value.clone()]),
+                toml::Value::Integer(value) => args.extend([option.clone(), value.to_string()]),
+                toml::Value::Float(value) => args.extend([option.clone(), value.to_string()]),
+                _ => anyhow::bail!("Invalid value of {key} in {CONFIG_FILE_NAME}: {value}"),
+            }
+        }
+    }
+    Ok(args)
+}
+
+/// Builds the timepoint to log at from the `--time` and `--sequence` arguments.
+fn timepoint(times: &[String], sequences: &[String]) -> Result<Option<rerun::TimePoint>> {
+    if times.is_empty() && sequences.is_empty() {
+        return Ok(None);
+    }
+
+    let mut timepoint = rerun::TimePoint::default();
+
+    for time_str in times {
+        let (timeline_name, time) = time_str
+            .split_once('=')
+            .with_context(|| format!("Invalid time argument: {time_str}"))?;
+        let time = time
+            .parse::<i64>()
+            .with_context(|| format!("Invalid time value: {time}"))?;
+
+        timepoint.insert(rerun::Timeline::new_temporal(timeline_name), time);
+    }
+
+    for seq_str in sequences {
+        let (seqline_name, seq) = seq_str
+            .split_once('=')
+            .with_context(|| format!("Invalid sequence argument: {seq_str}"))?;
+        let seq = seq
+            .parse::<i64>()
+            .with_context(|| format!("Invalid sequence value: {seq}"))?;
+
+        timepoint.insert(rerun::Timeline::new_sequence(seqline_name), seq);
+    }
+
+    Ok(Some(timepoint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_config_to_options() {
+        let table: toml::Table = r#"
+            chunk-size = 500000
+            colormap = "viridis"
+            subsample = 0.5
+            send-blueprint = true
+            quiet = false
+            extra-field = ["reflectance", "deviation"]
+        "#
+        .parse()
+        .unwrap();
+        let command_line = ["--colormap".to_owned(), "turbo".to_owned()];
+        assert_eq!(
+            config_args(&table, &command_line).unwrap(),
+            [
+                "--chunk-size",
+                "500000",
+                "--extra-field",
+                "reflectance",
+                "--extra-field",
+                "deviation",
+                "--send-blueprint",
+                "--subsample",
+                "0.5",
+            ]
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Density-based clustering of the points of a scan with DBSCAN, for `--cluster`.
+
+use glam::DVec3;
+use std::collections::HashMap;
+
+/// Cluster of a point, or noise if it is in none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum ClusterId {
+    Cluster(usize),
+    Noise,
+}
+
+/// Assigns every point to a cluster of points that are connected by neighbors within `eps`,
+/// where every point with at least `min_points` neighbors, itself included, is a core point.
+///
+/// Clusters are numbered by decreasing size, so `Cluster(0)` is the largest one.
+pub(crate) fn dbscan(points: &[DVec3], eps: f64, min_points: usize) -> Vec<ClusterId> {
+    // Neighbors within `eps` are at most one cell of size `eps` away on every axis.
+    let cell = |p: DVec3| (p / eps).floor().as_i64vec3();
+    let mut cells: HashMap<glam::I64Vec3, Vec<usize>> = HashMap::new();
+    for (index, point) in points.iter().enumerate() {
+        cells.entry(cell(*point)).or_default().push(index);
+    }
+    let neighbors = |index: usize| {
+        let (point, center) = (points[index], cell(points[index]));
+        let mut neighbors: Vec<usize> = Vec::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let Some(indices) = cells.get(&(center + glam::I64Vec3::new(x, y, z))) else {
+                        continue;
+                    };
+                    neighbors.extend(
+                        indices
+                            .iter()
+                            .copied()
+                            .filter(|i| points[*i].distance_squared(point) <= eps * eps),
+                    );
+                }
+            }
+        }
+        neighbors
+    };
+
+    let mut labels: Vec<Option<usize>> = vec![None; points.len()];
+    let mut visited = vec![false; points.len()];
+    let mut sizes = Vec::new();
+    for start in 0..points.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let start_neighbors = neighbors(start);
+        if start_neighbors.len() < min_points {
+            continue;
+        }
+        let cluster = sizes.len();
+        sizes.push(0);
+        let mut stack = start_neighbors;
+        labels[start] = Some(cluster);
+        sizes[cluster] += 1;
+        while let Some(index) = stack.pop() {
+            if labels[index].is_none() {
+                labels[index] = Some(cluster);
+                sizes[cluster] += 1;
+            }
+            // Border points join the cluster, but only core points grow it.
+            if !visited[index] {
+                visited[index] = true;
+                let more = neighbors(index);
+                if more.len() >= min_points {
+                    stack.extend(more.into_iter().filter(|i| labels[*i].is_none()));
+                }
+            }
+        }
+    }
+
+    let mut by_size: Vec<usize> = (0..sizes.len()).collect();
+    by_size.sort_by_key(|cluster| std::cmp::Reverse(sizes[*cluster]));
+    let mut rank = vec![0; sizes.len()];
+    for (r, cluster) in by_size.into_iter().enumerate() {
+        rank[cluster] = r;
+    }
+    labels
+        .into_iter()
+        .map(|label| {
+            label.map_or(ClusterId::Noise, |cluster| {
+                ClusterId::Cluster(rank[cluster])
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separates_clusters_and_noise() {
+        // A line of 10 points and a line of 5 points 1 m apart, and a lone point.
+        let mut points: Vec<DVec3> = (0..10)
+            .map(|i| DVec3::new(i as f64 * 0.1, 0.0, 0.0))
+            .collect();
+        points.extend((0..5).map(|i| DVec3::new(i as f64 * 0.1, 1.0, 0.0)));
+        points.push(DVec3::new(5.0, 5.0, 5.0));
+        let labels = dbscan(&points, 0.15, 3);
+        assert!(labels[..10].iter().all(|l| *l == ClusterId::Cluster(0)));
+        assert!(labels[10..15].iter().all(|l| *l == ClusterId::Cluster(1)));
+        assert_eq!(labels[15], ClusterId::Noise);
+    }
+}
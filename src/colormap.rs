@@ -0,0 +1,186 @@
+//! Colormaps for scalar point attributes.
+
+/// Colormap used to turn a normalized scalar value into a color.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Colormap {
+    #[default]
+    Grayscale,
+    Turbo,
+    Viridis,
+    Plasma,
+}
+
+impl std::str::FromStr for Colormap {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "grayscale" | "gray" => Ok(Self::Grayscale),
+            "turbo" => Ok(Self::Turbo),
+            "viridis" => Ok(Self::Viridis),
+            "plasma" => Ok(Self::Plasma),
+            _ => Err(format!("Invalid colormap: {s}")),
+        }
+    }
+}
+
+/// Polynomial fit of the Turbo colormap, from <https://gist.github.com/mikhailov-work/0d177465a8151eb6ede1768d51d476c7>.
+const TURBO: [[f32; 6]; 3] = [
+    [
+        0.135_721_38,
+        4.615_392_6,
+        -42.660_324,
+        132.131_08,
+        -152.942_4,
+        59.286_38,
+    ],
+    [
+        0.091_402_61,
+        2.194_188_4,
+        4.842_966_6,
+        -14.185_033,
+        4.277_298_6,
+        2.829_566,
+    ],
+    [
+        0.106_673_3,
+        12.641_946,
+        -60.582_05,
+        110.362_77,
+        -89.903_11,
+        27.348_25,
+    ],
+];
+
+/// Polynomial fit of the Viridis colormap, from <https://www.shadertoy.com/view/WlfXRN>.
+const VIRIDIS: [[f32; 7]; 3] = [
+    [
+        0.277_727_33,
+        0.105_093_04,
+        -0.330_861_8,
+        -4.634_230_6,
+        6.228_27,
+        4.776_385,
+        -5.435_456,
+    ],
+    [
+        0.005_407_344_5,
+        1.404_613_5,
+        0.214_847_56,
+        -5.799_101,
+        14.179_933,
+        -13.745_145,
+        4.645_852_6,
+    ],
+    [
+        0.334_099_8,
+        1.384_590_2,
+        0.095_095_16,
+        -19.332_441,
+        56.690_55,
+        -65.353_03,
+        26.312_435,
+    ],
+];
+
+/// Polynomial fit of the Plasma colormap, from <https://www.shadertoy.com/view/WlfXRN>.
+const PLASMA: [[f32; 7]; 3] = [
+    [
+        0.058_732_344,
+        2.176_514_6,
+        -2.689_460_5,
+        6.130_348,
+        -11.107_436,
+        10.023_066,
+        -3.658_714,
+    ],
+    [
+        0.023_336_709,
+        0.238_383_42,
+        -7.455_851,
+        42.346_19,
+        -82.666_31,
+        71.413_62,
+        -22.931_535,
+    ],
+    [
+        0.543_340_2,
+        0.753_960_46,
+        3.110_8,
+        -28.518_854,
+        60.139_847,
+        -54.072_186,
+        18.191_908,
+    ],
+];
+
+/// Evaluates a polynomial with the given coefficients, starting at the constant term.
+fn polynomial(coefficients: &[f32], t: f32) -> f32 {
+    coefficients.iter().rev().fold(0.0, |acc, c| acc * t + c)
+}
+
+impl Colormap {
+    /// Returns the color for a value in `0..=1`; values outside are clamped.
+    pub fn color(self, t: f32) -> rerun::Color {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let rgb = match self {
+            Self::Grayscale => [t; 3],
+            Self::Turbo => TURBO.map(|c| polynomial(&c, t)),
+            Self::Viridis => VIRIDIS.map(|c| polynomial(&c, t)),
+            Self::Plasma => PLASMA.map(|c| polynomial(&c, t)),
+        };
+        let [r, g, b] = rgb.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+        rerun::Color::from_rgb(r, g, b)
+    }
+
+    /// Returns a color for a discrete label, spreading neighboring labels across the colormap.
+    pub fn label_color(self, label: u32) -> rerun::Color {
+        self.color((label as f32 * 0.618_034).fract())
+    }
+}
+
+/// Categorical palette of `--scan-palette`, from Tableau 10, whose colors stay apart from each
+/// other in any order.
+const SCAN_PALETTE: [[u8; 3]; 10] = [
+    [78, 121, 167],
+    [242, 142, 43],
+    [225, 87, 89],
+    [118, 183, 178],
+    [89, 161, 79],
+    [237, 201, 72],
+    [176, 122, 161],
+    [255, 157, 167],
+    [156, 117, 95],
+    [186, 176, 172],
+];
+
+/// Returns the palette color of a scan, which only depends on its index and repeats after the
+/// size of the palette.
+pub(crate) fn scan_color(index: usize) -> rerun::Color {
+    let [r, g, b] = SCAN_PALETTE[index % SCAN_PALETTE.len()];
+    rerun::Color::from_rgb(r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_endpoints_to_known_colors() {
+        assert_eq!(
+            Colormap::Grayscale.color(0.0),
+            rerun::Color::from_rgb(0, 0, 0)
+        );
+        assert_eq!(
+            Colormap::Grayscale.color(2.0),
+            rerun::Color::from_rgb(255, 255, 255)
+        );
+        // Viridis runs from dark purple to yellow.
+        assert_eq!(
+            Colormap::Viridis.color(0.0),
+            rerun::Color::from_rgb(71, 1, 85)
+        );
+        let [r, g, b, _] = Colormap::Viridis.color(1.0).to_array();
+        assert!(r > 240 && g > 220 && b < 60);
+    }
+}
@@ -0,0 +1,94 @@
+//! Coverage of the survey by its scans, for `--coverage-heatmap`.
+
+use glam::{DMat3, DVec3};
+use std::collections::HashMap;
+
+use crate::Colormap;
+
+/// Size in meters of the voxels that coverage is counted in.
+const VOXEL: f64 = 0.25;
+
+/// Voxels that the points of a single scan are in, with the sum and number of their
+/// file-level positions in meters.
+#[derive(Default)]
+pub(crate) struct ScanCoverage {
+    voxels: HashMap<[i64; 3], (DVec3, u64)>,
+}
+
+impl ScanCoverage {
+    pub fn insert(&mut self, position: DVec3) {
+        let voxel = (position / VOXEL).floor().as_i64vec3().to_array();
+        let (sum, count) = self.voxels.entry(voxel).or_default();
+        *sum += position;
+        *count += 1;
+    }
+}
+
+/// Number of scans with points in every voxel of all scans.
+#[derive(Default)]
+pub(crate) struct Coverage {
+    voxels: HashMap<[i64; 3], Voxel>,
+}
+
+#[derive(Default)]
+struct Voxel {
+    sum: DVec3,
+    points: u64,
+    scans: u32,
+}
+
+impl Coverage {
+    pub fn add(&mut self, scan: ScanCoverage) {
+        for (key, (sum, points)) in scan.voxels {
+            let voxel = self.voxels.entry(key).or_default();
+            voxel.sum += sum;
+            voxel.points += points;
+            voxel.scans += 1;
+        }
+    }
+
+    /// Returns the centroid of every voxel relative to `center` in the axes of the prefix,
+    /// colored with the turbo colormap from one scan to the most scans of any voxel, and the
+    /// most scans. Returns `None` without voxels.
+    pub fn into_points(self, axes: DMat3, center: DVec3) -> Option<(rerun::Points3D, u32)> {
+        let max_scans = self.voxels.values().map(|voxel| voxel.scans).max()?;
+        let mut voxels: Vec<([i64; 3], Voxel)> = self.voxels.into_iter().collect();
+        voxels.sort_unstable_by_key(|(key, _)| *key);
+        let positions = voxels.iter().map(|(_, voxel)| {
+            let centroid = voxel.sum / voxel.points as f64;
+            (axes * (centroid - center)).as_vec3().to_array()
+        });
+        let colors = voxels.iter().map(|(_, voxel)| {
+            let t = (voxel.scans - 1) as f32 / (max_scans - 1).max(1) as f32;
+            Colormap::Turbo.color(t)
+        });
+        let points = rerun::Points3D::new(positions)
+            .with_colors(colors)
+            .with_radii([rerun::Radius::new_scene_units(VOXEL as f32 / 2.0)]);
+        Some((points, max_scans))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_the_scans_of_every_voxel() {
+        let mut coverage = Coverage::default();
+        for offset in [0.0, 1.0] {
+            let mut scan = ScanCoverage::default();
+            for x in 0..8 {
+                scan.insert(DVec3::new(offset + x as f64 * 0.25 + 0.1, 0.1, 0.1));
+            }
+            coverage.add(scan);
+        }
+        // The scans cover 0 to 2 m and 1 to 3 m along X.
+        let scans: Vec<u32> = (0..12)
+            .filter_map(|x| coverage.voxels.get(&[x, 0, 0]).map(|voxel| voxel.scans))
+            .collect();
+        assert_eq!(scans, [1, 1, 1, 1, 2, 2, 2, 2, 1, 1, 1, 1]);
+        let (_, max_scans) = coverage.into_points(DMat3::IDENTITY, DVec3::ZERO).unwrap();
+        assert_eq!(max_scans, 2);
+    }
+}
@@ -0,0 +1,106 @@
+//! Digital elevation models from the ground points of the scans, for `--dem`.
+
+use crate::grid::Grid;
+use anyhow::Result;
+use glam::{Mat3, Vec3};
+use std::collections::HashMap;
+
+/// Sum and number of the heights of the ground points in every cell of a horizontal grid, in
+/// file-level coordinates in meters.
+pub(crate) struct Dem {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), (f64, u64)>,
+}
+
+impl Dem {
+    pub fn new(cell_size: f64) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Adds a ground point with its horizontal position and height.
+    pub fn insert(&mut self, [x, y]: [f64; 2], height: f64) {
+        let cell = (
+            (x / self.cell_size).floor() as i64,
+            (y / self.cell_size).floor() as i64,
+        );
+        let (sum, count) = self.cells.entry(cell).or_default();
+        *sum += height;
+        *count += 1;
+    }
+
+    /// Adds the ground points of another model with the same cells.
+    pub fn add(&mut self, other: Dem) {
+        for (cell, (sum, count)) in other.cells {
+            let (total, points) = self.cells.entry(cell).or_default();
+            *total += sum;
+            *points += count;
+        }
+    }
+
+    /// Returns a depth image with the mean height of every cell above the lowest one, where
+    /// cells without ground points are NaN, and the transform from its pixels to the
+    /// horizontal axes, with the lowest height as the third axis. The first row is the one
+    /// with the largest second horizontal coordinate, as in north-up rasters. Returns `None`
+    /// without ground points, and fails for rasters of more than `MAX_GRID_CELLS` cells.
+    pub fn into_raster(self) -> Result<Option<(rerun::DepthImage, rerun::Transform3D)>> {
+        // The rows are the negated second horizontal coordinates, so that they grow southwards.
+        let cells = self.cells.keys().map(|(x, y)| (-*y, *x));
+        let Some(grid) = Grid::bounding(cells, "--dem")? else {
+            return Ok(None);
+        };
+        let (x_min, y_max) = (grid.column_min, -grid.row_min);
+
+        let mean = |(sum, count): (f64, u64)| sum / count as f64;
+        let base = self
+            .cells
+            .values()
+            .map(|cell| mean(*cell))
+            .fold(f64::INFINITY, f64::min);
+        let mut heights = vec![f32::NAN; grid.len()];
+        for ((x, y), cell) in self.cells {
+            heights[grid.index(-y, x).expect("in the bounds")] = (mean(cell) - base) as f32;
+        }
+
+        let bytes: Vec<u8> = heights.iter().flat_map(|h| h.to_le_bytes()).collect();
+        let image = rerun::DepthImage::from_data_type_and_bytes(
+            bytes,
+            [grid.width as u32, grid.height as u32],
+            rerun::datatypes::ChannelDatatype::F32,
+        )
+        .with_meter(1.0)
+        .with_colormap(rerun::components::Colormap::Turbo);
+
+        // The top left corner of the first pixel is the top left corner of its cell.
+        let cell_size = self.cell_size as f32;
+        let transform = rerun::Transform3D::from_translation_mat3x3(
+            [
+                (x_min as f64 * self.cell_size) as f32,
+                ((y_max + 1) as f64 * self.cell_size) as f32,
+                base as f32,
+            ],
+            Mat3::from_diagonal(Vec3::new(cell_size, -cell_size, 1.0)),
+        );
+        Ok(Some((image, transform)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_the_heights_of_every_cell() {
+        let mut dem = Dem::new(0.5);
+        dem.insert([0.1, 0.1], 1.0);
+        dem.insert([0.2, 0.3], 2.0);
+        let mut other = Dem::new(0.5);
+        other.insert([1.2, 0.6], 3.0);
+        dem.add(other);
+        assert_eq!(dem.cells[&(0, 0)], (3.0, 2));
+        assert_eq!(dem.cells[&(2, 1)], (3.0, 1));
+        assert!(dem.into_raster().unwrap().is_some());
+    }
+}
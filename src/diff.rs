@@ -0,0 +1,181 @@
+//! Change detection between two files, for the `diff` command.
+//!
+//! Both files are loaded as merged scans downsampled to a voxel grid, relative to a common
+//! center, and the voxels that only one of them has are changes: voxels of the second file
+//! are added, voxels of the first file are removed, and added and removed voxels close to each
+//! other are taken to be an object that moved. The voxels are found in file-level coordinates
+//! in 64 bits, from the small 32-bit positions around the center.
+
+use anyhow::{Context, Result};
+use glam::{DVec3, I64Vec3, Vec3};
+use rerun::components::Position3D;
+use rerun::log::{Chunk, LogMsg};
+use rerun::{RecordingStream, RecordingStreamBuilder};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::{Center, LoadOptions};
+
+/// Size in meters of the voxels that are compared without `--voxel-size`.
+const VOXEL: f64 = 0.1;
+/// Distance in voxels on every axis up to which added and removed voxels are a move.
+const MOVE_DISTANCE: i64 = 3;
+
+/// Number of voxels of every kind of change.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DiffStats {
+    pub unchanged: u64,
+    pub added: u64,
+    pub removed: u64,
+    pub moved: u64,
+}
+
+/// Logs the voxels that changed from the file at `before` to the file at `after` as
+/// `diff/added` (green), `diff/removed` (red) and `diff/moved` (yellow), under the entity path
+/// prefix if there is one.
+pub fn diff_files(
+    before: &Path,
+    after: &Path,
+    options: &LoadOptions,
+    rec: &RecordingStream,
+) -> Result<DiffStats> {
+    anyhow::ensure!(
+        options.export.is_none() && !options.resume && options.cache.is_none(),
+        "diff cannot be combined with --export, --resume or --cache"
+    );
+    let size = options.voxel_size.unwrap_or(VOXEL);
+    let center = common_center(before, options, size)?;
+    let before_voxels = file_voxels(before, options, size, center)?;
+    let after_voxels = file_voxels(after, options, size, center)?;
+
+    let added: HashSet<I64Vec3> = after_voxels.difference(&before_voxels).copied().collect();
+    let removed: HashSet<I64Vec3> = before_voxels.difference(&after_voxels).copied().collect();
+    let is_near = |voxel: &I64Vec3, others: &HashSet<I64Vec3>| {
+        let range = -MOVE_DISTANCE..=MOVE_DISTANCE;
+        range.clone().any(|x| {
+            range.clone().any(|y| {
+                range
+                    .clone()
+                    .any(|z| others.contains(&(*voxel + I64Vec3::new(x, y, z))))
+            })
+        })
+    };
+    let (moved_from, removed_only): (Vec<I64Vec3>, Vec<I64Vec3>) =
+        removed.iter().partition(|voxel| is_near(voxel, &added));
+    let (moved_to, added_only): (Vec<I64Vec3>, Vec<I64Vec3>) =
+        added.iter().partition(|voxel| is_near(voxel, &removed));
+
+    let root = options.entity_path_prefix.as_deref().unwrap_or("diff");
+    // The voxels are logged relative to `--center` as loaded files are, where the center of
+    // `--center auto` is the common one.
+    let logged_center = match options.center {
+        Some(Center::Offset(offset)) => DVec3::from(offset),
+        Some(Center::Auto) => center.as_dvec3() * size,
+        None => DVec3::ZERO,
+    };
+    if let Some(transform) = crate::root_transform(options, logged_center, None) {
+        rec.log_with_static(root, options.static_, &transform)?;
+    }
+    let moved: Vec<I64Vec3> = moved_from.iter().chain(&moved_to).copied().collect();
+    for (name, voxels, color) in [
+        ("added", &added_only, rerun::Color::from_rgb(0, 200, 0)),
+        ("removed", &removed_only, rerun::Color::from_rgb(220, 0, 0)),
+        ("moved", &moved, rerun::Color::from_rgb(255, 200, 0)),
+    ] {
+        if voxels.is_empty() {
+            continue;
+        }
+        let centers = voxels.iter().map(|voxel| {
+            ((voxel.as_dvec3() + 0.5) * size - logged_center)
+                .as_vec3()
+                .to_array()
+        });
+        rec.log_with_static(
+            format!("{root}/{name}"),
+            options.static_,
+            &rerun::Points3D::new(centers)
+                .with_colors([color])
+                .with_radii([rerun::Radius::new_scene_units(size as f32 / 2.0)]),
+        )?;
+    }
+
+    Ok(DiffStats {
+        unchanged: before_voxels.intersection(&after_voxels).count() as u64,
+        added: added_only.len() as u64,
+        removed: removed_only.len() as u64,
+        moved: moved.len() as u64,
+    })
+}
+
+/// Returns the voxel both files are loaded relative to: the one of a numeric `--center`, or
+/// else the one of any point of the first file, so that the positions of the recording are
+/// small enough for 32 bits.
+fn common_center(path: &Path, options: &LoadOptions, size: f64) -> Result<I64Vec3> {
+    if let Some(Center::Offset(offset)) = options.center {
+        return Ok((DVec3::from(offset) / size).floor().as_i64vec3());
+    }
+    let options = LoadOptions {
+        max_points: Some(1),
+        center: None,
+        voxel_size: None,
+        ..options.clone()
+    };
+    // The point is rounded to 32 bits, which is close enough for a center.
+    let point = merged_positions(path, &options)?.into_iter().next();
+    Ok(point.map_or(I64Vec3::ZERO, |point| {
+        (point.as_dvec3() / size).floor().as_i64vec3()
+    }))
+}
+
+/// Returns the voxels with points of a file in file-level coordinates, loaded as merged scans
+/// relative to the corner of the `center` voxel.
+fn file_voxels(
+    path: &Path,
+    options: &LoadOptions,
+    size: f64,
+    center: I64Vec3,
+) -> Result<HashSet<I64Vec3>> {
+    let options = LoadOptions {
+        voxel_size: Some(size),
+        center: Some(Center::Offset((center.as_dvec3() * size).to_array())),
+        ..options.clone()
+    };
+    // The positions are the centroids of the points in their voxels.
+    let positions = merged_positions(path, &options)?;
+    Ok(positions
+        .into_iter()
+        .map(|position| (position.as_dvec3() / size).floor().as_i64vec3() + center)
+        .collect())
+}
+
+/// Returns the logged positions of a file, loaded as merged scans.
+fn merged_positions(path: &Path, options: &LoadOptions) -> Result<Vec<Vec3>> {
+    let (rec, storage) = RecordingStreamBuilder::new("rerun_e57_loader").memory()?;
+    let options = LoadOptions {
+        merge_scans: true,
+        group_stations: false,
+        no_images: true,
+        ..options.clone()
+    };
+    crate::load_file(path, &options, &rec).with_context(|| format!("Failed to load {path:?}"))?;
+    rec.flush_blocking();
+
+    let mut positions = Vec::new();
+    for msg in storage.take() {
+        let LogMsg::ArrowMsg(_, msg) = msg else {
+            continue;
+        };
+        let chunk = Chunk::from_arrow_msg(&msg)?;
+        if !chunk.entity_path().to_string().contains("/merged") {
+            continue;
+        }
+        for row in 0..chunk.num_rows() {
+            let Some(batch) = chunk.component_batch::<Position3D>(row) else {
+                continue;
+            };
+            positions.extend(batch?.into_iter().map(|position| Vec3::from(position.0)));
+        }
+    }
+    Ok(positions)
+}
@@ -0,0 +1,311 @@
+//! Writes the points of all scans to a single PCD or LAS file instead of logging them.
+//!
+//! The number of points and the bounds are only known at the end, but both formats store them
+//! in the header, so the points are collected in a temporary file first.
+
+use anyhow::{Context, Result};
+use glam::{DQuat, DVec3};
+use rerun::Vec3D;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tempfile::NamedTempFile;
+
+use crate::pipeline::normalize_scalar;
+
+/// File format of an export, chosen by the extension of the output path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    Pcd,
+    Las,
+}
+
+/// Size of a point in the temporary file: position as 3 `f64`, RGB, and the raw and the
+/// normalized intensity as `f32`.
+const RECORD_SIZE: usize = 3 * 8 + 3 + 2 * 4;
+
+/// Scale of LAS coordinates, keeping millimeters.
+const LAS_SCALE: f64 = 0.001;
+
+/// Size of the LAS 1.2 header, which is directly followed by the points.
+const LAS_HEADER_SIZE: u16 = 227;
+
+/// Record length of LAS point format 2, with intensity and RGB.
+const LAS_RECORD_LENGTH: u16 = 26;
+
+struct ExportState {
+    data: BufWriter<NamedTempFile>,
+    points: u64,
+    min: DVec3,
+    max: DVec3,
+}
+
+/// Collects the points that all scan workers would otherwise log.
+pub(crate) struct Exporter {
+    path: PathBuf,
+    format: ExportFormat,
+    intensities: bool,
+    state: Mutex<ExportState>,
+}
+
+impl Exporter {
+    /// Creates an exporter for `path`, which must end in `.pcd` or `.las`.
+    pub fn new(path: &Path, intensities: bool) -> Result<Self> {
+        let extension = path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_ascii_lowercase();
+        let format = match extension.as_str() {
+            "pcd" => ExportFormat::Pcd,
+            "las" => ExportFormat::Las,
+            _ => anyhow::bail!("Unsupported export format, expected a .pcd or .las file: {path:?}"),
+        };
+
+        // The temporary file sits next to the output, which usually has room for it.
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let data = NamedTempFile::new_in(dir)
+            .with_context(|| format!("Failed to create a temporary file in {dir:?}"))?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            format,
+            intensities,
+            state: Mutex::new(ExportState {
+                data: BufWriter::new(data),
+                points: 0,
+                min: DVec3::splat(f64::INFINITY),
+                max: DVec3::splat(f64::NEG_INFINITY),
+            }),
+        })
+    }
+
+    /// Adds a chunk of points, transforming them into file-level coordinates with `world`.
+    pub fn write(
+        &self,
+        world: Option<(DVec3, DQuat)>,
+        intensity_range: Option<(f64, f64)>,
+        positions: &[Vec3D],
+        colors: &[rerun::Color],
+        intensities: &[Option<f32>],
+    ) -> Result<()> {
+        let mut state = self.state.lock().expect("export state poisoned");
+        let mut record = [0_u8; RECORD_SIZE];
+
+        for (i, (position, color)) in positions.iter().zip(colors).enumerate() {
+            let local = glam::Vec3::from(*position).as_dvec3();
+            let p = world.map_or(local, |(t, q)| q * local + t);
+            state.min = state.min.min(p);
+            state.max = state.max.max(p);
+
+            for (axis, value) in p.to_array().into_iter().enumerate() {
+                record[8 * axis..8 * axis + 8].copy_from_slice(&value.to_le_bytes());
+            }
+            let [r, g, b, _] = color.to_array();
+            record[24..27].copy_from_slice(&[r, g, b]);
+            let intensity = intensities.get(i).copied().flatten();
+            let normalized = intensity
+                .zip(intensity_range)
+                .map(|(intensity, range)| normalize_scalar(intensity, range));
+            record[27..31].copy_from_slice(&intensity.unwrap_or(f32::NAN).to_le_bytes());
+            record[31..35].copy_from_slice(&normalized.unwrap_or(f32::NAN).to_le_bytes());
+
+            state.data.write_all(&record)?;
+            state.points += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the output file and returns the number of exported points.
+    pub fn finish(self) -> Result<u64> {
+        let state = self.state.into_inner().expect("export state poisoned");
+        let points = state.points;
+        let mut data = state.data.into_inner().map_err(|e| e.into_error())?;
+        data.seek(SeekFrom::Start(0))?;
+        let mut data = BufReader::new(data);
+
+        let mut out = BufWriter::new(
+            File::create(&self.path)
+                .with_context(|| format!("Failed to create export file: {:?}", self.path))?,
+        );
+        match self.format {
+            ExportFormat::Pcd => {
+                write_pcd(&mut out, &mut data, points, self.intensities)?;
+            }
+            ExportFormat::Las => {
+                write_las(&mut out, &mut data, points, state.min, state.max)?;
+            }
+        }
+        out.flush()?;
+
+        Ok(points)
+    }
+}
+
+/// Reads the next point of the temporary file, with its raw and normalized intensity.
+fn read_record(data: &mut impl Read) -> Result<(DVec3, [u8; 3], f32, f32)> {
+    let mut record = [0_u8; RECORD_SIZE];
+    data.read_exact(&mut record)?;
+    let value =
+        |i: usize| f64::from_le_bytes(record[8 * i..8 * i + 8].try_into().unwrap_or_default());
+    let intensity = |i: usize| f32::from_le_bytes(record[i..i + 4].try_into().unwrap_or_default());
+    Ok((
+        DVec3::new(value(0), value(1), value(2)),
+        [record[24], record[25], record[26]],
+        intensity(27),
+        intensity(31),
+    ))
+}
+
+/// Writes a binary PCD file with the colors packed into a float `rgb` field, as PCL expects.
+fn write_pcd(
+    out: &mut impl Write,
+    data: &mut impl Read,
+    points: u64,
+    intensities: bool,
+) -> Result<()> {
+    let (fields, sizes, types, counts) = if intensities {
+        ("x y z rgb intensity", "4 4 4 4 4", "F F F F F", "1 1 1 1 1")
+    } else {
+        ("x y z rgb", "4 4 4 4", "F F F F", "1 1 1 1")
+    };
+    write!(
+        out,
+        "# .PCD v0.7 - Point Cloud Data file format\n\
+         VERSION 0.7\n\
+         FIELDS {fields}\n\
+         SIZE {sizes}\n\
+         TYPE {types}\n\
+         COUNT {counts}\n\
+         WIDTH {points}\n\
+         HEIGHT 1\n\
+         VIEWPOINT 0 0 0 1 0 0 0\n\
+         POINTS {points}\n\
+         DATA binary\n"
+    )?;
+
+    for _ in 0..points {
+        let (p, [r, g, b], intensity, _) = read_record(data)?;
+        for value in p.as_vec3().to_array() {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        let rgb = (r as u32) << 16 | (g as u32) << 8 | b as u32;
+        out.write_all(&rgb.to_le_bytes())?;
+        if intensities {
+            out.write_all(&intensity.to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a LAS 1.2 file with point format 2, with the minimum of the bounds as offset.
+fn write_las(
+    out: &mut impl Write,
+    data: &mut impl Read,
+    points: u64,
+    min: DVec3,
+    max: DVec3,
+) -> Result<()> {
+    anyhow::ensure!(
+        points <= u32::MAX as u64,
+        "LAS 1.2 files cannot hold more than {} points",
+        u32::MAX
+    );
+    let (min, max) = if points == 0 {
+        (DVec3::ZERO, DVec3::ZERO)
+    } else {
+        (min, max)
+    };
+    let offset = min.floor();
+
+    let mut header = vec![0_u8; LAS_HEADER_SIZE as usize];
+    header[0..4].copy_from_slice(b"LASF");
+    header[24] = 1;
+    header[25] = 2;
+    let system = b"rerun-loader-e57";
+    header[26..26 + system.len()].copy_from_slice(system);
+    let software = format!("rerun-loader-e57 {}", env!("CARGO_PKG_VERSION"));
+    header[58..58 + software.len()].copy_from_slice(software.as_bytes());
+    header[94..96].copy_from_slice(&LAS_HEADER_SIZE.to_le_bytes());
+    header[96..100].copy_from_slice(&(LAS_HEADER_SIZE as u32).to_le_bytes());
+    header[104] = 2;
+    header[105..107].copy_from_slice(&LAS_RECORD_LENGTH.to_le_bytes());
+    header[107..111].copy_from_slice(&(points as u32).to_le_bytes());
+    header[111..115].copy_from_slice(&(points as u32).to_le_bytes());
+    let doubles = [[LAS_SCALE; 3], offset.to_array()]
+        .concat()
+        .into_iter()
+        .chain([max.x, min.x, max.y, min.y, max.z, min.z]);
+    for (i, value) in doubles.enumerate() {
+        header[131 + 8 * i..139 + 8 * i].copy_from_slice(&value.to_le_bytes());
+    }
+    out.write_all(&header)?;
+
+    let mut record = [0_u8; LAS_RECORD_LENGTH as usize];
+    for _ in 0..points {
+        let (p, rgb, _, intensity) = read_record(data)?;
+        for (axis, value) in (p - offset).to_array().into_iter().enumerate() {
+            let raw = (value / LAS_SCALE).round() as i32;
+            record[4 * axis..4 * axis + 4].copy_from_slice(&raw.to_le_bytes());
+        }
+        // LAS intensities are normalized to the full 16-bit range.
+        let intensity = if intensity.is_nan() {
+            0
+        } else {
+            (intensity * u16::MAX as f32).round() as u16
+        };
+        record[12..14].copy_from_slice(&intensity.to_le_bytes());
+        // Return 1 of 1.
+        record[14] = 0b0000_1001;
+        for (i, channel) in rgb.into_iter().enumerate() {
+            record[20 + 2 * i..22 + 2 * i].copy_from_slice(&(channel as u16 * 257).to_le_bytes());
+        }
+        out.write_all(&record)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_transformed_points_to_las() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.las");
+        let exporter = Exporter::new(&path, false).unwrap();
+        exporter
+            .write(
+                Some((DVec3::new(100.0, 0.0, 0.0), DQuat::IDENTITY)),
+                Some((0.0, 2.0)),
+                &[Vec3D::new(1.0, 2.0, 3.0), Vec3D::new(0.0, 0.0, 0.0)],
+                &[rerun::Color::from_rgb(255, 0, 0); 2],
+                &[Some(1.0), None],
+            )
+            .unwrap();
+        assert_eq!(exporter.finish().unwrap(), 2);
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(
+            bytes.len(),
+            LAS_HEADER_SIZE as usize + 2 * LAS_RECORD_LENGTH as usize
+        );
+        let double =
+            |offset: usize| f64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        // The offset is the floored minimum and the bounds are stored as max, min pairs.
+        assert_eq!(double(155), 100.0);
+        assert_eq!([double(179), double(187)], [101.0, 100.0]);
+
+        let point = &bytes[LAS_HEADER_SIZE as usize..];
+        assert_eq!(i32::from_le_bytes(point[0..4].try_into().unwrap()), 1000);
+        assert_eq!(u16::from_le_bytes(point[12..14].try_into().unwrap()), 32768);
+        assert_eq!(u16::from_le_bytes(point[20..22].try_into().unwrap()), 65535);
+    }
+}
@@ -0,0 +1,291 @@
+//! Point attributes from E57 extensions and the per-point time stamps, which the simple point
+//! iterator of the `e57` crate does not expose.
+
+use anyhow::{Context, Result};
+use e57::{E57Reader, PointCloud, PointCloudReaderRaw, Record, RecordName};
+use std::io::{Read, Seek};
+
+/// Attribute names used for per-point classifications by common E57 exporters.
+const CLASSIFICATION_NAMES: [&str; 2] = ["classification", "class"];
+
+/// Attribute names of the normal components in the `nor` E57 extension.
+const NORMAL_NAMES: [&str; 3] = ["normalX", "normalY", "normalZ"];
+
+/// Maximum number of `--extra-field` attributes, which are stored inline with every point.
+pub(crate) const MAX_EXTRA_FIELDS: usize = 8;
+
+/// Values of the `--extra-field` attributes of a point, in the order they were requested.
+pub(crate) type ExtraScalars = [Option<f32>; MAX_EXTRA_FIELDS];
+
+/// Extension attributes of a single point.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ExtraValues {
+    pub classification: Option<u8>,
+    pub normal: Option<[f32; 3]>,
+    /// Seconds since the acquisition start of the scan, if the time stamp is valid.
+    pub time_stamp: Option<f64>,
+    /// Zero-based return index and number of returns of the pulse.
+    pub returns: Option<(u32, u32)>,
+    pub extras: ExtraScalars,
+}
+
+/// Reads the extension attributes of a point cloud in lockstep with its simple point iterator.
+pub(crate) struct ExtraFields<'a, T: Read + Seek> {
+    iter: PointCloudReaderRaw<'a, T>,
+    prototype: Vec<Record>,
+    classification: Option<usize>,
+    normal: Option<[usize; 3]>,
+    /// Prototype indices of the time stamp and of its optional invalid flag.
+    time_stamp: Option<(usize, Option<usize>)>,
+    /// Prototype indices of the return index and of the return count.
+    returns: Option<(usize, usize)>,
+    /// Slots in `ExtraScalars` and prototype indices of the requested extra fields the point
+    /// cloud has.
+    extras: Vec<(usize, usize)>,
+}
+
+impl<'a, T: Read + Seek> ExtraFields<'a, T> {
+    /// Returns `None` if the point cloud has none of the requested attributes.
+    pub fn new(
+        reader: &'a mut E57Reader<T>,
+        pointcloud: &PointCloud,
+        classification: bool,
+        normals: bool,
+        time_stamps: bool,
+        returns: bool,
+        extra_names: &[String],
+    ) -> Result<Option<Self>> {
+        let classification = classification
+            .then(|| classification_field(pointcloud))
+            .flatten();
+        let normal = normals.then(|| normal_fields(pointcloud)).flatten();
+        let time_stamp = time_stamps.then(|| time_stamp_fields(pointcloud)).flatten();
+        let returns = returns.then(|| return_fields(pointcloud)).flatten();
+        let extras: Vec<_> = extra_names
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, name)| Some((slot, extra_field(pointcloud, name)?)))
+            .collect();
+        if classification.is_none()
+            && normal.is_none()
+            && time_stamp.is_none()
+            && returns.is_none()
+            && extras.is_empty()
+        {
+            return Ok(None);
+        }
+
+        let iter = reader
+            .pointcloud_raw(pointcloud)
+            .context("Unable to get raw point cloud iterator")?;
+
+        Ok(Some(Self {
+            iter,
+            prototype: pointcloud.prototype.clone(),
+            classification,
+            normal,
+            time_stamp,
+            returns,
+            extras,
+        }))
+    }
+
+    pub fn has_classification(&self) -> bool {
+        self.classification.is_some()
+    }
+
+    pub fn has_normals(&self) -> bool {
+        self.normal.is_some()
+    }
+
+    /// Returns the slots of the requested extra fields the point cloud has, with their names
+    /// in the prototype, without the namespace.
+    pub fn extra_fields(&self) -> Vec<(usize, String)> {
+        self.extras
+            .iter()
+            .filter_map(|&(slot, i)| match &self.prototype[i].name {
+                RecordName::Unknown { name, .. } => Some((slot, name.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the attributes of the next point, which must be called once per record.
+    pub fn next_values(&mut self) -> ExtraValues {
+        let Some(Ok(values)) = self.iter.next() else {
+            return ExtraValues::default();
+        };
+
+        let value = |i: usize| values[i].to_f64(&self.prototype[i].data_type).ok();
+        ExtraValues {
+            classification: self
+                .classification
+                .and_then(|i| Some(value(i)?.round().clamp(0.0, 255.0) as u8)),
+            normal: self
+                .normal
+                .and_then(|[x, y, z]| Some([value(x)? as f32, value(y)? as f32, value(z)? as f32])),
+            time_stamp: self
+                .time_stamp
+                .filter(|(_, invalid)| invalid.and_then(value).is_none_or(|flag| flag == 0.0))
+                .and_then(|(i, _)| value(i)),
+            returns: self
+                .returns
+                .and_then(|(index, count)| Some((value(index)? as u32, value(count)? as u32))),
+            extras: {
+                let mut extras = ExtraScalars::default();
+                for &(slot, i) in &self.extras {
+                    extras[slot] = value(i).map(|v| v as f32);
+                }
+                extras
+            },
+        }
+    }
+}
+
+/// Returns the prototype index of the classification attribute, if the point cloud has one.
+pub(crate) fn classification_field(pointcloud: &PointCloud) -> Option<usize> {
+    pointcloud.prototype.iter().position(|record| {
+        matches!(&record.name, RecordName::Unknown { name, .. } if is_classification_name(name))
+    })
+}
+
+/// Returns the prototype indices of the normal components, if the point cloud has all three.
+pub(crate) fn normal_fields(pointcloud: &PointCloud) -> Option<[usize; 3]> {
+    let field = |normal_name: &str| {
+        pointcloud.prototype.iter().position(|record| {
+            matches!(&record.name, RecordName::Unknown { name, .. } if name == normal_name)
+        })
+    };
+    Some([
+        field(NORMAL_NAMES[0])?,
+        field(NORMAL_NAMES[1])?,
+        field(NORMAL_NAMES[2])?,
+    ])
+}
+
+/// Returns the prototype indices of the time stamp and of its invalid flag, if the point cloud
+/// has time stamps.
+pub(crate) fn time_stamp_fields(pointcloud: &PointCloud) -> Option<(usize, Option<usize>)> {
+    let field = |name: RecordName| pointcloud.prototype.iter().position(|r| r.name == name);
+    Some((
+        field(RecordName::TimeStamp)?,
+        field(RecordName::IsTimeStampInvalid),
+    ))
+}
+
+/// Returns the prototype indices of the return index and count, if the point cloud has both.
+pub(crate) fn return_fields(pointcloud: &PointCloud) -> Option<(usize, usize)> {
+    let field = |name: RecordName| pointcloud.prototype.iter().position(|r| r.name == name);
+    Some((
+        field(RecordName::ReturnIndex)?,
+        field(RecordName::ReturnCount)?,
+    ))
+}
+
+/// Returns the prototype index of an attribute by its name, with or without the namespace of
+/// its extension, e.g. `reflectance` or `riegl:reflectance`.
+pub(crate) fn extra_field(pointcloud: &PointCloud, extra_name: &str) -> Option<usize> {
+    pointcloud
+        .prototype
+        .iter()
+        .position(|record| match &record.name {
+            RecordName::Unknown { namespace, name } => {
+                name.eq_ignore_ascii_case(extra_name)
+                    || extra_name.split_once(':').is_some_and(|(ns, n)| {
+                        ns == namespace.as_str() && n.eq_ignore_ascii_case(name)
+                    })
+            }
+            _ => false,
+        })
+}
+
+/// Returns whether an attribute name is one of the common names for classifications.
+pub(crate) fn is_classification_name(name: &str) -> bool {
+    CLASSIFICATION_NAMES.contains(&name.to_ascii_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use e57::RecordDataType;
+
+    #[test]
+    fn finds_classification_extension_field() {
+        let mut pointcloud = PointCloud::default();
+        pointcloud.prototype = vec![
+            Record::CARTESIAN_X_F32,
+            Record {
+                name: RecordName::Unknown {
+                    namespace: "las".to_owned(),
+                    name: "Classification".to_owned(),
+                },
+                data_type: RecordDataType::U8,
+            },
+        ];
+        assert_eq!(classification_field(&pointcloud), Some(1));
+
+        pointcloud.prototype.truncate(1);
+        assert_eq!(classification_field(&pointcloud), None);
+    }
+
+    #[test]
+    fn finds_normal_extension_fields() {
+        let normal = |name: &str| Record {
+            name: RecordName::Unknown {
+                namespace: "nor".to_owned(),
+                name: name.to_owned(),
+            },
+            data_type: RecordDataType::F32,
+        };
+        let mut pointcloud = PointCloud::default();
+        pointcloud.prototype = vec![
+            Record::CARTESIAN_X_F32,
+            normal("normalX"),
+            normal("normalY"),
+            normal("normalZ"),
+        ];
+        assert_eq!(normal_fields(&pointcloud), Some([1, 2, 3]));
+
+        pointcloud.prototype.pop();
+        assert_eq!(normal_fields(&pointcloud), None);
+    }
+
+    #[test]
+    fn finds_extra_fields_with_or_without_namespace() {
+        let mut pointcloud = PointCloud::default();
+        pointcloud.prototype = vec![
+            Record::CARTESIAN_X_F32,
+            Record {
+                name: RecordName::Unknown {
+                    namespace: "riegl".to_owned(),
+                    name: "reflectance".to_owned(),
+                },
+                data_type: RecordDataType::F32,
+            },
+        ];
+        assert_eq!(extra_field(&pointcloud, "reflectance"), Some(1));
+        assert_eq!(extra_field(&pointcloud, "riegl:Reflectance"), Some(1));
+        assert_eq!(extra_field(&pointcloud, "leica:reflectance"), None);
+        assert_eq!(extra_field(&pointcloud, "deviation"), None);
+    }
+
+    #[test]
+    fn finds_time_stamp_fields() {
+        let time_stamp = Record {
+            name: RecordName::TimeStamp,
+            data_type: RecordDataType::F64,
+        };
+        let invalid = Record {
+            name: RecordName::IsTimeStampInvalid,
+            data_type: RecordDataType::U8,
+        };
+        let mut pointcloud = PointCloud::default();
+        pointcloud.prototype = vec![Record::CARTESIAN_X_F32, time_stamp, invalid];
+        assert_eq!(time_stamp_fields(&pointcloud), Some((1, Some(2))));
+
+        pointcloud.prototype.pop();
+        assert_eq!(time_stamp_fields(&pointcloud), Some((1, None)));
+        pointcloud.prototype.pop();
+        assert_eq!(time_stamp_fields(&pointcloud), None);
+    }
+}
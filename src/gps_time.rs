@@ -0,0 +1,95 @@
+//! Conversion of the GPS timestamps in E57 files to UTC.
+
+/// Seconds between the Unix epoch and the GPS epoch, 1980-01-06 00:00:00 UTC.
+const GPS_EPOCH: i64 = 315_964_800;
+
+/// Months whose first day starts with one more leap second between GPS time and UTC.
+const LEAP_SECONDS: [(i64, u32); 18] = [
+    (1981, 7),
+    (1982, 7),
+    (1983, 7),
+    (1985, 7),
+    (1988, 1),
+    (1990, 1),
+    (1991, 1),
+    (1992, 7),
+    (1993, 7),
+    (1994, 7),
+    (1996, 1),
+    (1997, 7),
+    (1999, 1),
+    (2006, 1),
+    (2009, 1),
+    (2012, 7),
+    (2015, 7),
+    (2017, 1),
+];
+
+/// Converts seconds since the GPS epoch to seconds since the Unix epoch.
+pub(crate) fn gps_to_unix(gps_time: f64) -> f64 {
+    let leap_seconds = LEAP_SECONDS
+        .iter()
+        .enumerate()
+        .filter(|(i, (year, month))| {
+            let gps_at_leap =
+                days_from_civil(*year, *month, 1) * 86_400 - GPS_EPOCH + *i as i64 + 1;
+            gps_time >= gps_at_leap as f64
+        })
+        .count();
+    gps_time + GPS_EPOCH as f64 - leap_seconds as f64
+}
+
+/// Formats a GPS timestamp as `YYYY-MM-DD hh:mm:ss UTC`.
+pub(crate) fn format_gps_time(gps_time: f64) -> String {
+    let unix = gps_to_unix(gps_time).floor() as i64;
+    let (days, seconds) = (unix.div_euclid(86_400), unix.rem_euclid(86_400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Days since the Unix epoch of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date of a day since the Unix epoch, the inverse of [`days_from_civil`].
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_gps_time_to_utc_with_leap_seconds() {
+        assert_eq!(format_gps_time(0.0), "1980-01-06 00:00:00 UTC");
+        // GPS week 2000, after all 18 leap seconds.
+        assert_eq!(
+            format_gps_time(2000.0 * 604_800.0),
+            "2018-05-05 23:59:42 UTC"
+        );
+        assert_eq!(gps_to_unix(1_000_000_000.0), 1_315_964_785.0);
+    }
+}
@@ -0,0 +1,85 @@
+//! Dense grids over the bounds of sparse integer cells, like the rows and columns of structured
+//! scans or the horizontal cells of the ground and elevation models.
+
+use anyhow::Result;
+
+/// Most cells of a dense grid, which keeps grids of a few dozen bytes per cell below a
+/// gigabyte.
+pub(crate) const MAX_GRID_CELLS: u64 = 16_000_000;
+
+/// Row-major grid from the smallest to the largest row and column of some cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Grid {
+    pub row_min: i64,
+    pub column_min: i64,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Grid {
+    /// Returns the grid over the rows and columns of the cells, or `None` without cells.
+    ///
+    /// Fails for grids of more than `MAX_GRID_CELLS` cells, which are usually due to outliers
+    /// or corrupt indices far away from the others. The message names `what` needs the grid.
+    pub fn bounding(
+        cells: impl IntoIterator<Item = (i64, i64)>,
+        what: &str,
+    ) -> Result<Option<Self>> {
+        let mut bounds: Option<(i64, i64, i64, i64)> = None;
+        for (row, column) in cells {
+            bounds = Some(match bounds {
+                Some((r0, r1, c0, c1)) => {
+                    (r0.min(row), r1.max(row), c0.min(column), c1.max(column))
+                }
+                None => (row, row, column, column),
+            });
+        }
+        let Some((row_min, row_max, column_min, column_max)) = bounds else {
+            return Ok(None);
+        };
+        // In 128 bits, since indices from the ends of the 64-bit range would overflow.
+        let height = row_max as i128 - row_min as i128 + 1;
+        let width = column_max as i128 - column_min as i128 + 1;
+        anyhow::ensure!(
+            height * width <= MAX_GRID_CELLS as i128,
+            "{what} would need a grid of {height} x {width} cells, more than the \
+             {MAX_GRID_CELLS} supported. Points far away from the others can be left out with \
+             --crop-box or --max-range, or with --rows and --cols in structured scans."
+        );
+        Ok(Some(Self {
+            row_min,
+            column_min,
+            width: width as usize,
+            height: height as usize,
+        }))
+    }
+
+    /// Returns the row-major index of a cell, or `None` outside of the grid.
+    pub fn index(&self, row: i64, column: i64) -> Option<usize> {
+        let row = usize::try_from(row.checked_sub(self.row_min)?).ok()?;
+        let column = usize::try_from(column.checked_sub(self.column_min)?).ok()?;
+        (row < self.height && column < self.width).then(|| row * self.width + column)
+    }
+
+    /// Returns the number of cells of the grid.
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_cells_and_rejects_outliers() {
+        let grid = Grid::bounding([(2, -1), (4, 3)], "test").unwrap().unwrap();
+        assert_eq!((grid.width, grid.height, grid.len()), (5, 3, 15));
+        assert_eq!(grid.index(2, -1), Some(0));
+        assert_eq!(grid.index(4, 3), Some(14));
+        assert_eq!(grid.index(5, 0), None);
+        assert!(Grid::bounding([], "test").unwrap().is_none());
+        assert!(Grid::bounding([(0, 0), (5000, 5000)], "test").is_err());
+        assert!(Grid::bounding([(i64::MIN, 0), (i64::MAX, 0)], "test").is_err());
+    }
+}
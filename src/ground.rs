@@ -0,0 +1,139 @@
+//! Ground segmentation with a progressive morphological filter, for `--segment-ground`.
+//!
+//! The lowest point of every cell of a horizontal grid is opened (eroded, then dilated) with
+//! growing windows, which removes objects up to the size of the largest window while slopes up
+//! to `MAX_SLOPE` are kept, following Zhang et al. (2003). Points close to the opened surface
+//! are ground.
+
+use crate::grid::Grid;
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Size in meters of the cells of the grid.
+const CELL: f64 = 1.0;
+/// Half widths in cells of the windows of the openings, i.e. objects up to 32 m wide are removed.
+const WINDOWS: [usize; 5] = [1, 2, 4, 8, 16];
+/// Terrain slope in meters per meter that is still ground.
+const MAX_SLOPE: f64 = 0.3;
+/// Height difference in meters of a cell to its opening that is always kept as terrain, and
+/// above which it never is.
+const MIN_HEIGHT_DIFFERENCE: f64 = 0.3;
+const MAX_HEIGHT_DIFFERENCE: f64 = 3.0;
+/// Height in meters above the ground surface up to which points are ground.
+const GROUND_THRESHOLD: f64 = 0.3;
+
+/// Lowest heights of the sampled points by cell.
+#[derive(Default)]
+pub(crate) struct GroundSamples {
+    cells: HashMap<(i64, i64), f64>,
+}
+
+impl GroundSamples {
+    /// Adds a point with its horizontal position and height in meters.
+    pub fn insert(&mut self, horizontal: [f64; 2], height: f64) {
+        let cell = self.cells.entry(cell(horizontal)).or_insert(height);
+        *cell = cell.min(height);
+    }
+
+    /// Filters the sampled heights into the ground surface, or returns `None` without samples.
+    /// Fails if the grid over their bounds would have more than `MAX_GRID_CELLS` cells.
+    pub fn into_model(self) -> Result<Option<GroundModel>> {
+        // The rows of the grid are along the second horizontal axis.
+        let cells = self.cells.keys().map(|(x, y)| (*y, *x));
+        let Some(grid) = Grid::bounding(cells, "--segment-ground")? else {
+            return Ok(None);
+        };
+
+        // Cells without points are NaN, which `f64::min` and `f64::max` skip.
+        let mut surface = vec![f64::NAN; grid.len()];
+        for ((x, y), z) in self.cells {
+            surface[grid.index(y, x).expect("in the bounds")] = z;
+        }
+        for window in WINDOWS {
+            let eroded = filter(&surface, grid.width, window, f64::min);
+            let opened = filter(&eroded, grid.width, window, f64::max);
+            let threshold = (MIN_HEIGHT_DIFFERENCE + MAX_SLOPE * (2 * window) as f64 * CELL)
+                .min(MAX_HEIGHT_DIFFERENCE);
+            for (z, opened) in surface.iter_mut().zip(opened) {
+                if *z - opened > threshold {
+                    *z = opened;
+                }
+            }
+        }
+        Ok(Some(GroundModel { grid, surface }))
+    }
+}
+
+/// Height of the ground surface by cell.
+pub(crate) struct GroundModel {
+    grid: Grid,
+    surface: Vec<f64>,
+}
+
+impl GroundModel {
+    /// Returns whether a point with its horizontal position and height in meters is ground.
+    /// Points outside of the sampled cells are not.
+    pub fn is_ground(&self, horizontal: [f64; 2], height: f64) -> bool {
+        let (x, y) = cell(horizontal);
+        self.grid
+            .index(y, x)
+            .is_some_and(|index| height <= self.surface[index] + GROUND_THRESHOLD)
+    }
+}
+
+fn cell([x, y]: [f64; 2]) -> (i64, i64) {
+    ((x / CELL).floor() as i64, (y / CELL).floor() as i64)
+}
+
+/// Applies `f` over square windows of the given half width, as a row pass and a column pass.
+fn filter(grid: &[f64], width: usize, window: usize, f: fn(f64, f64) -> f64) -> Vec<f64> {
+    let height = grid.len() / width;
+    let mut rows = vec![f64::NAN; grid.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let range = x.saturating_sub(window)..(x + window + 1).min(width);
+            rows[y * width + x] = range.map(|x| grid[y * width + x]).fold(f64::NAN, f);
+        }
+    }
+    let mut columns = vec![f64::NAN; grid.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let range = y.saturating_sub(window)..(y + window + 1).min(height);
+            columns[y * width + x] = range.map(|y| rows[y * width + x]).fold(f64::NAN, f);
+        }
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_objects_and_keeps_slopes() {
+        // A 40 m square sloping up by 10 % along X, with a 5 m wide and 4 m high box on it.
+        let mut samples = GroundSamples::default();
+        let is_box = |x: f64, y: f64| (10.0..15.0).contains(&x) && (10.0..15.0).contains(&y);
+        let ground = |x: f64| 0.1 * x;
+        for x in 0..80 {
+            for y in 0..80 {
+                let (x, y) = (x as f64 * 0.5, y as f64 * 0.5);
+                let z = ground(x) + if is_box(x, y) { 4.0 } else { 0.0 };
+                samples.insert([x, y], z);
+            }
+        }
+        let model = samples.into_model().unwrap().unwrap();
+        assert!(model.is_ground([30.0, 30.0], ground(30.0)));
+        assert!(model.is_ground([12.0, 12.0], ground(12.0)));
+        assert!(!model.is_ground([12.0, 12.0], ground(12.0) + 4.0));
+        assert!(!model.is_ground([100.0, 0.0], 0.0));
+    }
+
+    #[test]
+    fn rejects_grids_spread_by_outliers() {
+        let mut samples = GroundSamples::default();
+        samples.insert([0.0, 0.0], 0.0);
+        samples.insert([5000.0, 5000.0], 0.0);
+        assert!(samples.into_model().is_err());
+    }
+}
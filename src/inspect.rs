@@ -0,0 +1,329 @@
+//! Describes the structure of an E57 file without reading its points, for `--info`.
+
+use anyhow::{Context, Result};
+use e57::{E57Reader, ImageFormat, PointCloud, Projection};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::fields::{classification_field, normal_fields};
+use crate::{header_bounds, pose};
+
+/// Output format of `--info`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InfoFormat {
+    /// Aligned tables for reading in a terminal.
+    #[default]
+    Table,
+    /// A JSON array with one object per file, for scripts.
+    Json,
+}
+
+impl std::str::FromStr for InfoFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Invalid info format: {s}")),
+        }
+    }
+}
+
+/// Scans and images of an E57 file, as read from its XML section.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileInfo {
+    pub path: PathBuf,
+    pub guid: String,
+    pub scans: Vec<ScanDetails>,
+    pub images: Vec<ImageDetails>,
+}
+
+/// Header of a scan.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanDetails {
+    pub index: usize,
+    pub name: Option<String>,
+    pub guid: Option<String>,
+    /// Number of records, including invalid points.
+    pub records: u64,
+    /// Point attributes of the prototype, e.g. `rgb`, `intensity`, `normals` or `time`.
+    pub attributes: Vec<&'static str>,
+    /// Local Cartesian bounds from the header, if the file stores them.
+    pub bounds: Option<Bounds>,
+    /// Pose of the scan in file-level coordinates.
+    pub transform: Option<Pose>,
+}
+
+/// Header of an embedded image.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageDetails {
+    pub index: usize,
+    pub name: Option<String>,
+    /// Index of the scan the image was taken with.
+    pub scan: Option<usize>,
+    /// `pinhole`, `spherical`, `cylindrical` or `visual` for visual reference images.
+    pub projection: Option<&'static str>,
+    pub format: Option<&'static str>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Pose of the camera in file-level coordinates.
+    pub transform: Option<Pose>,
+}
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+/// Rigid transform from a local frame to file-level coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Pose {
+    pub translation: [f64; 3],
+    /// Rotation as a unit quaternion in `[x, y, z, w]` order.
+    pub rotation: [f64; 4],
+}
+
+impl From<&e57::Transform> for Pose {
+    fn from(transform: &e57::Transform) -> Self {
+        let (t, q) = pose(transform);
+        Self {
+            translation: t.to_array(),
+            rotation: q.to_array(),
+        }
+    }
+}
+
+/// Reads the structure of the E57 file at `path`, without reading any points or images.
+pub fn inspect_e57(path: &Path) -> Result<FileInfo> {
+    let reader =
+        E57Reader::from_file(path).with_context(|| format!("Failed to read E57 file: {path:?}"))?;
+    let pointclouds = reader.pointclouds();
+
+    let scans = pointclouds
+        .iter()
+        .enumerate()
+        .map(|(index, pc)| ScanDetails {
+            index,
+            name: pc.name.clone(),
+            guid: pc.guid.clone(),
+            records: pc.records,
+            attributes: attributes(pc),
+            bounds: header_bounds(pc).map(|(min, max)| Bounds { min, max }),
+            transform: pc.transform.as_ref().map(Pose::from),
+        })
+        .collect();
+
+    let images = reader
+        .images()
+        .iter()
+        .enumerate()
+        .map(|(index, image)| {
+            let representation = match (&image.projection, &image.visual_reference) {
+                (Some(Projection::Pinhole(p)), _) => {
+                    Some(("pinhole", &p.blob, p.properties.width, p.properties.height))
+                }
+                (Some(Projection::Spherical(s)), _) => Some((
+                    "spherical",
+                    &s.blob,
+                    s.properties.width,
+                    s.properties.height,
+                )),
+                (Some(Projection::Cylindrical(c)), _) => Some((
+                    "cylindrical",
+                    &c.blob,
+                    c.properties.width,
+                    c.properties.height,
+                )),
+                (None, Some(v)) => {
+                    Some(("visual", &v.blob, v.properties.width, v.properties.height))
+                }
+                (None, None) => None,
+            };
+            ImageDetails {
+                index,
+                name: image.name.clone(),
+                scan: image.pointcloud_guid.as_ref().and_then(|guid| {
+                    pointclouds
+                        .iter()
+                        .position(|pc| pc.guid.as_ref() == Some(guid))
+                }),
+                projection: representation.map(|(projection, ..)| projection),
+                format: representation.map(|(_, blob, ..)| match blob.format {
+                    ImageFormat::Jpeg => "jpeg",
+                    ImageFormat::Png => "png",
+                }),
+                width: representation.map(|(.., width, _)| width),
+                height: representation.map(|(.., height)| height),
+                transform: image.transform.as_ref().map(Pose::from),
+            }
+        })
+        .collect();
+
+    Ok(FileInfo {
+        path: path.to_owned(),
+        guid: reader.guid().to_owned(),
+        scans,
+        images,
+    })
+}
+
+/// Returns the names of the point attributes in the prototype of a point cloud.
+fn attributes(pc: &PointCloud) -> Vec<&'static str> {
+    [
+        (pc.has_cartesian(), "cartesian"),
+        (pc.has_spherical(), "spherical"),
+        (pc.has_color(), "rgb"),
+        (pc.has_intensity(), "intensity"),
+        (pc.has_row_column(), "row_column"),
+        (pc.has_return(), "return"),
+        (pc.has_timestamp(), "time"),
+        (normal_fields(pc).is_some(), "normals"),
+        (classification_field(pc).is_some(), "classification"),
+    ]
+    .into_iter()
+    .filter_map(|(has, name)| has.then_some(name))
+    .collect()
+}
+
+/// Prints the scans and images as aligned tables.
+impl fmt::Display for FileInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} scans, {} images",
+            self.path.display(),
+            self.scans.len(),
+            self.images.len()
+        )?;
+
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_owned());
+        let vector = |v: &[f64]| {
+            let values: Vec<_> = v.iter().map(|x| format!("{x:.3}")).collect();
+            format!("({})", values.join(", "))
+        };
+
+        let scans = self.scans.iter().map(|scan| {
+            vec![
+                scan.index.to_string(),
+                optional(scan.name.clone()),
+                scan.records.to_string(),
+                scan.attributes.join(","),
+                optional(
+                    scan.bounds
+                        .map(|b| format!("{} - {}", vector(&b.min), vector(&b.max))),
+                ),
+                optional(scan.transform.map(|t| vector(&t.translation))),
+            ]
+        });
+        write_table(
+            f,
+            &[
+                "Scan",
+                "Name",
+                "Records",
+                "Attributes",
+                "Bounds",
+                "Position",
+            ],
+            scans,
+        )?;
+
+        if !self.images.is_empty() {
+            writeln!(f)?;
+            let images = self.images.iter().map(|image| {
+                vec![
+                    image.index.to_string(),
+                    optional(image.name.clone()),
+                    optional(image.scan.map(|scan| scan.to_string())),
+                    optional(image.projection.map(str::to_owned)),
+                    optional(image.format.map(str::to_owned)),
+                    optional(
+                        image
+                            .width
+                            .zip(image.height)
+                            .map(|(w, h)| format!("{w}x{h}")),
+                    ),
+                    optional(image.transform.map(|t| vector(&t.translation))),
+                ]
+            });
+            write_table(
+                f,
+                &[
+                    "Image",
+                    "Name",
+                    "Scan",
+                    "Projection",
+                    "Format",
+                    "Size",
+                    "Position",
+                ],
+                images,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes rows with left-aligned columns as wide as their longest cell.
+fn write_table(
+    f: &mut fmt::Formatter<'_>,
+    header: &[&str],
+    rows: impl Iterator<Item = Vec<String>>,
+) -> fmt::Result {
+    let mut rows: Vec<Vec<String>> = rows.collect();
+    rows.insert(0, header.iter().map(|s| s.to_string()).collect());
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for row in rows {
+        let cells: Vec<_> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        writeln!(f, "{}", cells.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prints_scans_as_aligned_table() {
+        let info = FileInfo {
+            path: PathBuf::from("site.e57"),
+            guid: "file".to_owned(),
+            scans: vec![ScanDetails {
+                index: 0,
+                name: Some("Station A".to_owned()),
+                guid: None,
+                records: 10000,
+                attributes: vec!["cartesian", "rgb"],
+                bounds: None,
+                transform: Some(Pose {
+                    translation: [10.0, 0.0, 1.5],
+                    rotation: [0.0, 0.0, 0.0, 1.0],
+                }),
+            }],
+            images: Vec::new(),
+        };
+
+        assert_eq!(
+            info.to_string(),
+            "site.e57: 1 scans, 0 images\n\
+             Scan  Name       Records  Attributes     Bounds  Position\n\
+             0     Station A  10000    cartesian,rgb  -       (10.000, 0.000, 1.500)\n"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! A static kd-tree over a slice of points, for nearest-neighbor queries.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// A neighbor found by a query, ordered by its squared distance.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Neighbor {
+    pub distance_squared: f32,
+    pub index: u32,
+}
+
+impl PartialEq for Neighbor {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Neighbor {}
+
+impl PartialOrd for Neighbor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbor {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance_squared.total_cmp(&other.distance_squared)
+    }
+}
+
+/// Balanced kd-tree stored implicitly as a permutation of the point indices: the median of
+/// every subrange is its node, splitting on the axes in turn.
+pub(crate) struct KdTree<'a> {
+    points: &'a [[f32; 3]],
+    indices: Vec<u32>,
+}
+
+impl<'a> KdTree<'a> {
+    pub fn new(points: &'a [[f32; 3]]) -> Self {
+        let mut indices: Vec<u32> = (0..points.len() as u32).collect();
+        build(points, &mut indices, 0);
+        Self { points, indices }
+    }
+
+    /// Returns up to `k` nearest neighbors of `query` within `max_distance`, including the
+    /// point itself if it is part of the tree, sorted by distance.
+    pub fn nearest(&self, query: [f32; 3], k: usize, max_distance: f32) -> Vec<Neighbor> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        if k > 0 {
+            self.search(
+                0,
+                self.indices.len(),
+                0,
+                query,
+                k,
+                max_distance * max_distance,
+                &mut heap,
+            );
+        }
+        heap.into_sorted_vec()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        &self,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        query: [f32; 3],
+        k: usize,
+        max_distance_squared: f32,
+        heap: &mut BinaryHeap<Neighbor>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let index = self.indices[mid];
+        let point = self.points[index as usize];
+
+        let distance_squared = (0..3).map(|i| (query[i] - point[i]).powi(2)).sum::<f32>();
+        if distance_squared <= max_distance_squared {
+            heap.push(Neighbor {
+                distance_squared,
+                index,
+            });
+            if heap.len() > k {
+                heap.pop();
+            }
+        }
+
+        let axis = depth % 3;
+        let diff = query[axis] - point[axis];
+        let (near, far) = if diff < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+
+        self.search(
+            near.0,
+            near.1,
+            depth + 1,
+            query,
+            k,
+            max_distance_squared,
+            heap,
+        );
+
+        // The other side can only hold closer points if the splitting plane is closer
+        // than the current k-th neighbor.
+        let bound = match heap.peek() {
+            Some(farthest) if heap.len() == k => {
+                farthest.distance_squared.min(max_distance_squared)
+            }
+            _ => max_distance_squared,
+        };
+        if diff * diff <= bound {
+            self.search(
+                far.0,
+                far.1,
+                depth + 1,
+                query,
+                k,
+                max_distance_squared,
+                heap,
+            );
+        }
+    }
+}
+
+fn build(points: &[[f32; 3]], indices: &mut [u32], depth: usize) {
+    if indices.len() <= 1 {
+        return;
+    }
+    let axis = depth % 3;
+    let mid = indices.len() / 2;
+    indices.select_nth_unstable_by(mid, |a, b| {
+        points[*a as usize][axis].total_cmp(&points[*b as usize][axis])
+    });
+    let (left, right) = indices.split_at_mut(mid);
+    build(points, left, depth + 1);
+    build(points, &mut right[1..], depth + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn finds_the_same_neighbors_as_a_linear_search() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let points: Vec<[f32; 3]> = (0..500).map(|_| rng.gen::<[f32; 3]>()).collect();
+        let tree = KdTree::new(&points);
+
+        for query in points.iter().step_by(37) {
+            let mut expected: Vec<f32> = points
+                .iter()
+                .map(|p| (0..3).map(|i| (query[i] - p[i]).powi(2)).sum())
+                .collect();
+            expected.sort_by(f32::total_cmp);
+            expected.truncate(8);
+
+            let found: Vec<f32> = tree
+                .nearest(*query, 8, f32::INFINITY)
+                .iter()
+                .map(|n| n.distance_squared)
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+}
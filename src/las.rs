@@ -0,0 +1,426 @@
+//! Loads uncompressed LAS point clouds (versions 1.0 to 1.4, point formats 0 to 10).
+//!
+//! LAS files hold a single point cloud, which is logged like an E57 scan as `{prefix}/scan_0`.
+//! Compressed LAZ point data is recognized but not decoded.
+
+use anyhow::{Context, Result};
+use glam::{DQuat, DVec3};
+use rerun::RecordingStream;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::pipeline::{Loader, ScanInfo, ScanPipeline, SourcePoint};
+use crate::{validate_options, ColorBy, IntensityRange, LoadOptions, Stats};
+
+/// Size of the public header block of LAS 1.0 to 1.2, which later versions extend.
+const MIN_HEADER_SIZE: usize = 227;
+
+/// Minimum point record length of the point formats 0 to 10.
+const POINT_RECORD_LENGTHS: [usize; 11] = [20, 28, 26, 34, 57, 63, 30, 36, 38, 59, 67];
+
+/// The parts of the LAS public header block needed to decode the points.
+#[derive(Debug, Clone)]
+struct LasHeader {
+    version: (u8, u8),
+    system_identifier: String,
+    generating_software: String,
+    offset_to_points: u64,
+    point_format: u8,
+    point_record_length: usize,
+    point_count: u64,
+    scale: [f64; 3],
+    offset: [f64; 3],
+    min: [f64; 3],
+    max: [f64; 3],
+}
+
+impl LasHeader {
+    fn read(reader: &mut impl Read) -> Result<Self> {
+        let mut bytes = vec![0; MIN_HEADER_SIZE];
+        reader
+            .read_exact(&mut bytes)
+            .context("File is too short for a LAS header")?;
+        anyhow::ensure!(&bytes[0..4] == b"LASF", "Missing LAS file signature");
+
+        let header_size = u16_at(&bytes, 94) as usize;
+        anyhow::ensure!(
+            header_size >= MIN_HEADER_SIZE,
+            "Invalid LAS header size: {header_size}"
+        );
+        bytes.resize(header_size, 0);
+        reader
+            .read_exact(&mut bytes[MIN_HEADER_SIZE..])
+            .context("File is too short for its LAS header")?;
+
+        let version = (bytes[24], bytes[25]);
+        let point_format = bytes[104];
+        // Compressed point formats have the highest bit set, older LASzip versions the one below.
+        anyhow::ensure!(
+            point_format & 0xc0 == 0,
+            "Compressed LAZ point data is not supported"
+        );
+        let min_record_length = POINT_RECORD_LENGTHS
+            .get(point_format as usize)
+            .with_context(|| format!("Unsupported LAS point format: {point_format}"))?;
+        let point_record_length = u16_at(&bytes, 105) as usize;
+        anyhow::ensure!(
+            point_record_length >= *min_record_length,
+            "Point record length {point_record_length} is too short for point format {point_format}"
+        );
+
+        // LAS 1.4 moved the point count to a 64-bit field, leaving the legacy one at zero
+        // for files with more than 2^32 points or one of the new point formats.
+        let legacy_count = u32_at(&bytes, 107) as u64;
+        let point_count = if version >= (1, 4) && header_size >= 255 && legacy_count == 0 {
+            u64::from_le_bytes(bytes[247..255].try_into()?)
+        } else {
+            legacy_count
+        };
+
+        let f64s = |offset: usize| [0, 1, 2].map(|i| f64_at(&bytes, offset + 8 * i));
+        let [max_x, min_x, max_y, min_y, max_z, min_z] =
+            [0, 1, 2, 3, 4, 5].map(|i| f64_at(&bytes, 179 + 8 * i));
+
+        Ok(Self {
+            version,
+            system_identifier: ascii_at(&bytes, 26, 32),
+            generating_software: ascii_at(&bytes, 58, 32),
+            offset_to_points: u32_at(&bytes, 96) as u64,
+            point_format,
+            point_record_length,
+            point_count,
+            scale: f64s(131),
+            offset: f64s(155),
+            min: [min_x, min_y, min_z],
+            max: [max_x, max_y, max_z],
+        })
+    }
+
+    /// Byte offset of the RGB color within a point record, if the point format has colors.
+    fn color_offset(&self) -> Option<usize> {
+        match self.point_format {
+            2 => Some(20),
+            3 | 5 => Some(28),
+            7 | 8 | 10 => Some(30),
+            _ => None,
+        }
+    }
+
+    /// Decodes a point record, with the position relative to the minimum of the header bounds.
+    fn decode(&self, record: &[u8]) -> SourcePoint {
+        let position = [0, 1, 2].map(|i| {
+            let raw = i32::from_le_bytes(record[4 * i..4 * i + 4].try_into().unwrap_or_default());
+            raw as f64 * self.scale[i] + self.offset[i] - self.min[i]
+        });
+        let classification = if self.point_format < 6 {
+            record[15] & 0x1f
+        } else {
+            record[16]
+        };
+        // Return numbers start at 1, and are packed into 3 or 4 bits with the number of returns.
+        let (number, count) = if self.point_format < 6 {
+            (record[14] & 0x07, (record[14] >> 3) & 0x07)
+        } else {
+            (record[14] & 0x0f, record[14] >> 4)
+        };
+
+        SourcePoint {
+            position: DVec3::from(position),
+            // LAS colors are 16-bit.
+            color: self.color_offset().map(|offset| {
+                let [r, g, b] = [0, 1, 2].map(|i| (u16_at(record, offset + 2 * i) >> 8) as u8);
+                rerun::Color::from_rgb(r, g, b)
+            }),
+            intensity: Some(u16_at(record, 12) as f32),
+            row: -1,
+            column: -1,
+            classification: Some(classification),
+            normal: None,
+            time: None,
+            returns: (number > 0 && count > 0).then(|| (u32::from(number - 1), u32::from(count))),
+            extras: Default::default(),
+        }
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap_or_default())
+}
+
+fn f64_at(bytes: &[u8], offset: usize) -> f64 {
+    f64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap_or_default())
+}
+
+/// Reads a NUL-padded ASCII field.
+fn ascii_at(bytes: &[u8], offset: usize, len: usize) -> String {
+    let field = &bytes[offset..offset + len];
+    let end = field.iter().position(|&b| b == 0).unwrap_or(len);
+    String::from_utf8_lossy(&field[..end]).trim().to_owned()
+}
+
+/// Iterates over the raw point records of a LAS file.
+struct PointRecords {
+    reader: BufReader<File>,
+    record: Vec<u8>,
+    remaining: u64,
+}
+
+impl PointRecords {
+    fn open(path: &Path, header: &LasHeader) -> Result<Self> {
+        let mut reader = BufReader::new(
+            File::open(path).with_context(|| format!("Failed to read LAS file: {path:?}"))?,
+        );
+        reader.seek(SeekFrom::Start(header.offset_to_points))?;
+        Ok(Self {
+            reader,
+            record: vec![0; header.point_record_length],
+            remaining: header.point_count,
+        })
+    }
+
+    /// Returns the next record, or `None` after the last one.
+    fn next_record(&mut self) -> Result<Option<&[u8]>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        self.reader
+            .read_exact(&mut self.record)
+            .context("LAS file ends before its last point")?;
+        Ok(Some(&self.record))
+    }
+}
+
+/// Renders the LAS header as a Markdown table.
+fn las_metadata(header: &LasHeader) -> String {
+    let mut rows = vec![
+        (
+            "Version",
+            format!("{}.{}", header.version.0, header.version.1),
+        ),
+        ("Point format", header.point_format.to_string()),
+        ("Points", header.point_count.to_string()),
+    ];
+    if !header.system_identifier.is_empty() {
+        rows.push(("System", header.system_identifier.clone()));
+    }
+    if !header.generating_software.is_empty() {
+        rows.push(("Software", header.generating_software.clone()));
+    }
+
+    let mut markdown = String::from("| Field | Value |\n| --- | --- |\n");
+    for (field, value) in rows {
+        markdown.push_str(&format!("| {field} | {} |\n", value.replace('|', "\\|")));
+    }
+    markdown
+}
+
+/// Loads the LAS file at `path` and logs its points to `rec` as `{prefix}/scan_0`.
+///
+/// The points are logged relative to the minimum of the header bounds, which is logged as
+/// the translation of the scan unless `no_transform` is set, to keep the precision of `f32`
+/// positions for georeferenced coordinates.
+pub fn load_las(path: &Path, options: &LoadOptions, rec: &RecordingStream) -> Result<Stats> {
+    validate_options(options)?;
+
+    let header = {
+        let mut file = BufReader::new(
+            File::open(path).with_context(|| format!("Failed to read LAS file: {path:?}"))?,
+        );
+        LasHeader::read(&mut file).with_context(|| format!("Failed to read LAS file: {path:?}"))?
+    };
+
+    let entity_path_prefix: &str = &crate::entity_path_prefix(options, path, "las_pointcloud");
+    let index = 0;
+    if !crate::is_scan_selected(options, index, None, None) {
+        return Ok(Stats::default());
+    }
+    if header.point_count < crate::min_points(options) {
+        return Ok(Stats {
+            skipped_scans: 1,
+            ..Default::default()
+        });
+    }
+
+    let world = (!options.no_transform).then(|| (DVec3::from(header.min), DQuat::IDENTITY));
+    let extent = [0, 1, 2].map(|i| header.max[i] - header.min[i]);
+    let color_range = match (options.color_range, crate::geometric_color_by(options)) {
+        (Some(range), _) => Some(range),
+        (None, Some(ColorBy::Height)) => {
+            let up = options.up_axis.index();
+            let scale = options.scale.unwrap_or(1.0);
+            let base = world.map_or(0.0, |(t, _)| t[up]) * scale;
+            Some((base, base + extent[up] * scale))
+        }
+        (None, Some(ColorBy::Range)) => Some((
+            0.0,
+            DVec3::from(extent).length() * options.scale.unwrap_or(1.0),
+        )),
+        (None, _) => None,
+    };
+
+    // Every LAS point format has intensities and classes.
+    let color_by = crate::scan_color_by(options, header.color_offset().is_some(), true, true);
+    let read_intensity = color_by == ColorBy::Intensity || options.log_intensity || options.stats;
+    let intensity_range = if read_intensity {
+        match options.intensity_range {
+            IntensityRange::Limits => Some((0.0, u16::MAX as f64)),
+            IntensityRange::Auto => {
+                let mut records = PointRecords::open(path, &header)?;
+                let mut range: Option<(f64, f64)> = None;
+                while let Some(record) = records.next_record()? {
+                    let value = u16_at(record, 12) as f64;
+                    range = Some(
+                        range.map_or((value, value), |(lo, hi)| (lo.min(value), hi.max(value))),
+                    );
+                }
+                range
+            }
+            IntensityRange::Fixed(min, max) => Some((min, max)),
+        }
+    } else {
+        None
+    };
+
+    let center = crate::center_offset(options, [(([0.0; 3], extent), world)]);
+    let loader = Loader::new(
+        rec,
+        path,
+        options,
+        entity_path_prefix,
+        header.point_count,
+        color_range,
+        center,
+    )?;
+    if loader.was_logged(index) {
+        return Ok(Stats::default());
+    }
+    loader.send_blueprint([index])?;
+    let entity_path = loader.scan_path(index);
+    let mut pipeline = ScanPipeline::new(
+        &loader,
+        ScanInfo {
+            index,
+            entity_path: entity_path.clone(),
+            color_by,
+            log_intensity: options.log_intensity,
+            intensity_range,
+            normals: false,
+            extra_fields: Vec::new(),
+            bounds: Some(([0.0; 3], extent)),
+            world,
+            structured: false,
+            classified: true,
+            records: header.point_count,
+            acquisition_time: None,
+            preview: false,
+            shading: None,
+        },
+    )?;
+
+    rec.log_with_static(
+        entity_path.as_str(),
+        options.static_,
+        &rerun::TextDocument::from_markdown(las_metadata(&header)),
+    )?;
+
+    if pipeline.samples_ground() {
+        let mut records = PointRecords::open(path, &header)?;
+        while let Some(record) = records.next_record()? {
+            pipeline.sample_ground(header.decode(record).position);
+        }
+        pipeline.build_ground()?;
+    }
+    let mut records = PointRecords::open(path, &header)?;
+    while let Some(record) = records.next_record()? {
+        if crate::is_cancelled(options) {
+            break;
+        }
+        let mut point = header.decode(record);
+        if !read_intensity {
+            point.intensity = None;
+        }
+        pipeline.push(point)?;
+    }
+    let points = pipeline.finish()?;
+    let (issues, reports) = loader.finish()?;
+
+    Ok(Stats {
+        scans: 1,
+        points,
+        invalid_points: issues.invalid_points,
+        decode_errors: issues.decode_errors,
+        reports,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a LAS 1.2 header for point format 2 with the given point count.
+    fn header_bytes(point_count: u32) -> Vec<u8> {
+        let mut bytes = vec![0; MIN_HEADER_SIZE];
+        bytes[0..4].copy_from_slice(b"LASF");
+        bytes[24] = 1;
+        bytes[25] = 2;
+        bytes[26..30].copy_from_slice(b"test");
+        bytes[94..96].copy_from_slice(&(MIN_HEADER_SIZE as u16).to_le_bytes());
+        bytes[96..100].copy_from_slice(&(MIN_HEADER_SIZE as u32).to_le_bytes());
+        bytes[104] = 2;
+        bytes[105..107].copy_from_slice(&26_u16.to_le_bytes());
+        bytes[107..111].copy_from_slice(&point_count.to_le_bytes());
+        for (i, value) in [
+            0.01, 0.01, 0.01, 100.0, 200.0, 0.0, 101.0, 100.0, 202.0, 200.0, 3.0, 0.0,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            bytes[131 + 8 * i..139 + 8 * i].copy_from_slice(&f64::to_le_bytes(value));
+        }
+        bytes
+    }
+
+    #[test]
+    fn decodes_header_and_point_records() {
+        let header = LasHeader::read(&mut header_bytes(1).as_slice()).unwrap();
+        assert_eq!(header.version, (1, 2));
+        assert_eq!(header.system_identifier, "test");
+        assert_eq!(header.point_count, 1);
+        assert_eq!(header.min, [100.0, 200.0, 0.0]);
+        assert_eq!(header.max, [101.0, 202.0, 3.0]);
+
+        let mut record = vec![0; 26];
+        record[0..4].copy_from_slice(&50_i32.to_le_bytes());
+        record[4..8].copy_from_slice(&100_i32.to_le_bytes());
+        record[8..12].copy_from_slice(&150_i32.to_le_bytes());
+        record[12..14].copy_from_slice(&1000_u16.to_le_bytes());
+        // The second of three returns.
+        record[14] = (3 << 3) | 2;
+        record[15] = 0x20 | 6;
+        for (i, value) in [65535_u16, 0, 32768].into_iter().enumerate() {
+            record[20 + 2 * i..22 + 2 * i].copy_from_slice(&value.to_le_bytes());
+        }
+
+        let point = header.decode(&record);
+        assert_eq!(point.position, DVec3::new(0.5, 1.0, 1.5));
+        assert_eq!(point.intensity, Some(1000.0));
+        assert_eq!(point.classification, Some(6));
+        assert_eq!(point.returns, Some((1, 3)));
+        assert_eq!(point.color, Some(rerun::Color::from_rgb(255, 0, 128)));
+    }
+
+    #[test]
+    fn rejects_compressed_point_data() {
+        let mut bytes = header_bytes(1);
+        bytes[104] = 0x80 | 2;
+        let err = LasHeader::read(&mut bytes.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("LAZ"));
+    }
+}
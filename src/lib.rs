@@ -0,0 +1,3003 @@
+//! Loads E57 point clouds and the images embedded in them into a Rerun recording.
+//!
+//! The `rerun-loader-e57` binary is a thin command line wrapper around [`load_file`], which
+//! picks the loader by the file extension, [`load_files`] for several files and
+//! [`load_e57_dir`] for directories of E57 files.
+//! PTS and PTX files are loaded the same way with [`load_pts`] and [`load_ptx`], and with the
+//! `las` and `ply` features, uncompressed LAS files with `load_las` and PLY files with `load_ply`.
+//! [`inspect_e57`] describes the scans and images of an E57 file without loading them.
+
+use anyhow::{Context, Result};
+use e57::{
+    CartesianCoordinate, E57Reader, ImageFormat, Point, PointCloud, Projection, RecordDataType,
+    RecordName, SphericalCoordinate,
+};
+use glam::{DMat3, DQuat, DVec3};
+use rayon::prelude::*;
+use rerun::{RecordingStream, Vec3D};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+mod ascii;
+mod blueprint;
+mod cache;
+mod classification;
+mod cli;
+mod clusters;
+mod colormap;
+mod coverage;
+mod dem;
+mod diff;
+mod export;
+mod fields;
+mod gps_time;
+mod grid;
+mod ground;
+mod inspect;
+mod kdtree;
+#[cfg(feature = "las")]
+mod las;
+mod measure;
+#[cfg(feature = "mesh")]
+mod mesh;
+mod normals;
+mod perf;
+mod pipeline;
+mod planes;
+#[cfg(feature = "ply")]
+mod ply;
+mod registration;
+mod resume;
+mod samples;
+mod shading;
+mod source;
+mod stats;
+mod verify;
+
+pub use ascii::{load_pts, load_ptx};
+pub use cli::{Args, Command, DiffArgs};
+pub use colormap::Colormap;
+pub use diff::{diff_files, DiffStats};
+pub use inspect::{inspect_e57, Bounds, FileInfo, ImageDetails, InfoFormat, Pose, ScanDetails};
+#[cfg(feature = "las")]
+pub use las::load_las;
+#[cfg(feature = "ply")]
+pub use ply::load_ply;
+// Only public for the benchmarks.
+#[doc(hidden)]
+pub use pipeline::transform_points;
+
+use fields::{classification_field, ExtraFields};
+use pipeline::{Loader, ScanInfo, ScanPipeline, SourcePoint};
+
+/// Options controlling what [`load_e57`] logs and how.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Prefix for all entity paths (default: `e57_pointcloud`).
+    pub entity_path_prefix: Option<String>,
+    /// Entities the points are logged to, and where each file is rooted.
+    pub entity_layout: EntityLayout,
+    /// Indices of the scans to load, or all scans if `None`.
+    pub scans: Option<HashSet<usize>>,
+    /// Only load scans whose name matches this regular expression anywhere, so plain text
+    /// selects the scans whose name contains it. Scans without a name do not match.
+    pub scan_name: Option<regex::Regex>,
+    /// Only load the scans with these GUIDs, unless the list is empty.
+    pub scan_guids: Vec<String>,
+    /// Upper bound for the number of points logged of a file, distributed over the scans in
+    /// proportion to their number of records.
+    pub max_points: Option<u64>,
+    /// Skip scans with fewer records than this, e.g. calibration targets or aborted scans.
+    /// Empty scans are always skipped.
+    pub min_points: Option<u64>,
+    /// Point attribute used for coloring.
+    pub color_by: ColorBy,
+    /// Attributes tried in order for every scan, overriding `color_by` with the first one the
+    /// scan has. Height, range and scan colors are always available.
+    pub color_priority: Option<Vec<ColorBy>>,
+    /// Colormap for scalar attributes (default: grayscale for the intensity, turbo otherwise).
+    /// Classes use the ASPRS class colors unless a colormap is given.
+    pub colormap: Option<Colormap>,
+    /// Height or range mapped to the ends of the colormap, computed from the scan bounds if `None`.
+    pub color_range: Option<(f64, f64)>,
+    /// Log every class of a classified scan as its own `scan_N/class_K` entity.
+    pub split_by_class: bool,
+    /// Split the points of E57, LAS and PLY scans into `ground` and `non_ground` entities
+    /// with a progressive morphological filter, which reads every scan twice.
+    pub segment_ground: bool,
+    /// Number of dominant planes that are detected with RANSAC in the logged points of every
+    /// scan, and logged as thin boxes on `scan_N/planes`.
+    pub detect_planes: Option<usize>,
+    /// Split the logged points of every scan into `cluster_K` entities with DBSCAN, which keeps
+    /// them in memory until the scan is read.
+    pub cluster: Option<Clustering>,
+    /// Log structured scans as a surface mesh on `scan_N/mesh` instead of their points, which
+    /// needs the `mesh` feature.
+    pub mesh: bool,
+    /// Refine the poses of the scans of E57 files with point-to-plane ICP between overlapping
+    /// scans, and log the residuals on `registration`.
+    pub refine_registration: bool,
+    /// Log a heatmap of how many scans have points in every 25 cm voxel on `coverage`, to find
+    /// the under-scanned areas of a survey.
+    pub coverage_heatmap: bool,
+    /// Only log the points of a thin slab of file-level coordinates, and also log them
+    /// projected onto the plane of the slab on `section_2d`, e.g. for floor plans.
+    pub section: Option<Section>,
+    /// Log the oriented bounding boxes of the scans, or of their clusters with `cluster`, on
+    /// `measurements`, labeled with their dimensions along their principal axes.
+    pub measure: bool,
+    /// Size in meters of the cells of a digital elevation model of the ground points of E57,
+    /// LAS and PLY scans, which is logged as a heightmap on `dem/{prefix}`.
+    pub dem: Option<f64>,
+    /// Entity paths of the scans and their chunks with placeholders for the names of the file
+    /// and the scan, instead of `{prefix}/scan_N/chunk_M`.
+    pub entity_template: Option<EntityTemplate>,
+    /// JSON file with names and colors of classes, instead of the ASPRS ones.
+    pub class_map: Option<PathBuf>,
+    /// Thickness in meters of the horizontal slices that are logged as `scan_N/slice_K`
+    /// entities, counted from a height of 0 along the up axis.
+    pub slice_z: Option<f64>,
+    /// Size in meters of the square tiles the merged points are logged in, as
+    /// `merged/tile_X_Y` entities, in the horizontal plane of the up axis.
+    pub tile_size: Option<f64>,
+    /// Range used to normalize intensity values.
+    pub intensity_range: IntensityRange,
+    /// Log the raw intensity values as a custom `rerun_e57.Intensity` component.
+    pub log_intensity: bool,
+    /// Log the row and column indices of structured scans as custom `rerun_e57.Row` and
+    /// `rerun_e57.Column` components.
+    pub log_row_col: bool,
+    /// How per-point normals are logged, for scans that have them.
+    pub normals: NormalsMode,
+    /// Names of vendor-specific E57 attributes logged as custom `rerun_e57.{name}` components,
+    /// optionally prefixed with the namespace of their extension, e.g. `riegl:reflectance`.
+    /// Other formats have no such attributes.
+    pub extra_fields: Vec<String>,
+    /// Estimate the normals of points without them from their neighbors, to log them as
+    /// configured by `normals`.
+    pub estimate_normals: Option<NormalEstimation>,
+    /// Ignore the scan poses and show every scan in its local scanner frame.
+    pub no_transform: bool,
+    /// Apply the scan poses to the points and log the points of all scans together under
+    /// `{prefix}/merged`.
+    pub merge_scans: bool,
+    /// Factor from the units of the file to meters, for files that are not in meters. All
+    /// other lengths in the options are in meters.
+    pub scale: Option<f64>,
+    /// Vertical axis of the coordinates in the file, which are converted to Z-up.
+    pub up_axis: UpAxis,
+    /// Handedness of the coordinates in the file, which are converted to right-handed.
+    pub handedness: Handedness,
+    /// Offset subtracted from the file-level coordinates, which is logged as the transform of
+    /// the prefix, to keep the precision of the 32-bit positions for geo-referenced files.
+    pub center: Option<Center>,
+    /// Do not log the images embedded in the E57 file.
+    pub no_images: bool,
+    /// Log structured scans with row/column indices as a range image on `scan_N/depth_image`.
+    pub depth_image: bool,
+    /// Log the colors of structured scans as an equirectangular RGB image on
+    /// `scan_N/panorama`, for files without embedded images.
+    pub reconstruct_panorama: bool,
+    /// Shading baked into the point colors of structured E57 scans, for depth perception in
+    /// scans without colors and with a flat intensity.
+    pub shade: Option<Shade>,
+    /// Do not log the points themselves, e.g. when only the depth images are of interest.
+    pub no_points: bool,
+    /// Returns of multi-return scans that are loaded.
+    pub returns: Returns,
+    /// Log the bounding box of every scan as `scan_N/bounds`.
+    pub bounds: bool,
+    /// Log histograms of the ranges and intensities of every scan as bar charts under
+    /// `scan_N/stats`.
+    pub stats: bool,
+    /// How the scanner position of posed scans is shown.
+    pub show_scan_origins: ScanOrigins,
+    /// Connect the scanner positions of the posed scans in the order of their acquisition, as
+    /// `{prefix}/trajectory`.
+    pub show_trajectory: bool,
+    /// Radius of the logged points, instead of the viewer's default.
+    pub point_radius: Option<PointRadius>,
+    /// Color the points of each scan with a fixed palette color of their scan index for
+    /// `ColorBy::Scan`, and when they have neither colors nor intensities.
+    pub scan_palette: bool,
+    /// Round the logged colors to 32 levels per channel, which compress much better.
+    pub quantize_colors: bool,
+    /// Precision of the logged intensities and extra fields. The positions and normals always
+    /// keep their full precision.
+    pub precision: Precision,
+    /// Keep only the points inside the box with these min and max corners, in `crop_frame`.
+    pub crop_box: Option<([f64; 3], [f64; 3])>,
+    /// Drop points closer than this to the origin of `crop_frame`.
+    pub min_range: Option<f64>,
+    /// Drop points farther than this from the origin of `crop_frame`.
+    pub max_range: Option<f64>,
+    /// Frame in which `crop_box`, `min_range` and `max_range` are applied.
+    pub crop_frame: CropFrame,
+    /// Keep only the records of structured scans in these rows, for a quick look at a part of
+    /// a panorama. Scans without row and column indices are loaded in full.
+    pub rows: Option<std::ops::Range<i64>>,
+    /// Keep only the records of structured scans in these columns, like `rows`.
+    pub columns: Option<std::ops::Range<i64>>,
+    /// What happens to points without a valid position.
+    pub invalid_points: InvalidPoints,
+    /// Downsample each scan to one point per voxel of the given size in meters.
+    pub voxel_size: Option<f64>,
+    /// Randomly keep only the given fraction of points.
+    pub subsample: Option<f64>,
+    /// Keep only every n-th point of each scan.
+    pub every_nth: Option<usize>,
+    /// Seed for `subsample` to get reproducible results.
+    pub seed: Option<u64>,
+    /// Log an octree level-of-detail pyramid with the given number of levels.
+    pub lod_levels: Option<u32>,
+    /// Number of points per logged chunk (default: 1,000,000).
+    pub chunk_size: Option<usize>,
+    /// Approximate size of each logged chunk in bytes, as an alternative to `chunk_size`.
+    pub chunk_bytes: Option<usize>,
+    /// Upper bound in bytes for the point buffers of all workers together.
+    pub max_memory: Option<usize>,
+    /// Log all data statically.
+    pub static_: bool,
+    /// Timepoint to log all data at.
+    pub timepoint: Option<rerun::TimePoint>,
+    /// Additionally log each scan at a time taken from the file.
+    pub timeline: Option<TimeSource>,
+    /// Group the points of E57 scans with per-point time stamps into windows of this many
+    /// seconds, each logged at its start on the `acquisition` timeline.
+    pub per_point_time: Option<f64>,
+    /// Check the checksums of all pages of E57 files before loading them, and fail with a
+    /// report of the corrupt pages instead of a decode error while loading.
+    pub verify: bool,
+    /// Read E57 files through a memory map instead of buffered reads, which is faster for
+    /// large files on fast storage. The files must not be modified while they are loaded.
+    pub mmap: bool,
+    /// Do not print progress to stderr.
+    pub quiet: bool,
+    /// Log the decoded records, logged points and logged bytes per second as scalars under
+    /// `{prefix}/perf`.
+    pub log_perf: bool,
+    /// Stops the loading once set, e.g. by a Ctrl+C handler. The points read until then are
+    /// still logged, while scans and files that were not started yet are left out.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Skip the scans that an earlier load logged to the same recording ID and prefix, as
+    /// recorded in a `{file}.resume.json` sidecar that is updated after every logged scan.
+    pub resume: bool,
+    /// Flush the recording every given number of chunks or seconds, so the points reach the
+    /// viewer sooner, at the cost of smaller batches.
+    pub flush_every: Option<FlushEvery>,
+    /// Skip the E57 scans of a directory or of [`load_files`] that were already logged from
+    /// an earlier file, as identified by their GUID, or by their metadata for scans without one.
+    pub dedup_scans: bool,
+    /// Log the posed E57 scans at the same scanner position under a common
+    /// `{prefix}/station_{station}` entity, numbered in the order of their first scan.
+    pub group_stations: bool,
+    /// Directory of a cache of what was logged of every file, keyed by the file and the
+    /// options, from which repeated loads of an unchanged file are replayed without decoding,
+    /// downsampling or building the level-of-detail pyramid again.
+    pub cache: Option<PathBuf>,
+    /// Send a blueprint with a 3D view of all scans and a larger default point radius. The scan
+    /// markers are shown as `show_scan_origins` logs them.
+    pub send_blueprint: bool,
+    /// Log every 100th point of all E57 scans to `scan_N/preview` first, which is cleared once
+    /// the full resolution points of the scan are logged.
+    pub preview_first: bool,
+    /// Scans that are visible in the view of the blueprint, while the others are loaded hidden.
+    pub visible_scans: Option<VisibleScans>,
+    /// Write the points to this `.pcd` or `.las` file instead of logging them, in file-level
+    /// coordinates. Intensities are only written with `log_intensity`.
+    pub export: Option<PathBuf>,
+}
+
+/// Summary of what [`load_e57`] logged.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    /// Number of loaded scans.
+    pub scans: usize,
+    /// Number of scans that failed to load and were left out.
+    pub failed_scans: usize,
+    /// Number of selected scans that were left out for having fewer records than
+    /// `min_points`, or none at all.
+    pub skipped_scans: usize,
+    /// Number of scans that were left out by `dedup_scans`, since they were already logged.
+    pub duplicate_scans: usize,
+    /// Number of logged points, after downsampling.
+    pub points: u64,
+    /// Number of points without a valid position, handled as configured by `invalid_points`.
+    pub invalid_points: u64,
+    /// Number of points that failed to decode.
+    pub decode_errors: u64,
+    /// Number of logged images.
+    pub images: usize,
+    /// Number of files of a directory or of [`load_files`] that failed to load and were left out.
+    pub failed_files: usize,
+    /// What was logged of every loaded scan, by file and scan index.
+    pub reports: Vec<ScanReport>,
+}
+
+/// Summary of what was logged of a single scan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub file: PathBuf,
+    pub index: usize,
+    pub entity_path: String,
+    /// Number of records of the scan, including invalid ones.
+    pub records: u64,
+    /// Number of logged points, after downsampling.
+    pub points: u64,
+    /// Number of records that were not logged, because they were invalid, failed to decode,
+    /// were cropped or were dropped by downsampling.
+    pub skipped: u64,
+    pub invalid_points: u64,
+    pub decode_errors: u64,
+    /// Bounds of the logged points in the local frame of the scan, in meters.
+    pub bounds: Option<Bounds>,
+    /// Time it took to read and log the points, in seconds.
+    pub duration: f64,
+}
+
+impl std::ops::AddAssign for Stats {
+    fn add_assign(&mut self, other: Self) {
+        self.scans += other.scans;
+        self.failed_scans += other.failed_scans;
+        self.skipped_scans += other.skipped_scans;
+        self.duplicate_scans += other.duplicate_scans;
+        self.points += other.points;
+        self.invalid_points += other.invalid_points;
+        self.decode_errors += other.decode_errors;
+        self.images += other.images;
+        self.failed_files += other.failed_files;
+        self.reports.extend(other.reports);
+    }
+}
+
+/// Point attribute used for coloring.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ColorBy {
+    /// RGB colors, falling back to the intensity for scans without colors.
+    #[default]
+    Rgb,
+    /// Intensity in grayscale.
+    Intensity,
+    /// Class colors from the classification attribute.
+    Classification,
+    /// Height above the origin of the file, i.e. the Z coordinate after applying the scan pose.
+    Height,
+    /// Distance from the scanner.
+    Range,
+    /// One color per scan.
+    Scan,
+    /// One color per return index of multi-return scans.
+    Return,
+}
+
+impl std::str::FromStr for ColorBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "rgb" => Ok(Self::Rgb),
+            "intensity" => Ok(Self::Intensity),
+            "classification" => Ok(Self::Classification),
+            "height" => Ok(Self::Height),
+            "range" => Ok(Self::Range),
+            "scan" => Ok(Self::Scan),
+            "return" => Ok(Self::Return),
+            _ => Err(format!("Invalid color mode: {s}")),
+        }
+    }
+}
+
+/// Shading baked into the colors of the points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shade {
+    /// Light structured scans from the scanner, with normals from the gradients of their range
+    /// image.
+    RangeGradient,
+}
+
+impl std::str::FromStr for Shade {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "range-gradient" => Ok(Self::RangeGradient),
+            _ => Err(format!("Invalid shading: {s}")),
+        }
+    }
+}
+
+/// Parameters of the DBSCAN clustering of the points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clustering {
+    /// Distance in meters of the neighbors of a point.
+    pub eps: f64,
+    /// Fewest neighbors of a point, itself included, for it to grow a cluster.
+    pub min_points: usize,
+}
+
+impl std::str::FromStr for Clustering {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // `0.1:10` is a distance of 10 cm and at least 10 neighbors.
+        let invalid = || format!("Invalid clustering, expected <eps>:<min-pts>: {s}");
+        let (eps, min_points) = s.split_once(':').ok_or_else(invalid)?;
+        let eps = eps
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|eps| eps.is_finite() && *eps > 0.0)
+            .ok_or_else(invalid)?;
+        let min_points = min_points
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(invalid)?;
+        Ok(Self { eps, min_points })
+    }
+}
+
+/// How per-point normals are logged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NormalsMode {
+    /// Do not read the normals.
+    #[default]
+    Skip,
+    /// Attach the normals to the points as a custom `rerun_e57.Normal` component.
+    Log,
+    /// Log a subset of the normals as arrows on `chunk_N/normals`.
+    Arrows,
+}
+
+impl std::str::FromStr for NormalsMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "log" => Ok(Self::Log),
+            "arrows" => Ok(Self::Arrows),
+            _ => Err(format!("Invalid normals mode: {s}")),
+        }
+    }
+}
+
+/// Vertical axis of the coordinates in a file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UpAxis {
+    /// Z is up, as in the E57 standard.
+    #[default]
+    Z,
+    /// Y is up, with Z pointing backwards for right-handed and forwards for left-handed
+    /// coordinates.
+    Y,
+}
+
+impl UpAxis {
+    /// Index of the vertical coordinate.
+    pub(crate) fn index(self) -> usize {
+        match self {
+            Self::Z => 2,
+            Self::Y => 1,
+        }
+    }
+
+    /// Indices of the horizontal coordinates.
+    pub(crate) fn horizontal(self) -> [usize; 2] {
+        match self {
+            Self::Z => [0, 1],
+            Self::Y => [0, 2],
+        }
+    }
+}
+
+/// Slab of points perpendicular to an axis of the file-level coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Section {
+    /// Index of the axis of the file: 0 for X, 1 for Y and 2 for Z.
+    pub axis: usize,
+    /// Coordinate in meters of the middle of the slab along the axis.
+    pub offset: f64,
+    /// Thickness in meters of the slab.
+    pub thickness: f64,
+}
+
+impl Section {
+    /// Returns whether a position in file-level coordinates in meters is in the slab.
+    pub(crate) fn contains(&self, position: DVec3) -> bool {
+        (position[self.axis] - self.offset).abs() <= self.thickness / 2.0
+    }
+
+    /// Returns a position in meters projected onto the section, with the second axis
+    /// pointing up in 2D views, whose Y axis points down.
+    pub(crate) fn project(&self, position: DVec3) -> [f32; 2] {
+        let [u, v] = match self.axis {
+            0 => [1, 2],
+            1 => [0, 2],
+            _ => [0, 1],
+        };
+        [position[u] as f32, -position[v] as f32]
+    }
+}
+
+impl std::str::FromStr for Section {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // `plane=z:1.2,thickness=0.05`, where the thickness defaults to 5 cm.
+        let invalid =
+            || format!("Invalid section, expected plane=<x|y|z>:<offset>[,thickness=<m>]: {s}");
+        let mut section = None;
+        let mut thickness = 0.05;
+        for part in s.split(',') {
+            match part.trim().split_once('=').ok_or_else(invalid)? {
+                ("plane", plane) => {
+                    let (axis, offset) = plane.split_once(':').ok_or_else(invalid)?;
+                    let axis = match axis.trim() {
+                        "x" | "X" => 0,
+                        "y" | "Y" => 1,
+                        "z" | "Z" => 2,
+                        _ => return Err(invalid()),
+                    };
+                    let offset = offset.trim().parse::<f64>().map_err(|_| invalid())?;
+                    section = Some((axis, offset));
+                }
+                ("thickness", value) => {
+                    thickness = value
+                        .trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|t| t.is_finite() && *t > 0.0)
+                        .ok_or_else(invalid)?;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        let (axis, offset) = section
+            .filter(|(_, offset)| offset.is_finite())
+            .ok_or_else(invalid)?;
+        Ok(Self {
+            axis,
+            offset,
+            thickness,
+        })
+    }
+}
+
+/// Returns of a laser pulse that are loaded, for scans with return indices.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Returns {
+    /// All returns.
+    #[default]
+    All,
+    /// Only the first return of every pulse, e.g. the canopy in vegetation.
+    First,
+    /// Only the last return of every pulse, e.g. the ground below vegetation.
+    Last,
+}
+
+impl std::str::FromStr for Returns {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Self::All),
+            "first" => Ok(Self::First),
+            "last" => Ok(Self::Last),
+            _ => Err(format!("Invalid returns: {s}")),
+        }
+    }
+}
+
+/// Scans that are visible when the blueprint is sent, while the others start hidden.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VisibleScans {
+    /// The given number of scans with the lowest indices, in every file.
+    First(usize),
+    /// The scans with the given indices.
+    Indices(HashSet<usize>),
+}
+
+impl VisibleScans {
+    /// Returns the indices of the scans that start hidden.
+    pub(crate) fn hidden(&self, scans: impl IntoIterator<Item = usize>) -> Vec<usize> {
+        let mut scans: Vec<usize> = scans.into_iter().collect();
+        scans.sort_unstable();
+        match self {
+            Self::First(n) => scans.split_off((*n).min(scans.len())),
+            Self::Indices(indices) => {
+                scans.retain(|index| !indices.contains(index));
+                scans
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for VisibleScans {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // A single number is a count of scans, a selection such as `0,2,5-9` their indices.
+        if s.contains([',', '-']) {
+            parse_scan_selection(s)
+                .map(Self::Indices)
+                .map_err(|e| format!("{e:#}"))
+        } else {
+            s.trim()
+                .parse()
+                .map(Self::First)
+                .map_err(|_| format!("Invalid number of visible scans: {s}"))
+        }
+    }
+}
+
+/// Precision of the logged floating point attributes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Precision {
+    /// Full 32-bit floats.
+    #[default]
+    F32,
+    /// 16-bit floats, with about three significant decimal digits at half the size.
+    F16,
+}
+
+impl std::str::FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "f32" => Ok(Self::F32),
+            "f16" => Ok(Self::F16),
+            _ => Err(format!("Invalid precision: {s}")),
+        }
+    }
+}
+
+/// How the scanner position of a posed scan is shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScanOrigins {
+    /// A red point labeled with the scan, as `scan_N/point`.
+    #[default]
+    Point,
+    /// Arrows along the axes of the scan, as `scan_N/origin`, to show its orientation as well.
+    Axes,
+    /// Nothing.
+    None,
+}
+
+impl std::str::FromStr for ScanOrigins {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "point" => Ok(Self::Point),
+            "axes" => Ok(Self::Axes),
+            "none" => Ok(Self::None),
+            _ => Err(format!("Invalid scan origin display: {s}")),
+        }
+    }
+}
+
+impl std::str::FromStr for UpAxis {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "z" => Ok(Self::Z),
+            "y" => Ok(Self::Y),
+            _ => Err(format!("Invalid up axis: {s}")),
+        }
+    }
+}
+
+/// Handedness of the coordinates in a file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Handedness {
+    #[default]
+    Right,
+    Left,
+}
+
+impl std::str::FromStr for Handedness {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "right" => Ok(Self::Right),
+            "left" => Ok(Self::Left),
+            _ => Err(format!("Invalid handedness: {s}")),
+        }
+    }
+}
+
+/// Entities the points of a scan are logged to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EntityLayout {
+    /// One `scan_N/chunk_M` entity per chunk.
+    #[default]
+    Hierarchical,
+    /// A single `scan_N/points` entity per scan, with one row per chunk.
+    Flat,
+    /// Like `Hierarchical`, but every file is rooted at an entity named after it instead of
+    /// the default prefix.
+    PerFile,
+}
+
+impl std::str::FromStr for EntityLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "hierarchical" => Ok(Self::Hierarchical),
+            "flat" => Ok(Self::Flat),
+            "per-file" => Ok(Self::PerFile),
+            _ => Err(format!("Invalid entity layout: {s}")),
+        }
+    }
+}
+
+/// Entity paths of the scans and their chunks, e.g. `{prefix}/{file}/{scan_name}/{chunk}`,
+/// with the placeholders `{prefix}`, `{file}` for the file stem, `{index}`, `{scan_name}`
+/// and `{guid}` of the scan, and `{chunk}` for the chunk index in the last segment. Without
+/// `{chunk}`, the template is the path of the scans, and their chunks are `chunk_M`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityTemplate {
+    scan: String,
+    chunk: Option<String>,
+}
+
+impl EntityTemplate {
+    /// Returns the entity path of a scan, where a missing name or GUID is `scan_{index}`.
+    pub(crate) fn scan_path(
+        &self,
+        prefix: &str,
+        file: &Path,
+        index: usize,
+        name: &ScanName,
+    ) -> String {
+        let fallback = || format!("scan_{index}");
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+        self.scan
+            .replace("{prefix}", prefix.trim_matches('/'))
+            .replace("{file}", &entity_part(&stem))
+            .replace("{index}", &index.to_string())
+            .replace(
+                "{scan_name}",
+                &name.name.as_deref().map_or_else(fallback, entity_part),
+            )
+            .replace(
+                "{guid}",
+                &name.guid.as_deref().map_or_else(fallback, entity_part),
+            )
+    }
+
+    /// Returns the last segment of the entity path of a chunk, if the template has one.
+    pub(crate) fn chunk_name(&self, index: usize) -> Option<String> {
+        let chunk = self.chunk.as_ref()?;
+        Some(chunk.replace("{chunk}", &index.to_string()))
+    }
+
+    /// Whether the template has a `{chunk}` segment.
+    pub(crate) fn has_chunk(&self) -> bool {
+        self.chunk.is_some()
+    }
+}
+
+impl std::str::FromStr for EntityTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed placeholder in entity template: {s}"))?;
+            let placeholder = &rest[start + 1..start + end];
+            if !["prefix", "file", "index", "scan_name", "guid", "chunk"].contains(&placeholder) {
+                return Err(format!(
+                    "Invalid placeholder {{{placeholder}}} in entity template, expected \
+                     {{prefix}}, {{file}}, {{index}}, {{scan_name}}, {{guid}} or {{chunk}}: {s}"
+                ));
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        let (scan, chunk) = match s.rsplit_once('/') {
+            Some((scan, last)) if last.contains("{chunk}") => (scan, Some(last.to_owned())),
+            _ => (s, None),
+        };
+        if scan.contains("{chunk}") || (chunk.is_none() && s.contains("{chunk}")) {
+            return Err(format!(
+                "{{chunk}} can only be in the last segment of an entity template, after the scan: {s}"
+            ));
+        }
+        // Every scan needs a path of its own. Scans of a file with the same name or GUID are
+        // told apart by their index in `scan_path`.
+        if !["{index}", "{scan_name}", "{guid}"]
+            .iter()
+            .any(|placeholder| scan.contains(placeholder))
+        {
+            return Err(format!(
+                "Entity template needs {{index}}, {{scan_name}} or {{guid}} before the chunk: {s}"
+            ));
+        }
+        Ok(Self {
+            scan: scan.to_owned(),
+            chunk,
+        })
+    }
+}
+
+/// Returns a name as a single entity path part, with the characters other than letters,
+/// digits, `-`, `_` and `.` replaced by `_`, e.g. the braces of GUIDs.
+fn entity_part(name: &str) -> String {
+    let part: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    part.trim_matches('_').to_owned()
+}
+
+/// Offset subtracted from the file-level coordinates with `--center`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Center {
+    /// The center of the bounds of all loaded scans.
+    Auto,
+    /// A fixed offset in meters.
+    Offset([f64; 3]),
+}
+
+impl std::str::FromStr for Center {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(Self::Auto);
+        }
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>().ok().filter(|v| v.is_finite()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("Invalid center: {s}"))?;
+        let offset: [f64; 3] = values
+            .try_into()
+            .map_err(|_| format!("Invalid center, expected auto or x,y,z: {s}"))?;
+        Ok(Self::Offset(offset))
+    }
+}
+
+/// Source of the time each scan is logged at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeSource {
+    /// The acquisition start of the scan, or of the image, on the `acquisition` timeline.
+    Acquisition,
+}
+
+impl std::str::FromStr for TimeSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "acquisition" => Ok(Self::Acquisition),
+            _ => Err(format!("Invalid timeline: {s}")),
+        }
+    }
+}
+
+/// Radius of the logged points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointRadius {
+    /// A radius in meters, so points grow when zooming in.
+    Meters(f32),
+    /// A radius in UI points, so points keep their size on screen.
+    UiPoints(f32),
+}
+
+impl std::str::FromStr for PointRadius {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // `0.02` and `0.02m` are meters, `2ui` is UI points.
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|radius| *radius > 0.0)
+                .ok_or_else(|| format!("Invalid point radius: {s}"))
+        };
+        match s.strip_suffix("ui") {
+            Some(value) => parse(value).map(Self::UiPoints),
+            None => parse(s.strip_suffix('m').unwrap_or(s)).map(Self::Meters),
+        }
+    }
+}
+
+impl From<PointRadius> for rerun::Radius {
+    fn from(radius: PointRadius) -> Self {
+        match radius {
+            PointRadius::Meters(meters) => Self::new_scene_units(meters),
+            PointRadius::UiPoints(points) => Self::new_ui_points(points),
+        }
+    }
+}
+
+/// How often the recording is flushed while the points are logged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlushEvery {
+    /// After every given number of logged chunks.
+    Chunks(u64),
+    /// Whenever a chunk is logged after the given interval since the last flush.
+    Interval(std::time::Duration),
+}
+
+impl std::str::FromStr for FlushEvery {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // `10` is a number of chunks, `2s` and `0.5s` are seconds.
+        let invalid = || format!("Invalid flush interval: {s}");
+        match s.strip_suffix('s') {
+            Some(seconds) => seconds
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+                .map(|seconds| Self::Interval(std::time::Duration::from_secs_f64(seconds)))
+                .ok_or_else(invalid),
+            None => s
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|chunks| *chunks > 0)
+                .map(Self::Chunks)
+                .ok_or_else(invalid),
+        }
+    }
+}
+
+/// Frame in which the cropping filters are applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CropFrame {
+    /// File-level coordinates, i.e. after applying the scan pose.
+    #[default]
+    World,
+    /// The local frame of each scan, centered on the scanner.
+    Scanner,
+}
+
+impl std::str::FromStr for CropFrame {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "world" => Ok(Self::World),
+            "scanner" => Ok(Self::Scanner),
+            _ => Err(format!("Invalid crop frame: {s}")),
+        }
+    }
+}
+
+/// Handling of points without a valid position, e.g. without a return or only a direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidPoints {
+    /// Drop the points.
+    #[default]
+    Skip,
+    /// Log the points at the origin of their scan.
+    Zero,
+    /// Log the points as `scan_N/invalid`, at their direction one meter from the scanner if
+    /// they have one.
+    LogSeparately,
+}
+
+impl std::str::FromStr for InvalidPoints {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "zero" => Ok(Self::Zero),
+            "log-separately" => Ok(Self::LogSeparately),
+            _ => Err(format!("Invalid invalid-points mode: {s}")),
+        }
+    }
+}
+
+/// Neighborhood used to estimate a normal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalEstimation {
+    /// The given number of nearest neighbors.
+    Neighbors(usize),
+    /// All neighbors within the given radius in meters, up to 64.
+    Radius(f64),
+}
+
+impl std::str::FromStr for NormalEstimation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // Integers are neighbor counts, anything else like `0.05` is a radius.
+        match s.parse::<usize>() {
+            Ok(k) => Ok(Self::Neighbors(k)),
+            Err(_) => s
+                .parse::<f64>()
+                .map(Self::Radius)
+                .map_err(|_| format!("Invalid normal estimation: {s}")),
+        }
+    }
+}
+
+/// Source of the min/max range used to normalize intensity values.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum IntensityRange {
+    /// The intensity limits stored in the E57 file.
+    #[default]
+    Limits,
+    /// The actual min/max intensity, computed in an extra pass over the points.
+    Auto,
+    /// A fixed min/max range.
+    Fixed(f64, f64),
+}
+
+impl std::str::FromStr for IntensityRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "limits" => Ok(Self::Limits),
+            "auto" => Ok(Self::Auto),
+            _ => {
+                let (min, max) = s
+                    .split_once(',')
+                    .ok_or_else(|| format!("Invalid intensity range: {s}"))?;
+                let min = min.trim().parse::<f64>().map_err(|e| e.to_string())?;
+                let max = max.trim().parse::<f64>().map_err(|e| e.to_string())?;
+                Ok(Self::Fixed(min, max))
+            }
+        }
+    }
+}
+
+/// Parses a `min,max` pair.
+pub(crate) fn parse_min_max(value: &str) -> Result<(f64, f64), String> {
+    let (min, max) = value
+        .split_once(',')
+        .ok_or_else(|| format!("Expected min,max: {value}"))?;
+    let min = min.trim().parse::<f64>().map_err(|e| e.to_string())?;
+    let max = max.trim().parse::<f64>().map_err(|e| e.to_string())?;
+    Ok((min, max))
+}
+
+/// Parses a range of row or column indices, whose ends can be left out.
+pub(crate) fn parse_index_range(value: &str) -> Result<std::ops::Range<i64>, String> {
+    let (start, end) = value
+        .split_once("..")
+        .ok_or_else(|| format!("Expected a..b: {value}"))?;
+    let parse = |index: &str, default| match index.trim() {
+        "" => Ok(default),
+        index => index.parse::<i64>().map_err(|e| e.to_string()),
+    };
+    Ok(parse(start, 0)?..parse(end, i64::MAX)?)
+}
+
+/// Parses a comma-separated list of color modes.
+pub(crate) fn parse_color_priority(value: &str) -> Result<Vec<ColorBy>, String> {
+    value
+        .split(',')
+        .map(|mode| mode.trim().parse::<ColorBy>())
+        .collect()
+}
+
+/// Parses a length unit into its length in meters.
+pub(crate) fn parse_units(value: &str) -> Result<f64, String> {
+    match value {
+        "mm" => Ok(0.001),
+        "cm" => Ok(0.01),
+        "m" => Ok(1.0),
+        "ft" => Ok(0.3048),
+        _ => Err(format!("Invalid units: {value}")),
+    }
+}
+
+/// Parses the `xmin,ymin,zmin,xmax,ymax,zmax` corners of a box.
+pub(crate) fn parse_crop_box(value: &str) -> Result<([f64; 3], [f64; 3]), String> {
+    let values = value
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match values[..] {
+        [xmin, ymin, zmin, xmax, ymax, zmax] => Ok(([xmin, ymin, zmin], [xmax, ymax, zmax])),
+        _ => Err(format!("Expected xmin,ymin,zmin,xmax,ymax,zmax: {value}")),
+    }
+}
+
+/// Whether the scan at `index`, with its name and GUID if the format has them, passes all scan
+/// selections of the options.
+pub(crate) fn is_scan_selected(
+    options: &LoadOptions,
+    index: usize,
+    name: Option<&str>,
+    guid: Option<&str>,
+) -> bool {
+    options
+        .scans
+        .as_ref()
+        .is_none_or(|scans| scans.contains(&index))
+        && options
+            .scan_name
+            .as_ref()
+            .is_none_or(|regex| name.is_some_and(|name| regex.is_match(name)))
+        && (options.scan_guids.is_empty()
+            || guid.is_some_and(|guid| options.scan_guids.iter().any(|g| g == guid)))
+}
+
+/// Returns the number of records a scan needs to be loaded.
+pub(crate) fn min_points(options: &LoadOptions) -> u64 {
+    options.min_points.unwrap_or(0).max(1)
+}
+
+/// Returns whether the loading was cancelled through `LoadOptions::cancel`.
+pub(crate) fn is_cancelled(options: &LoadOptions) -> bool {
+    options
+        .cancel
+        .as_ref()
+        .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+}
+
+/// Parses a scan selection such as `0,2,5-9` into a set of scan indices.
+pub fn parse_scan_selection(selection: &str) -> Result<HashSet<usize>> {
+    let mut scans = HashSet::new();
+
+    for part in selection
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+    {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let start = start
+                    .trim()
+                    .parse::<usize>()
+                    .with_context(|| format!("Invalid scan range: {part}"))?;
+                let end = end
+                    .trim()
+                    .parse::<usize>()
+                    .with_context(|| format!("Invalid scan range: {part}"))?;
+                anyhow::ensure!(start <= end, "Invalid scan range: {part}");
+                scans.extend(start..=end);
+            }
+            None => {
+                let index = part
+                    .parse::<usize>()
+                    .with_context(|| format!("Invalid scan index: {part}"))?;
+                scans.insert(index);
+            }
+        }
+    }
+
+    Ok(scans)
+}
+
+/// Loads the E57 file at `path` and logs its scans and images to `rec`.
+pub fn load_e57(path: &Path, options: &LoadOptions, rec: &RecordingStream) -> Result<Stats> {
+    validate_options(options)?;
+    if options.verify {
+        verify::verify_e57(path)?;
+    }
+
+    let entity_path_prefix: &str = &entity_path_prefix(options, path, "e57_pointcloud");
+    // The XML section is usually written last, so nothing can be recovered from a
+    // truncated file, but the recording still shows why.
+    let mut reader = match source::open_e57(path, options.mmap) {
+        Ok(reader) => reader,
+        Err(mut error) => {
+            if let Some(truncation) = verify::truncation(path) {
+                error = error.context(truncation);
+                pipeline::warn(rec, options, entity_path_prefix, &format!("{error:#}"));
+            }
+            return Err(error);
+        }
+    };
+
+    let mut pointclouds = reader.pointclouds();
+    let mut selected = Vec::new();
+    let mut skipped_scans = 0;
+    for (index, pointcloud) in pointclouds.iter().enumerate() {
+        if !pointcloud.has_cartesian() && !pointcloud.has_spherical() {
+            eprintln!("Point cloud #{index} has no XYZ or spherical data, skipping...");
+            continue;
+        }
+
+        if !is_scan_selected(
+            options,
+            index,
+            pointcloud.name.as_deref(),
+            pointcloud.guid.as_deref(),
+        ) {
+            continue;
+        }
+
+        if pointcloud.records < min_points(options) {
+            skipped_scans += 1;
+            continue;
+        }
+
+        selected.push(index);
+    }
+    if options.refine_registration {
+        registration::refine_registration(
+            &mut reader,
+            rec,
+            options,
+            entity_path_prefix,
+            &mut pointclouds,
+            &selected,
+        )?;
+    }
+    let scans: Vec<(usize, &PointCloud)> = selected
+        .iter()
+        .map(|&index| (index, &pointclouds[index]))
+        .collect();
+
+    let auto_color_range = options.color_range.is_none() && geometric_color_by(options).is_some();
+    let scan_bounds = if auto_color_range || options.center == Some(Center::Auto) {
+        scan_bounds_and_poses(&mut reader, options, &scans)?
+    } else {
+        Vec::new()
+    };
+    let color_range = match options.color_range {
+        Some(range) => Some(range),
+        None if auto_color_range => bounds_color_range(options, scan_bounds.iter().copied()),
+        None => None,
+    };
+    let center = center_offset(options, scan_bounds);
+
+    let total = scans.iter().map(|(_, pointcloud)| pointcloud.records).sum();
+    let mut loader = Loader::new(
+        rec,
+        path,
+        options,
+        entity_path_prefix,
+        total,
+        color_range,
+        center,
+    )?;
+    loader.stations = scan_stations(options, &pointclouds);
+    loader.template_paths =
+        template_paths(options, path, entity_path_prefix, &scan_names(&pointclouds));
+    loader.send_blueprint(scans.iter().map(|(index, _)| *index))?;
+
+    // The provenance of the file is preserved on the prefix entity, above all scans.
+    rec.log_with_static(
+        entity_path_prefix,
+        options.static_,
+        &rerun::TextDocument::from_markdown(file_metadata(path, &reader, pointclouds.len())),
+    )?;
+
+    // The previews of all scans are logged before the full resolution of any, so that the whole
+    // survey shows up quickly. A scan whose preview fails is still loaded in full.
+    // Their extents are found once for both passes.
+    let extents: Vec<Option<ScanExtents>> = if options.preview_first {
+        scans
+            .par_iter()
+            .map(|(index, pointcloud)| {
+                if is_cancelled(options) || loader.is_logged(*index) {
+                    return None;
+                }
+                // The full pass finds the extents again if this fails.
+                let extents = source::open_e57(path, options.mmap)
+                    .and_then(|mut reader| scan_extents(options, &mut reader, pointcloud))
+                    .ok();
+                let preview = load_scan(&loader, *index, pointcloud, extents, true);
+                if let Err(e) = preview {
+                    eprintln!("Failed to log the preview of point cloud #{index}: {e:#}");
+                }
+                extents
+            })
+            .collect()
+    } else {
+        vec![None; scans.len()]
+    };
+
+    // Every worker opens its own reader, since decoding needs exclusive access to it.
+    // A failing scan is reported and left out, so the other scans still load, and scans that
+    // were not started before a cancellation are left out as well.
+    let (points, loaded_scans, failed_scans) = scans
+        .par_iter()
+        .zip(&extents)
+        .map(|((index, pointcloud), extents)| {
+            if is_cancelled(options) || loader.was_logged(*index) {
+                return (0, 0, 0);
+            }
+            match load_scan(&loader, *index, pointcloud, *extents, false) {
+                Ok(points) => (points, 1, 0),
+                Err(e) => {
+                    loader.warn(&format!("Failed to load point cloud #{index}: {e:#}"));
+                    (0, 0, 1)
+                }
+            }
+        })
+        .reduce(|| (0, 0, 0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
+
+    // The images need the origins of the logged points of their scans.
+    let mut images = 0;
+    if !options.no_images && !is_cancelled(options) {
+        if let Some(timepoint) = &options.timepoint {
+            rec.set_timepoint(timepoint.clone());
+        }
+        images = load_images(&loader, &mut reader, &pointclouds, &scans)?;
+    }
+    let (issues, reports) = loader.finish()?;
+
+    Ok(Stats {
+        scans: loaded_scans,
+        failed_scans,
+        skipped_scans,
+        points,
+        invalid_points: issues.invalid_points,
+        decode_errors: issues.decode_errors,
+        reports,
+        images,
+        ..Default::default()
+    })
+}
+
+/// Loads all E57 files in a directory, each under `{prefix}/{file_stem}`, in the order of
+/// their names.
+///
+/// With `recursive`, the files in subdirectories are loaded too, under their path relative
+/// to `dir`. A file that fails to load is reported and left out, so the other files still load.
+pub fn load_e57_dir(
+    dir: &Path,
+    recursive: bool,
+    options: &LoadOptions,
+    rec: &RecordingStream,
+) -> Result<Stats> {
+    validate_options(options)?;
+
+    let mut paths = Vec::new();
+    collect_e57_files(dir, recursive, &mut paths)?;
+    paths.sort();
+    anyhow::ensure!(!paths.is_empty(), "No E57 files found in {dir:?}");
+
+    let root = files_root(options);
+    let files: Vec<(PathBuf, String)> = paths
+        .into_iter()
+        .map(|path| {
+            let relative = path.strip_prefix(dir).unwrap_or(&path).with_extension("");
+            let components: Vec<_> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect();
+            let prefix = file_prefix(root, &components.join("/"));
+            (path, prefix)
+        })
+        .collect();
+    load_each(&files, root, options, rec)
+}
+
+/// Loads several files one after the other, each under `{prefix}/{file_stem}` and with the
+/// loader for its extension.
+///
+/// A file that fails to load is reported and left out, so the other files still load.
+pub fn load_files(
+    paths: &[PathBuf],
+    options: &LoadOptions,
+    rec: &RecordingStream,
+) -> Result<Stats> {
+    validate_options(options)?;
+
+    let root = files_root(options);
+    let mut files: Vec<(PathBuf, String)> = Vec::new();
+    for path in paths {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let prefix = file_prefix(root, &stem);
+        if let Some((other, _)) = files
+            .iter()
+            .find(|(_, other_prefix)| *other_prefix == prefix)
+        {
+            anyhow::bail!("{other:?} and {path:?} would be logged under the same entity path");
+        }
+        files.push((path.clone(), prefix));
+    }
+    load_each(&files, root, options, rec)
+}
+
+/// Returns the entity path prefix of a single file: the given prefix, the file stem with
+/// `EntityLayout::PerFile`, or the default prefix of its loader.
+fn entity_path_prefix(options: &LoadOptions, path: &Path, default: &str) -> String {
+    match (&options.entity_path_prefix, options.entity_layout) {
+        (Some(prefix), _) => prefix.clone(),
+        (None, EntityLayout::PerFile) => path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        (None, _) => default.to_owned(),
+    }
+}
+
+/// Returns the entity all files of a multi-file load are logged under, which is the root for
+/// `EntityLayout::PerFile`.
+fn files_root(options: &LoadOptions) -> &str {
+    match (&options.entity_path_prefix, options.entity_layout) {
+        (Some(prefix), _) => prefix,
+        (None, EntityLayout::PerFile) => "",
+        (None, _) => "e57_pointcloud",
+    }
+}
+
+/// Returns the entity path prefix of a file with the given path relative to `root`.
+fn file_prefix(root: &str, relative: &str) -> String {
+    if root.is_empty() {
+        relative.to_owned()
+    } else {
+        format!("{root}/{relative}")
+    }
+}
+
+/// Returns whether [`load_file`] has a loader for the extension of `path`.
+pub fn is_supported_file(path: &Path) -> bool {
+    let extension = extension(path);
+    matches!(extension.as_str(), "e57" | "pts" | "ptx")
+        || (cfg!(feature = "las") && extension == "las")
+        || (cfg!(feature = "ply") && extension == "ply")
+}
+
+/// Loads a file with the loader for its extension, or as an E57 file if it has no other.
+///
+/// With `cache`, what was logged is stored in the cache, or replayed from it.
+pub fn load_file(path: &Path, options: &LoadOptions, rec: &RecordingStream) -> Result<Stats> {
+    if let Some(cache_dir) = &options.cache {
+        validate_options(options)?;
+        let options = LoadOptions {
+            cache: None,
+            ..options.clone()
+        };
+        return cache::load_cached(path, &options, rec, cache_dir, |rec| {
+            load_file(path, &options, rec)
+        });
+    }
+    match extension(path).as_str() {
+        "pts" => load_pts(path, options, rec),
+        "ptx" => load_ptx(path, options, rec),
+        #[cfg(feature = "las")]
+        "las" => load_las(path, options, rec),
+        #[cfg(feature = "ply")]
+        "ply" => load_ply(path, options, rec),
+        _ => load_e57(path, options, rec),
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Loads each file under its entity path prefix, with a single blueprint for all of them.
+fn load_each(
+    files: &[(PathBuf, String)],
+    entity_path_prefix: &str,
+    options: &LoadOptions,
+    rec: &RecordingStream,
+) -> Result<Stats> {
+    // Hiding scans that are not loaded is harmless, so they are taken from the scans of the
+    // files alone.
+    if options.send_blueprint {
+        let mut hidden = Vec::new();
+        for (path, prefix) in files {
+            if extension(path) != "e57" {
+                continue;
+            }
+            if let Ok(reader) = E57Reader::from_file(path) {
+                let pointclouds = reader.pointclouds();
+                let stations = scan_stations(options, &pointclouds);
+                let paths = template_paths(options, path, prefix, &scan_names(&pointclouds));
+                let scan_path = |index| scan_path(options, path, prefix, &stations, &paths, index);
+                if let Some(visible) = &options.visible_scans {
+                    for index in visible.hidden(0..pointclouds.len()) {
+                        hidden.push(scan_path(index));
+                    }
+                }
+            }
+        }
+        blueprint::send_blueprint(rec, options, entity_path_prefix, &hidden)?;
+    }
+
+    let mut stats = Stats::default();
+    // Files the scans were logged from, by their scan keys.
+    let mut logged_scans: HashMap<String, &Path> = HashMap::new();
+    for (path, prefix) in files {
+        if is_cancelled(options) {
+            break;
+        }
+        let mut file_options = LoadOptions {
+            entity_path_prefix: Some(prefix.clone()),
+            send_blueprint: false,
+            ..options.clone()
+        };
+
+        let mut scan_keys = Vec::new();
+        if options.dedup_scans && extension(path) == "e57" {
+            if let Ok(reader) = E57Reader::from_file(path) {
+                let pointclouds = reader.pointclouds();
+                scan_keys = pointclouds.iter().map(scan_key).collect();
+                let (mut selected, mut duplicates) = (HashSet::new(), 0);
+                for (index, pointcloud) in pointclouds.iter().enumerate() {
+                    let name = pointcloud.name.as_deref();
+                    if !is_scan_selected(options, index, name, pointcloud.guid.as_deref()) {
+                        continue;
+                    }
+                    match logged_scans.get(&scan_keys[index]) {
+                        Some(other) => {
+                            if !options.quiet {
+                                eprintln!("Point cloud #{index} of {path:?} was already logged from {other:?}, skipping...");
+                            }
+                            duplicates += 1;
+                        }
+                        None => {
+                            selected.insert(index);
+                        }
+                    }
+                }
+                stats.duplicate_scans += duplicates;
+                // The images of a file of duplicate scans are most likely duplicates too.
+                if duplicates > 0 && selected.is_empty() {
+                    continue;
+                }
+                file_options.scans = Some(selected);
+            }
+        }
+
+        match load_file(path, &file_options, rec) {
+            Ok(file_stats) => {
+                for report in &file_stats.reports {
+                    if let Some(key) = scan_keys.get(report.index) {
+                        logged_scans.entry(key.clone()).or_insert(path);
+                    }
+                }
+                stats += file_stats;
+            }
+            Err(e) => {
+                eprintln!("Failed to load {path:?}: {e:#}");
+                stats.failed_files += 1;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Returns what identifies a scan across files for `dedup_scans`: its GUID, or a hash of its
+/// metadata for scans without one, which tells apart all but identical copies.
+fn scan_key(pointcloud: &PointCloud) -> String {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    if let Some(guid) = &pointcloud.guid {
+        return guid.clone();
+    }
+    let mut hasher = DefaultHasher::new();
+    (
+        pointcloud.records,
+        &pointcloud.name,
+        format!(
+            "{:?} {:?} {:?} {:?}",
+            pointcloud.prototype,
+            pointcloud.cartesian_bounds,
+            pointcloud.transform,
+            pointcloud.acquisition_start
+        ),
+    )
+        .hash(&mut hasher);
+    format!("metadata:{:016x}", hasher.finish())
+}
+
+fn collect_e57_files(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read directory: {dir:?}"))?;
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("Failed to read directory: {dir:?}"))?
+            .path();
+        if path.is_dir() {
+            if recursive {
+                collect_e57_files(&path, recursive, files)?;
+            }
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("e57"))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Rejects option values and combinations that no loader can honor.
+fn validate_options(options: &LoadOptions) -> Result<()> {
+    if let Some(voxel_size) = options.voxel_size {
+        anyhow::ensure!(voxel_size > 0.0, "--voxel-size must be positive");
+    }
+    if let Some(ratio) = options.subsample {
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&ratio),
+            "--subsample must be between 0 and 1"
+        );
+    }
+    if let Some(thickness) = options.slice_z {
+        anyhow::ensure!(
+            thickness.is_finite() && thickness > 0.0,
+            "--slice-z must be positive"
+        );
+    }
+    if let Some(size) = options.tile_size {
+        anyhow::ensure!(
+            size.is_finite() && size > 0.0,
+            "--tile-size must be positive"
+        );
+        anyhow::ensure!(options.merge_scans, "--tile-size needs --merge-scans");
+    }
+    #[cfg(not(feature = "mesh"))]
+    anyhow::ensure!(!options.mesh, "--mesh needs a build with the mesh feature");
+    anyhow::ensure!(
+        !(options.refine_registration && options.no_transform),
+        "--refine-registration cannot be combined with --no-transform"
+    );
+    if let Some(template) = &options.entity_template {
+        anyhow::ensure!(
+            !options.group_stations,
+            "--entity-template cannot be combined with --group-stations"
+        );
+        anyhow::ensure!(
+            !(template.has_chunk() && options.entity_layout == EntityLayout::Flat),
+            "--entity-template with {{chunk}} cannot be combined with --entity-layout flat"
+        );
+    }
+    if let Some(cell_size) = options.dem {
+        anyhow::ensure!(
+            cell_size.is_finite() && cell_size > 0.0,
+            "--dem must be positive"
+        );
+    }
+    if let Some(n) = options.detect_planes {
+        anyhow::ensure!(n > 0, "--detect-planes must be at least 1");
+    }
+    if let Some(max_points) = options.max_points {
+        anyhow::ensure!(max_points > 0, "--max-points must be at least 1");
+    }
+    if let Some(levels) = options.lod_levels {
+        anyhow::ensure!(levels > 0, "--lod-levels must be at least 1");
+    }
+    anyhow::ensure!(
+        !(options.static_ && options.timeline.is_some()),
+        "--timeline cannot be combined with --static"
+    );
+    if let Some(scale) = options.scale {
+        anyhow::ensure!(scale.is_finite() && scale > 0.0, "--scale must be positive");
+    }
+    // Static rows replace each other, so only the last chunk would remain.
+    anyhow::ensure!(
+        !(options.static_ && options.entity_layout == EntityLayout::Flat),
+        "--entity-layout flat cannot be combined with --static"
+    );
+    anyhow::ensure!(
+        options.extra_fields.len() <= fields::MAX_EXTRA_FIELDS,
+        "At most {} --extra-field attributes are supported",
+        fields::MAX_EXTRA_FIELDS
+    );
+    // Voxels average the points, which is not meaningful for arbitrary attributes.
+    anyhow::ensure!(
+        options.extra_fields.is_empty() || options.voxel_size.is_none(),
+        "--extra-field cannot be combined with --voxel-size"
+    );
+    if let Some(window) = options.per_point_time {
+        anyhow::ensure!(window > 0.0, "--per-point-time must be positive");
+        // Voxel grids merge points across time windows.
+        anyhow::ensure!(
+            !options.static_ && options.voxel_size.is_none() && options.lod_levels.is_none(),
+            "--per-point-time cannot be combined with --static, --voxel-size or --lod-levels"
+        );
+    }
+    if let Some(n) = options.every_nth {
+        anyhow::ensure!(n > 0, "--every-nth must be at least 1");
+    }
+    anyhow::ensure!(
+        options.chunk_size.is_none() || options.chunk_bytes.is_none(),
+        "--chunk-size and --chunk-mb cannot be used together"
+    );
+    if let Some(chunk_size) = options.chunk_size {
+        anyhow::ensure!(chunk_size > 0, "--chunk-size must be at least 1");
+    }
+    anyhow::ensure!(
+        options.export.is_none() || options.lod_levels.is_none(),
+        "--export and --lod-levels cannot be used together"
+    );
+    anyhow::ensure!(
+        options.export.is_none() || options.invalid_points != InvalidPoints::LogSeparately,
+        "--export and --invalid-points log-separately cannot be used together"
+    );
+    // Exports are not logged, while the cache cannot tell which scans were already logged.
+    anyhow::ensure!(
+        options.cache.is_none() || (options.export.is_none() && !options.resume),
+        "--cache cannot be combined with --export or --resume"
+    );
+    for (name, range) in [("--rows", &options.rows), ("--cols", &options.columns)] {
+        if let Some(range) = range {
+            anyhow::ensure!(!range.is_empty(), "{name} must not be empty");
+        }
+    }
+    anyhow::ensure!(
+        !(options.group_stations && options.merge_scans),
+        "--group-stations cannot be combined with --merge-scans"
+    );
+    anyhow::ensure!(
+        options.visible_scans.is_none() || options.send_blueprint,
+        "--visible-scans needs --send-blueprint"
+    );
+    // The merged points of all scans would need a preview of their own.
+    anyhow::ensure!(
+        !options.preview_first || !options.merge_scans,
+        "--preview-first and --merge-scans cannot be used together"
+    );
+    // Skipped scans would be missing from the exported file.
+    anyhow::ensure!(
+        options.export.is_none() || !options.resume,
+        "--export and --resume cannot be used together"
+    );
+    if let Some((min, max)) = options.crop_box {
+        anyhow::ensure!(
+            (0..3).all(|i| min[i] <= max[i]),
+            "--crop-box must be given as xmin,ymin,zmin,xmax,ymax,zmax"
+        );
+    }
+    if let Some(min_range) = options.min_range {
+        anyhow::ensure!(min_range >= 0.0, "--min-range must not be negative");
+    }
+    if let (Some(min_range), Some(max_range)) = (options.min_range, options.max_range) {
+        anyhow::ensure!(
+            min_range <= max_range,
+            "--min-range must not be larger than --max-range"
+        );
+    }
+    match options.estimate_normals {
+        Some(NormalEstimation::Neighbors(k)) => {
+            anyhow::ensure!(k >= 3, "--estimate-normals needs at least 3 neighbors");
+        }
+        Some(NormalEstimation::Radius(radius)) => {
+            anyhow::ensure!(radius > 0.0, "--estimate-normals radius must be positive");
+        }
+        None => {}
+    }
+    anyhow::ensure!(
+        options.estimate_normals.is_none() || options.normals != NormalsMode::Skip,
+        "--estimate-normals cannot be used with --normals skip"
+    );
+    Ok(())
+}
+
+/// Returns the intensity limits of the point cloud, falling back to the bounds of the intensity record type.
+fn intensity_limits(pointcloud: &PointCloud) -> Option<(f64, f64)> {
+    let record = pointcloud
+        .prototype
+        .iter()
+        .find(|r| r.name == RecordName::Intensity)?;
+
+    if let Some(limits) = &pointcloud.intensity_limits {
+        if let (Some(min), Some(max)) = (&limits.intensity_min, &limits.intensity_max) {
+            if let (Ok(min), Ok(max)) =
+                (min.to_f64(&record.data_type), max.to_f64(&record.data_type))
+            {
+                return Some((min, max));
+            }
+        }
+    }
+
+    match record.data_type {
+        RecordDataType::Single {
+            min: Some(min),
+            max: Some(max),
+        } => Some((min as f64, max as f64)),
+        RecordDataType::Double {
+            min: Some(min),
+            max: Some(max),
+        } => Some((min, max)),
+        RecordDataType::ScaledInteger {
+            min,
+            max,
+            scale,
+            offset,
+        } => Some((min as f64 * scale + offset, max as f64 * scale + offset)),
+        RecordDataType::Integer { min, max } => Some((min as f64, max as f64)),
+        _ => None,
+    }
+}
+
+/// Reads all points of the point cloud once to find the actual min/max intensity.
+fn scan_intensity_range<T: Read + Seek>(
+    reader: &mut E57Reader<T>,
+    pointcloud: &PointCloud,
+) -> Result<Option<(f64, f64)>> {
+    let mut iter = reader
+        .pointcloud_simple(pointcloud)
+        .context("Unable to get simple point cloud iterator")?;
+    iter.normalize_intensity(false);
+    iter.intensity_to_color(false);
+
+    let range = iter
+        .filter_map(|p| p.ok()?.intensity)
+        .fold(None, |range, i| {
+            let i = i as f64;
+            match range {
+                Some((min, max)) => Some((f64::min(min, i), f64::max(max, i))),
+                None => Some((i, i)),
+            }
+        });
+
+    Ok(range)
+}
+
+/// Returns the shading of a structured point cloud from its range image, by reading all of its
+/// points.
+fn scan_range_shading<T: Read + Seek>(
+    reader: &mut E57Reader<T>,
+    pointcloud: &PointCloud,
+) -> Result<Option<shading::RangeShading>> {
+    let mut iter = reader
+        .pointcloud_simple(pointcloud)
+        .context("Unable to get simple point cloud iterator")?;
+    iter.apply_pose(false);
+    iter.spherical_to_cartesian(false);
+    iter.normalize_intensity(false);
+    iter.intensity_to_color(false);
+
+    let points = iter
+        .filter_map(|p| p.ok())
+        .filter_map(|p| Some((p.row, p.column, point_position(&p)?)));
+    shading::RangeShading::new(points)
+}
+
+/// Returns the local bounding box of the point cloud from the E57 header, if it has one.
+fn header_bounds(pointcloud: &PointCloud) -> Option<([f64; 3], [f64; 3])> {
+    let bounds = pointcloud.get_cartesian_bounds()?;
+    Some((
+        [bounds.x_min?, bounds.y_min?, bounds.z_min?],
+        [bounds.x_max?, bounds.y_max?, bounds.z_max?],
+    ))
+}
+
+/// Returns the local bounding box of the point cloud, from the E57 header if available
+/// or by reading all of its points otherwise.
+fn scan_bounds<T: Read + Seek>(
+    reader: &mut E57Reader<T>,
+    pointcloud: &PointCloud,
+) -> Result<Option<([f64; 3], [f64; 3])>> {
+    if let Some(bounds) = header_bounds(pointcloud) {
+        return Ok(Some(bounds));
+    }
+
+    let mut iter = reader
+        .pointcloud_simple(pointcloud)
+        .context("Unable to get simple point cloud iterator")?;
+    iter.apply_pose(false);
+    iter.spherical_to_cartesian(false);
+
+    let bounds: Option<([f64; 3], [f64; 3])> =
+        iter.filter_map(|p| point_position(&p.ok()?))
+            .fold(None, |bounds, p| {
+                let p = p.to_array();
+                match bounds {
+                    Some((min, max)) => Some((
+                        [0, 1, 2].map(|i| f64::min(min[i], p[i])),
+                        [0, 1, 2].map(|i| f64::max(max[i], p[i])),
+                    )),
+                    None => Some((p, p)),
+                }
+            });
+
+    Ok(bounds)
+}
+
+/// Returns the Cartesian position of a point, converting from spherical coordinates if needed.
+/// Points that are invalid or only define a direction without a range have no position.
+fn point_position(p: &Point) -> Option<DVec3> {
+    if let CartesianCoordinate::Valid { x, y, z } = p.cartesian {
+        return Some(DVec3::new(x, y, z));
+    }
+
+    match p.spherical {
+        SphericalCoordinate::Valid {
+            range,
+            azimuth,
+            elevation,
+        } => {
+            let cos_ele = elevation.cos();
+            Some(DVec3::new(
+                range * cos_ele * azimuth.cos(),
+                range * cos_ele * azimuth.sin(),
+                range * elevation.sin(),
+            ))
+        }
+        SphericalCoordinate::Direction { .. } | SphericalCoordinate::Invalid => None,
+    }
+}
+
+/// Returns the unit direction of a point that has a direction but no valid position.
+fn point_direction(p: &Point) -> Option<DVec3> {
+    match p.spherical {
+        SphericalCoordinate::Direction { azimuth, elevation } => Some(DVec3::new(
+            elevation.cos() * azimuth.cos(),
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+        )),
+        _ => match p.cartesian {
+            CartesianCoordinate::Direction { x, y, z } => DVec3::new(x, y, z).try_normalize(),
+            _ => None,
+        },
+    }
+}
+
+/// Local bounds of a scan with its optional pose in file-level coordinates.
+type ScanBounds = (([f64; 3], [f64; 3]), Option<(DVec3, DQuat)>);
+
+/// Returns the local bounds of all scans that have any, each with its pose in file-level
+/// coordinates unless transforms are disabled.
+fn scan_bounds_and_poses<T: Read + Seek>(
+    reader: &mut E57Reader<T>,
+    options: &LoadOptions,
+    scans: &[(usize, &PointCloud)],
+) -> Result<Vec<ScanBounds>> {
+    let mut scan_bounds_and_poses = Vec::new();
+    for (_, pointcloud) in scans {
+        let Some(bounds) = scan_bounds(reader, pointcloud)? else {
+            continue;
+        };
+        let world = pointcloud
+            .transform
+            .as_ref()
+            .filter(|_| !options.no_transform)
+            .map(pose);
+        scan_bounds_and_poses.push((bounds, world));
+    }
+
+    Ok(scan_bounds_and_poses)
+}
+
+/// Returns the attribute scans without a color priority are colored by, or the first one of
+/// the priority that is available given which attributes the scan has.
+fn scan_color_by(
+    options: &LoadOptions,
+    has_color: bool,
+    has_intensity: bool,
+    has_classification: bool,
+) -> ColorBy {
+    let Some(priority) = &options.color_priority else {
+        return options.color_by;
+    };
+    priority
+        .iter()
+        .copied()
+        .find(|color_by| match color_by {
+            ColorBy::Rgb => has_color,
+            ColorBy::Intensity => has_intensity,
+            ColorBy::Classification => has_classification,
+            // Points without a return index keep the colors of `rgb`.
+            ColorBy::Height | ColorBy::Range | ColorBy::Scan | ColorBy::Return => true,
+        })
+        .unwrap_or(ColorBy::Rgb)
+}
+
+/// Returns the height or range mode that needs a color range, if some scan may be colored by
+/// one. The first one of the priority wins, since a single range is shared by all scans.
+fn geometric_color_by(options: &LoadOptions) -> Option<ColorBy> {
+    let is_geometric = |color_by: &ColorBy| matches!(color_by, ColorBy::Height | ColorBy::Range);
+    match &options.color_priority {
+        Some(priority) => priority.iter().copied().find(is_geometric),
+        None => Some(options.color_by).filter(is_geometric),
+    }
+}
+
+/// Returns the range of heights or distances from the scanner over the corners of the given
+/// local scan bounds, each with an optional pose in file-level coordinates, in meters.
+fn bounds_color_range(
+    options: &LoadOptions,
+    scans: impl IntoIterator<Item = ScanBounds>,
+) -> Option<(f64, f64)> {
+    let mut range: Option<(f64, f64)> = None;
+
+    for (bounds, world) in scans {
+        for corner in bounds_corners(bounds) {
+            let value = match geometric_color_by(options) {
+                Some(ColorBy::Height) => {
+                    world.map_or(corner, |(t, q)| q * corner + t)[options.up_axis.index()]
+                }
+                _ => corner.length(),
+            } * options.scale.unwrap_or(1.0);
+            range = Some(match range {
+                Some((lo, hi)) => (lo.min(value), hi.max(value)),
+                None => (value, value),
+            });
+        }
+    }
+
+    // The corners of the bounds are not the closest points, so ranges start at the scanner.
+    if geometric_color_by(options) == Some(ColorBy::Range) {
+        range = range.map(|(_, max)| (0.0, max));
+    }
+
+    range
+}
+
+/// Returns the offset of `--center` in meters, where `Center::Auto` is the center of the given
+/// local scan bounds, each with an optional pose in file-level coordinates.
+fn center_offset(options: &LoadOptions, scans: impl IntoIterator<Item = ScanBounds>) -> DVec3 {
+    match options.center {
+        None => DVec3::ZERO,
+        Some(Center::Offset(offset)) => DVec3::from(offset),
+        Some(Center::Auto) => {
+            let mut extent: Option<(DVec3, DVec3)> = None;
+            for (bounds, world) in scans {
+                for corner in bounds_corners(bounds) {
+                    let p = world.map_or(corner, |(t, q)| q * corner + t);
+                    extent = Some(extent.map_or((p, p), |(min, max)| (min.min(p), max.max(p))));
+                }
+            }
+            extent.map_or(DVec3::ZERO, |(min, max)| {
+                (min + max) / 2.0 * options.scale.unwrap_or(1.0)
+            })
+        }
+    }
+}
+
+/// Returns the eight corners of a bounding box.
+fn bounds_corners((min, max): ([f64; 3], [f64; 3])) -> [DVec3; 8] {
+    std::array::from_fn(|i| {
+        DVec3::new(
+            if i & 1 == 0 { min[0] } else { max[0] },
+            if i & 2 == 0 { min[1] } else { max[1] },
+            if i & 4 == 0 { min[2] } else { max[2] },
+        )
+    })
+}
+
+/// Ranges of a scan that can take a pass over its records, found once for both passes of
+/// `--preview-first`.
+#[derive(Debug, Clone, Copy)]
+struct ScanExtents {
+    /// Range of the raw intensities, if they are read.
+    intensity_range: Option<(f64, f64)>,
+    /// Local bounding box of the positions.
+    bounds: Option<([f64; 3], [f64; 3])>,
+}
+
+/// Returns how a scan is colored, and whether its intensities are logged and read.
+fn scan_intensity(options: &LoadOptions, pointcloud: &PointCloud) -> (ColorBy, bool, bool) {
+    let color_by = scan_color_by(
+        options,
+        pointcloud.has_color(),
+        pointcloud.has_intensity(),
+        classification_field(pointcloud).is_some(),
+    );
+    let log_intensity = options.log_intensity && pointcloud.has_intensity();
+    let read_intensity = (color_by == ColorBy::Intensity || log_intensity || options.stats)
+        && pointcloud.has_intensity();
+    (color_by, log_intensity, read_intensity)
+}
+
+/// Returns the intensity range and the bounds of a scan, which read all of its points if they
+/// are not in the header or given.
+fn scan_extents<T: Read + Seek>(
+    options: &LoadOptions,
+    reader: &mut E57Reader<T>,
+    pointcloud: &PointCloud,
+) -> Result<ScanExtents> {
+    let (.., read_intensity) = scan_intensity(options, pointcloud);
+    let intensity_range = if read_intensity {
+        match options.intensity_range {
+            IntensityRange::Limits => intensity_limits(pointcloud),
+            IntensityRange::Auto => scan_intensity_range(reader, pointcloud)?,
+            IntensityRange::Fixed(min, max) => Some((min, max)),
+        }
+    } else {
+        None
+    };
+
+    // The bounds of the header are enough to find the origin of the logged points.
+    let bounds = if options.bounds || options.lod_levels.is_some() {
+        scan_bounds(reader, pointcloud)?
+    } else {
+        header_bounds(pointcloud)
+    };
+    Ok(ScanExtents {
+        intensity_range,
+        bounds,
+    })
+}
+
+/// Decodes a single point cloud and logs it as chunks under `{prefix}/scan_{index}`, with
+/// its extents if they were found already. Returns the number of logged points.
+fn load_scan(
+    loader: &Loader<'_>,
+    index: usize,
+    pointcloud: &PointCloud,
+    extents: Option<ScanExtents>,
+    preview: bool,
+) -> Result<u64> {
+    let &Loader {
+        rec, path, options, ..
+    } = loader;
+
+    let mut reader = source::open_e57(path, options.mmap)?;
+
+    // Intensities are read raw and normalized here, so the same values can be
+    // used for coloring and for the custom intensity component.
+    let (color_by, log_intensity, read_intensity) = scan_intensity(options, pointcloud);
+    let ScanExtents {
+        intensity_range,
+        bounds,
+    } = match extents {
+        Some(extents) => extents,
+        None => scan_extents(options, &mut reader, pointcloud)?,
+    };
+
+    // The shading needs the neighbors of every point, so the scan is read twice.
+    let shading = match options.shade {
+        Some(Shade::RangeGradient) if pointcloud.has_row_column() && !preview => {
+            scan_range_shading(&mut reader, pointcloud)?
+        }
+        _ => None,
+    };
+
+    let mut iter = reader
+        .pointcloud_simple(pointcloud)
+        .context("Unable to get simple point cloud iterator")?;
+
+    iter.apply_pose(false);
+    iter.spherical_to_cartesian(false);
+
+    if read_intensity {
+        iter.normalize_intensity(false);
+        iter.intensity_to_color(false);
+    }
+
+    // Extension attributes are read with a second reader, in lockstep with the points.
+    let mut extra_reader;
+    // The classification is always read, since it is logged as the class ids of the points.
+    let read_classification = classification_field(pointcloud).is_some();
+    let read_normals = options.normals != NormalsMode::Skip;
+    let read_time_stamps = options.per_point_time.is_some();
+    let read_returns = options.returns != Returns::All || color_by == ColorBy::Return;
+    let mut extra_fields = if read_classification
+        || read_normals
+        || read_time_stamps
+        || read_returns
+        || !options.extra_fields.is_empty()
+    {
+        extra_reader = source::open_e57(path, options.mmap)?;
+        ExtraFields::new(
+            &mut extra_reader,
+            pointcloud,
+            read_classification,
+            read_normals,
+            read_time_stamps,
+            read_returns,
+            &options.extra_fields,
+        )?
+    } else {
+        None
+    };
+
+    let transform = pointcloud
+        .transform
+        .as_ref()
+        .filter(|_| !options.no_transform);
+    let entity_path = loader.scan_path(index);
+    let mut pipeline = ScanPipeline::new(
+        loader,
+        ScanInfo {
+            index,
+            entity_path: entity_path.clone(),
+            color_by,
+            log_intensity,
+            intensity_range,
+            normals: extra_fields.as_ref().is_some_and(ExtraFields::has_normals),
+            extra_fields: extra_fields
+                .as_ref()
+                .map(ExtraFields::extra_fields)
+                .unwrap_or_default(),
+            bounds,
+            world: transform.map(pose),
+            structured: pointcloud.has_row_column(),
+            classified: extra_fields
+                .as_ref()
+                .is_some_and(ExtraFields::has_classification),
+            records: pointcloud.records,
+            acquisition_time: pointcloud
+                .acquisition_start
+                .as_ref()
+                .map(|t| gps_time::gps_to_unix(t.gps_time)),
+            preview,
+            shading,
+        },
+    )?;
+
+    // The full pass logs the metadata and the origin of the scan.
+    if !preview {
+        rec.log_with_static(
+            entity_path.as_str(),
+            options.static_,
+            &rerun::TextDocument::from_markdown(scan_metadata(index, pointcloud)),
+        )?;
+        if pointcloud.transform.is_some() {
+            pipeline.log_scan_origin()?;
+        }
+    }
+
+    // The ground model needs all points of the scan before the first one is split, so the scan
+    // is read twice.
+    if pipeline.samples_ground() {
+        let mut ground_reader = source::open_e57(path, options.mmap)?;
+        let mut ground_iter = ground_reader
+            .pointcloud_simple(pointcloud)
+            .context("Unable to get simple point cloud iterator")?;
+        ground_iter.apply_pose(false);
+        ground_iter.spherical_to_cartesian(false);
+        ground_iter.normalize_intensity(false);
+        ground_iter.intensity_to_color(false);
+        for p in ground_iter.take(pointcloud.records as usize).flatten() {
+            if let Some(position) = point_position(&p) {
+                pipeline.sample_ground(position);
+            }
+        }
+        pipeline.build_ground()?;
+    }
+
+    // After a corrupt data page the iterator may keep returning errors, so it is limited to
+    // the declared number of records.
+    for point_result in iter.take(pointcloud.records as usize) {
+        if is_cancelled(options) {
+            break;
+        }
+        let extra = extra_fields
+            .as_mut()
+            .map(ExtraFields::next_values)
+            .unwrap_or_default();
+
+        let p = match point_result {
+            Ok(p) => p,
+            Err(e) => {
+                pipeline.skip_error(e);
+                continue;
+            }
+        };
+
+        let position = point_position(&p);
+        let point = SourcePoint {
+            position: position
+                .or_else(|| point_direction(&p))
+                .unwrap_or(DVec3::ZERO),
+            color: p.color.map(|color| {
+                rerun::Color::from_rgb(
+                    (color.red * 255.0) as u8,
+                    (color.green * 255.0) as u8,
+                    (color.blue * 255.0) as u8,
+                )
+            }),
+            intensity: p.intensity,
+            row: p.row,
+            column: p.column,
+            classification: extra.classification,
+            normal: extra.normal.map(|[x, y, z]| Vec3D::new(x, y, z)),
+            time: extra.time_stamp,
+            returns: extra.returns,
+            extras: extra.extras,
+        };
+        match position {
+            Some(_) => pipeline.push(point)?,
+            None => pipeline.push_invalid(point)?,
+        }
+    }
+
+    pipeline.finish()
+}
+
+/// Formats an E57 timestamp as UTC, keeping the raw GPS time.
+fn format_date_time(t: &e57::DateTime) -> String {
+    format!(
+        "{} ({:.3} s GPS time)",
+        gps_time::format_gps_time(t.gps_time),
+        t.gps_time
+    )
+}
+
+/// Renders the provenance of an E57 file as a Markdown table, followed by its coordinate
+/// metadata, which is often a multi-line WKT string.
+fn file_metadata<T: Read + Seek>(path: &Path, reader: &E57Reader<T>, scans: usize) -> String {
+    let header = reader.header();
+    let fields = [
+        (
+            "File",
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+        ),
+        ("Format", Some(reader.format_name().to_owned())),
+        (
+            "Version",
+            Some(format!("{}.{}", header.major, header.minor)),
+        ),
+        ("GUID", Some(reader.guid().to_owned())),
+        (
+            "Creation date",
+            reader.creation().as_ref().map(format_date_time),
+        ),
+        (
+            "Library version",
+            reader.library_version().map(str::to_owned),
+        ),
+        (
+            "Extensions",
+            Some(
+                reader
+                    .extensions()
+                    .iter()
+                    .map(|e| format!("{} ({})", e.namespace, e.url))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )
+            .filter(|extensions| !extensions.is_empty()),
+        ),
+        ("Scans", Some(scans.to_string())),
+        (
+            "Loaded with",
+            Some(format!("rerun-loader-e57 {}", env!("CARGO_PKG_VERSION"))),
+        ),
+    ];
+
+    let mut markdown = String::from("# E57 file\n\n| Field | Value |\n| --- | --- |\n");
+    for (field, value) in fields {
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            markdown += &format!("| {field} | {} |\n", value.replace('|', "\\|"));
+        }
+    }
+    if let Some(coordinate_metadata) = reader.coordinate_metadata().filter(|m| !m.is_empty()) {
+        markdown += &format!("\n## Coordinate metadata\n\n```\n{coordinate_metadata}\n```\n");
+    }
+    markdown
+}
+
+/// Renders the header metadata of a scan as a Markdown table.
+fn scan_metadata(index: usize, pointcloud: &PointCloud) -> String {
+    let pc = pointcloud;
+    let fields = [
+        ("Name", pc.name.clone()),
+        ("Description", pc.description.clone()),
+        ("GUID", pc.guid.clone()),
+        ("Points", Some(pc.records.to_string())),
+        ("Sensor vendor", pc.sensor_vendor.clone()),
+        ("Sensor model", pc.sensor_model.clone()),
+        ("Sensor serial number", pc.sensor_serial.clone()),
+        ("Sensor hardware version", pc.sensor_hw_version.clone()),
+        ("Sensor software version", pc.sensor_sw_version.clone()),
+        ("Sensor firmware version", pc.sensor_fw_version.clone()),
+        ("Temperature", pc.temperature.map(|t| format!("{t} °C"))),
+        ("Relative humidity", pc.humidity.map(|h| format!("{h} %"))),
+        (
+            "Atmospheric pressure",
+            pc.atmospheric_pressure.map(|p| format!("{p} Pa")),
+        ),
+        (
+            "Acquisition start",
+            pc.acquisition_start.as_ref().map(format_date_time),
+        ),
+        (
+            "Acquisition end",
+            pc.acquisition_end.as_ref().map(format_date_time),
+        ),
+    ];
+
+    let mut markdown = format!("# Scan {index}\n\n| Field | Value |\n| --- | --- |\n");
+    for (field, value) in fields {
+        if let Some(value) = value {
+            markdown += &format!("| {field} | {} |\n", value.replace('|', "\\|"));
+        }
+    }
+    markdown
+}
+
+/// Maximum distance in meters between the scanner positions of the scans of a station.
+const STATION_TOLERANCE: f64 = 0.01;
+
+/// Returns the station of every scan with `group_stations`, numbered in the order of their
+/// first scan. E57 files have no grouping of scans by setup, so the posed scans at the same
+/// scanner position, e.g. of several resolutions or tilts, make up a station. Scans without a
+/// pose belong to none.
+fn scan_stations(options: &LoadOptions, pointclouds: &[PointCloud]) -> Vec<Option<usize>> {
+    if !options.group_stations {
+        return Vec::new();
+    }
+    let scale = options.scale.unwrap_or(1.0);
+    let mut positions: Vec<DVec3> = Vec::new();
+    pointclouds
+        .iter()
+        .map(|pointcloud| {
+            let (position, _) = pose(pointcloud.transform.as_ref()?);
+            let position = position * scale;
+            let station = positions
+                .iter()
+                .position(|other| other.distance(position) <= STATION_TOLERANCE)
+                .unwrap_or_else(|| {
+                    positions.push(position);
+                    positions.len() - 1
+                });
+            Some(station)
+        })
+        .collect()
+}
+
+/// Name and GUID of a scan, for `--entity-template`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScanName {
+    pub name: Option<String>,
+    pub guid: Option<String>,
+}
+
+/// Returns the names of the scans of an E57 file by scan index.
+fn scan_names(pointclouds: &[e57::PointCloud]) -> Vec<ScanName> {
+    pointclouds
+        .iter()
+        .map(|pointcloud| ScanName {
+            name: pointcloud.name.clone(),
+            guid: pointcloud.guid.clone(),
+        })
+        .collect()
+}
+
+/// Returns the entity paths of the scans from the `--entity-template` with their names, or no
+/// paths without a template.
+///
+/// Scans with the same path would overwrite each other's entities, e.g. scans with the same
+/// name, so every one of them gets its index, and more suffixes while that is the path of
+/// another scan too.
+pub(crate) fn template_paths(
+    options: &LoadOptions,
+    file: &Path,
+    prefix: &str,
+    names: &[ScanName],
+) -> Vec<String> {
+    let Some(template) = &options.entity_template else {
+        return Vec::new();
+    };
+    let rendered: Vec<String> = names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| template.scan_path(prefix, file, index, name))
+        .collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for path in &rendered {
+        *counts.entry(path).or_default() += 1;
+    }
+    let mut taken: HashSet<String> = rendered.iter().cloned().collect();
+    let mut paths = Vec::with_capacity(rendered.len());
+    for (index, path) in rendered.iter().enumerate() {
+        if counts[path.as_str()] == 1 {
+            paths.push(path.clone());
+            continue;
+        }
+        let mut unique = format!("{path}_{index}");
+        let mut suffix = 1;
+        while taken.contains(&unique) {
+            unique = format!("{path}_{index}_{suffix}");
+            suffix += 1;
+        }
+        taken.insert(unique.clone());
+        paths.push(unique);
+    }
+    paths
+}
+
+/// Returns the entity path of a scan: its path from `template_paths`, or from the
+/// `--entity-template` for files without scan names, or `{prefix}/station_{station}/scan_{index}`
+/// for the scans of a station.
+pub(crate) fn scan_path(
+    options: &LoadOptions,
+    file: &Path,
+    prefix: &str,
+    stations: &[Option<usize>],
+    paths: &[String],
+    index: usize,
+) -> String {
+    if let Some(template) = &options.entity_template {
+        return paths
+            .get(index)
+            .cloned()
+            .unwrap_or_else(|| template.scan_path(prefix, file, index, &ScanName::default()));
+    }
+    match stations.get(index).copied().flatten() {
+        Some(station) => format!("{prefix}/station_{station}/scan_{index}"),
+        None => format!("{prefix}/scan_{index}"),
+    }
+}
+
+/// Converts an E57 pose into a translation and rotation.
+fn pose(transform: &e57::Transform) -> (DVec3, DQuat) {
+    let t = &transform.translation;
+    let q = &transform.rotation;
+    (
+        DVec3::new(t.x, t.y, t.z),
+        DQuat::from_xyzw(q.x, q.y, q.z, q.w).normalize(),
+    )
+}
+
+/// Returns the linear map from the coordinates of the file to the right-handed Z-up
+/// coordinates of the recording.
+pub(crate) fn axes_conversion(options: &LoadOptions) -> DMat3 {
+    match (options.up_axis, options.handedness) {
+        (UpAxis::Z, Handedness::Right) => DMat3::IDENTITY,
+        (UpAxis::Z, Handedness::Left) => DMat3::from_diagonal(DVec3::new(1.0, -1.0, 1.0)),
+        (UpAxis::Y, Handedness::Right) => DMat3::from_cols(DVec3::X, DVec3::Z, DVec3::NEG_Y),
+        (UpAxis::Y, Handedness::Left) => DMat3::from_cols(DVec3::X, DVec3::Z, DVec3::Y),
+    }
+}
+
+/// Returns the transform of an entity directly under the prefix with the pose `world` in
+/// file-level coordinates in meters, relative to the `center` of the prefix and including
+/// the conversion of the axes of the file. Returns `None` if there is nothing to transform.
+pub(crate) fn root_transform(
+    options: &LoadOptions,
+    center: DVec3,
+    world: Option<(DVec3, DQuat)>,
+) -> Option<rerun::Transform3D> {
+    let axes = axes_conversion(options);
+    if axes == DMat3::IDENTITY && center == DVec3::ZERO && world.is_none() {
+        return None;
+    }
+
+    // The center is subtracted in double precision, before rounding the translation.
+    let (translation, rotation) = world.unwrap_or((DVec3::ZERO, DQuat::IDENTITY));
+    let translation = (axes * (translation - center)).as_vec3();
+    if axes == DMat3::IDENTITY {
+        return Some(rerun::Transform3D::from_translation_rotation(
+            translation,
+            rotation.as_quat(),
+        ));
+    }
+
+    // A change of handedness is a reflection, which only a matrix can express.
+    Some(rerun::Transform3D::from_translation_mat3x3(
+        translation,
+        (axes * DMat3::from_quat(rotation)).as_mat3(),
+    ))
+}
+
+/// Returns `child` as a `Transform3D` relative to `parent`, with both poses given in file-level
+/// coordinates, and the translation scaled to meters by `scale`. The points of the parent
+/// are logged relative to `origin`, in meters.
+fn relative_transform(
+    parent: Option<&e57::Transform>,
+    child: &e57::Transform,
+    scale: f64,
+    origin: DVec3,
+) -> rerun::Transform3D {
+    let (mut translation, mut rotation) = pose(child);
+
+    if let Some(parent) = parent {
+        let (parent_translation, parent_rotation) = pose(parent);
+        let inverse = parent_rotation.inverse();
+        translation = inverse * (translation - parent_translation);
+        rotation = inverse * rotation;
+    }
+
+    rerun::Transform3D::from_translation_rotation(
+        (translation * scale - origin).as_vec3(),
+        rotation.as_quat(),
+    )
+}
+
+/// Logs the images embedded in the E57 file under the scan they were captured with.
+/// Returns the number of logged images.
+fn load_images<T: Read + Seek>(
+    loader: &Loader<'_>,
+    reader: &mut E57Reader<T>,
+    pointclouds: &[PointCloud],
+    scans: &[(usize, &PointCloud)],
+) -> Result<usize> {
+    let &Loader {
+        rec,
+        options,
+        entity_path_prefix,
+        center,
+        ..
+    } = loader;
+    let mut count = 0;
+    for (image_idx, image) in reader.images().iter().enumerate() {
+        let scan_idx = image.pointcloud_guid.as_ref().and_then(|guid| {
+            pointclouds
+                .iter()
+                .position(|pc| pc.guid.as_ref() == Some(guid))
+        });
+
+        // Image poses are in file-level coordinates, while the scan points are
+        // logged in the local scanner frame.
+        let (entity_path, parent) = match scan_idx {
+            Some(scan_idx) => {
+                if !scans.iter().any(|(index, _)| *index == scan_idx) {
+                    continue;
+                }
+                (
+                    format!("{}/image_{image_idx}", loader.scan_path(scan_idx)),
+                    pointclouds[scan_idx].transform.as_ref(),
+                )
+            }
+            None => (format!("{entity_path_prefix}/image_{image_idx}"), None),
+        };
+
+        // Images without their own acquisition time are shown with their scan.
+        if options.timeline == Some(TimeSource::Acquisition) {
+            let acquisition = image.acquisition.as_ref().or_else(|| {
+                scan_idx.and_then(|scan_idx| pointclouds[scan_idx].acquisition_start.as_ref())
+            });
+            pipeline::set_acquisition_time(
+                rec,
+                acquisition.map(|t| gps_time::gps_to_unix(t.gps_time)),
+            );
+        }
+
+        let blob = match (&image.projection, &image.visual_reference) {
+            (Some(Projection::Pinhole(pinhole)), _) => &pinhole.blob,
+            (Some(Projection::Spherical(spherical)), _) => &spherical.blob,
+            (Some(Projection::Cylindrical(cylindrical)), _) => &cylindrical.blob,
+            (None, Some(visual_reference)) => &visual_reference.blob,
+            (None, None) => continue,
+        };
+
+        let mut bytes = Vec::new();
+        reader
+            .blob(&blob.data, &mut bytes)
+            .with_context(|| format!("Failed to read image #{image_idx}"))?;
+        let media_type = match blob.format {
+            ImageFormat::Png => rerun::MediaType::png(),
+            ImageFormat::Jpeg => rerun::MediaType::jpeg(),
+        };
+
+        // Images of a scan are relative to it, while the others are directly under the
+        // prefix and need the conversion of the axes as well.
+        let scale = options.scale.unwrap_or(1.0);
+        let origin = scan_idx
+            .and_then(|scan_idx| {
+                let origins = loader.origins.lock().expect("origins poisoned");
+                origins.get(&scan_idx).copied()
+            })
+            .unwrap_or_default();
+        let transform = match (scan_idx, &image.transform) {
+            (Some(_), Some(transform)) => {
+                Some(relative_transform(parent, transform, scale, origin))
+            }
+            // Without a pose of its own, the image is at the scanner.
+            (Some(_), None) => (origin != DVec3::ZERO)
+                .then(|| rerun::Transform3D::from_translation((-origin).as_vec3())),
+            (None, transform) => root_transform(
+                options,
+                center,
+                transform.as_ref().map(|t| {
+                    let (translation, rotation) = pose(t);
+                    (translation * scale, rotation)
+                }),
+            ),
+        };
+        if let Some(transform) = transform {
+            rec.log_with_static(entity_path.as_str(), options.static_, &transform)?;
+        }
+
+        if let Some(Projection::Pinhole(pinhole)) = &image.projection {
+            rec.log_with_static(
+                entity_path.as_str(),
+                options.static_,
+                &pinhole_camera(&pinhole.properties),
+            )?;
+        }
+
+        rec.log_with_static(
+            entity_path,
+            options.static_,
+            &rerun::EncodedImage::from_file_contents(bytes).with_media_type(media_type),
+        )?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Converts the E57 pinhole intrinsics (focal length and pixel size in meters) into pixel units.
+fn pinhole_camera(properties: &e57::PinholeImageProperties) -> rerun::Pinhole {
+    let focal_length = [
+        (properties.focal_length / properties.pixel_width) as f32,
+        (properties.focal_length / properties.pixel_height) as f32,
+    ];
+    let resolution = [properties.width as f32, properties.height as f32];
+
+    rerun::Pinhole::from_focal_length_and_resolution(focal_length, resolution)
+        .with_principal_point([properties.principal_x as f32, properties.principal_y as f32])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scan_indices_and_ranges() {
+        let scans = parse_scan_selection("0, 2,5-7").unwrap();
+        assert_eq!(scans, HashSet::from([0, 2, 5, 6, 7]));
+    }
+
+    #[test]
+    fn hides_scans_that_are_not_visible() {
+        let first: VisibleScans = "2".parse().unwrap();
+        assert_eq!(first.hidden([3, 0, 7, 1]), [3, 7]);
+        let selection: VisibleScans = "1,5-7".parse().unwrap();
+        assert_eq!(selection.hidden([0, 1, 6, 8]), [0, 8]);
+        assert!("a".parse::<VisibleScans>().is_err());
+    }
+
+    #[test]
+    fn parses_empty_scan_selection() {
+        assert!(parse_scan_selection("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_scan_selection() {
+        assert!(parse_scan_selection("1,x").is_err());
+        assert!(parse_scan_selection("5-2").is_err());
+        assert!(parse_scan_selection("-3").is_err());
+    }
+
+    #[test]
+    fn selects_scans_by_all_selections() {
+        let options = LoadOptions {
+            scans: Some(HashSet::from([0, 1])),
+            scan_name: Some(regex::Regex::new("Station").unwrap()),
+            scan_guids: vec!["{a}".to_owned(), "{b}".to_owned()],
+            ..Default::default()
+        };
+        assert!(is_scan_selected(
+            &options,
+            0,
+            Some("Station 1"),
+            Some("{a}")
+        ));
+        assert!(!is_scan_selected(
+            &options,
+            2,
+            Some("Station 3"),
+            Some("{b}")
+        ));
+        assert!(!is_scan_selected(&options, 1, Some("Target"), Some("{b}")));
+        assert!(!is_scan_selected(&options, 1, None, Some("{b}")));
+        assert!(!is_scan_selected(
+            &options,
+            1,
+            Some("Station 2"),
+            Some("{c}")
+        ));
+    }
+
+    #[test]
+    fn parses_point_radius_units() {
+        assert_eq!("0.02".parse(), Ok(PointRadius::Meters(0.02)));
+        assert_eq!("0.5m".parse(), Ok(PointRadius::Meters(0.5)));
+        assert_eq!("2ui".parse(), Ok(PointRadius::UiPoints(2.0)));
+        assert!("-1".parse::<PointRadius>().is_err());
+        assert!("2px".parse::<PointRadius>().is_err());
+    }
+
+    #[test]
+    fn parses_flush_intervals() {
+        assert_eq!("10".parse(), Ok(FlushEvery::Chunks(10)));
+        assert_eq!(
+            "0.5s".parse(),
+            Ok(FlushEvery::Interval(std::time::Duration::from_millis(500)))
+        );
+        assert!("0".parse::<FlushEvery>().is_err());
+        assert!("-1s".parse::<FlushEvery>().is_err());
+    }
+
+    #[test]
+    fn parses_clustering_parameters() {
+        assert_eq!(
+            "0.1:10".parse(),
+            Ok(Clustering {
+                eps: 0.1,
+                min_points: 10
+            })
+        );
+        assert!("0.1".parse::<Clustering>().is_err());
+        assert!("0:10".parse::<Clustering>().is_err());
+        assert!("0.1:0".parse::<Clustering>().is_err());
+    }
+
+    #[test]
+    fn parses_sections() {
+        assert_eq!(
+            "plane=z:1.2,thickness=0.1".parse(),
+            Ok(Section {
+                axis: 2,
+                offset: 1.2,
+                thickness: 0.1
+            })
+        );
+        assert_eq!(
+            "plane=x:-3".parse(),
+            Ok(Section {
+                axis: 0,
+                offset: -3.0,
+                thickness: 0.05
+            })
+        );
+        assert!("plane=w:1".parse::<Section>().is_err());
+        assert!("thickness=0.1".parse::<Section>().is_err());
+        assert!("plane=z:1,thickness=0".parse::<Section>().is_err());
+    }
+
+    #[test]
+    fn parses_entity_templates() {
+        let template: EntityTemplate = "{prefix}/{file}/{scan_name}/part_{chunk}".parse().unwrap();
+        let name = ScanName {
+            name: Some("Station A/1".to_owned()),
+            guid: None,
+        };
+        assert_eq!(
+            template.scan_path("site", Path::new("/data/survey.e57"), 3, &name),
+            "site/survey/Station_A_1"
+        );
+        assert_eq!(template.chunk_name(2).as_deref(), Some("part_2"));
+
+        let template: EntityTemplate = "{prefix}/{guid}".parse().unwrap();
+        assert_eq!(
+            template.scan_path("site", Path::new("a.e57"), 3, &ScanName::default()),
+            "site/scan_3"
+        );
+        assert_eq!(template.chunk_name(2), None);
+
+        assert!("{prefix}/{name}".parse::<EntityTemplate>().is_err());
+        assert!("{prefix}/{index".parse::<EntityTemplate>().is_err());
+        assert!("{prefix}/{chunk}/{index}"
+            .parse::<EntityTemplate>()
+            .is_err());
+        assert!("{prefix}/{file}/{chunk}".parse::<EntityTemplate>().is_err());
+    }
+
+    #[test]
+    fn tells_apart_scans_with_the_same_name() {
+        let options = LoadOptions {
+            entity_template: Some("{prefix}/{scan_name}".parse().unwrap()),
+            ..Default::default()
+        };
+        let names = ["Scan", "Scan", "Other"].map(|name| ScanName {
+            name: Some(name.to_owned()),
+            guid: None,
+        });
+        let paths = template_paths(&options, Path::new("a.e57"), "site", &names);
+        assert_eq!(paths, ["site/Scan_0", "site/Scan_1", "site/Other"]);
+
+        // The index of a repeated name can be the name of another scan.
+        let names = ["A", "A", "A_1"].map(|name| ScanName {
+            name: Some(name.to_owned()),
+            guid: None,
+        });
+        let paths = template_paths(&options, Path::new("a.e57"), "site", &names);
+        assert_eq!(paths, ["site/A_0", "site/A_1_1", "site/A_1"]);
+    }
+
+    #[test]
+    fn parses_index_ranges() {
+        assert_eq!(parse_index_range("10..20"), Ok(10..20));
+        assert_eq!(parse_index_range("10.."), Ok(10..i64::MAX));
+        assert_eq!(parse_index_range("..20"), Ok(0..20));
+        assert!(parse_index_range("10").is_err());
+    }
+
+    #[test]
+    fn parses_center_offset() {
+        assert_eq!("auto".parse(), Ok(Center::Auto));
+        assert_eq!(
+            "500000, 4649776.5,-12".parse(),
+            Ok(Center::Offset([500000.0, 4649776.5, -12.0]))
+        );
+        assert!("1,2".parse::<Center>().is_err());
+        assert!("1,2,x".parse::<Center>().is_err());
+    }
+
+    #[test]
+    fn picks_first_available_color_attribute() {
+        let mut options = LoadOptions {
+            color_by: ColorBy::Intensity,
+            ..Default::default()
+        };
+        assert_eq!(
+            scan_color_by(&options, true, false, false),
+            ColorBy::Intensity
+        );
+
+        options.color_priority = Some(vec![ColorBy::Rgb, ColorBy::Intensity, ColorBy::Height]);
+        assert_eq!(scan_color_by(&options, true, true, false), ColorBy::Rgb);
+        assert_eq!(
+            scan_color_by(&options, false, true, false),
+            ColorBy::Intensity
+        );
+        assert_eq!(
+            scan_color_by(&options, false, false, false),
+            ColorBy::Height
+        );
+        assert_eq!(geometric_color_by(&options), Some(ColorBy::Height));
+
+        options.color_priority = Some(vec![ColorBy::Classification]);
+        assert_eq!(scan_color_by(&options, true, true, false), ColorBy::Rgb);
+        assert_eq!(geometric_color_by(&options), None);
+    }
+
+    #[test]
+    fn roots_files_at_their_stem_with_per_file_layout() {
+        let path = Path::new("scans/station_1.e57");
+        let mut options = LoadOptions::default();
+        assert_eq!(
+            entity_path_prefix(&options, path, "e57_pointcloud"),
+            "e57_pointcloud"
+        );
+
+        options.entity_layout = EntityLayout::PerFile;
+        assert_eq!(
+            entity_path_prefix(&options, path, "e57_pointcloud"),
+            "station_1"
+        );
+        assert_eq!(file_prefix(files_root(&options), "a/b"), "a/b");
+
+        options.entity_path_prefix = Some("site".to_owned());
+        assert_eq!(entity_path_prefix(&options, path, "e57_pointcloud"), "site");
+        assert_eq!(file_prefix(files_root(&options), "a/b"), "site/a/b");
+    }
+
+    #[test]
+    fn converts_y_up_and_left_handed_axes_to_z_up() {
+        let axes = |up_axis, handedness| {
+            axes_conversion(&LoadOptions {
+                up_axis,
+                handedness,
+                ..Default::default()
+            })
+        };
+        for up_axis in [UpAxis::Z, UpAxis::Y] {
+            assert_eq!(axes(up_axis, Handedness::Right).determinant(), 1.0);
+            assert_eq!(axes(up_axis, Handedness::Left).determinant(), -1.0);
+            let mut up = DVec3::ZERO;
+            up[up_axis.index()] = 1.0;
+            for handedness in [Handedness::Right, Handedness::Left] {
+                assert_eq!(axes(up_axis, handedness) * up, DVec3::Z);
+            }
+        }
+    }
+
+    #[test]
+    fn renders_scan_metadata_without_missing_fields() {
+        let mut pointcloud = PointCloud::default();
+        pointcloud.name = Some("Station | 1".to_owned());
+        pointcloud.records = 42;
+
+        let markdown = scan_metadata(3, &pointcloud);
+        assert!(markdown.starts_with("# Scan 3\n"));
+        assert!(markdown.contains("| Name | Station \\| 1 |"));
+        assert!(markdown.contains("| Points | 42 |"));
+        assert!(!markdown.contains("Sensor"));
+    }
+}
@@ -1,283 +1,409 @@
-use std::collections::HashSet;
-use std::env;
 use anyhow::{Context, Result};
-use e57::{CartesianCoordinate, E57Reader};
-use rerun::{Points3D, EXTERNAL_DATA_LOADER_INCOMPATIBLE_EXIT_CODE};
-use rerun::{RecordingStreamBuilder, Vec3D};
-
-/// Command line arguments for the E57 Rerun data loader.
-#[derive(argh::FromArgs, Debug)]
-#[argh(description = "Load E57 point clouds and stream them to Rerun")]
-struct Args {
-    #[argh(positional)]
-    filepath: std::path::PathBuf,
-
-    #[argh(option, description = "optional recommended ID for the application")]
-    application_id: Option<String>,
-
-    #[argh(option, description = "optional recommended ID for the application for existing applications")]
-    opened_application_id: Option<String>,
-
-    #[argh(option, description = "optional recommended ID for the recording")]
-    recording_id: Option<String>,
-
-    #[argh(option, description = "optional recommended ID for the recording for existing applications")]
-    opened_recording_id: Option<String>,
-
-    #[argh(option, description = "optional prefix for all entity paths")]
-    entity_path_prefix: Option<String>,
-
-    #[argh(
-        arg_name = "static",
-        switch,
-        description = "optionally mark data to be logged statically"
-    )]
-    #[allow(dead_code)]
-    static_: bool,
-
-    #[argh(
-        option,
-        description = "optional timestamps to log at (e.g. --time sim_time=1709203426)"
-    )]
-    #[allow(dead_code)]
-    time: Vec<String>,
-
-    #[argh(
-        option,
-        description = "optional sequences to log at (e.g. --sequence sim_frame=42)"
-    )]
-    #[allow(dead_code)]
-    sequence: Vec<String>,
-}
+use notify::{EventKind, RecursiveMode, Watcher};
+use rerun::{RecordingStreamBuilder, EXTERNAL_DATA_LOADER_INCOMPATIBLE_EXIT_CODE};
+use rerun_loader_e57::{
+    diff_files, inspect_e57, is_supported_file, load_e57_dir, load_file, load_files, Args, Command,
+    EntityLayout, InfoFormat, InvalidPoints, Stats,
+};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
 
-fn extension(path: &std::path::Path) -> String {
-    path.extension()
-        .unwrap_or_default()
-        .to_ascii_lowercase()
-        .to_string_lossy()
-        .to_string()
-}
+fn main() -> Result<()> {
+    let args = Args::parse()?;
+
+    // E57 readers need random access and every worker opens the file on its own,
+    // so piped input is spilled to a temporary file first.
+    let stdin_file = if args.stdin {
+        anyhow::ensure!(
+            args.filepaths.is_empty(),
+            "--stdin cannot be used together with a file path"
+        );
+        let mut file = tempfile::Builder::new()
+            .suffix(".e57")
+            .tempfile()
+            .context("Failed to create a temporary file for stdin")?;
+        std::io::copy(&mut std::io::stdin().lock(), &mut file)
+            .context("Failed to read E57 data from stdin")?;
+        Some(file)
+    } else {
+        None
+    };
 
-fn get_allowed_scans() -> Option<HashSet<usize>> {
-    let allowed_scans: Option<HashSet<usize>> = match env::var("RERUN_E57_DISPLAY_SCANS") {
-        Ok(val) => {
-            let set = val
-                .split(',')
-                .filter_map(|s| s.trim().parse::<usize>().ok())
-                .collect::<HashSet<_>>();
-            Some(set)
+    // `diff <before> <after>` compares two files instead of loading them.
+    let diff = matches!(args.command, Some(Command::Diff(_)));
+    let filepaths = match &stdin_file {
+        Some(file) => {
+            anyhow::ensure!(!diff, "--stdin cannot be used together with diff");
+            vec![file.path().to_owned()]
+        }
+        None if diff => {
+            anyhow::ensure!(
+                args.filepaths.is_empty(),
+                "diff cannot be used together with other file paths"
+            );
+            let paths = args.paths();
+            for path in &paths {
+                anyhow::ensure!(
+                    path.is_file() && is_supported_file(path),
+                    "Not a supported point cloud file: {path:?}"
+                );
+            }
+            paths
+        }
+        None => {
+            anyhow::ensure!(
+                !args.filepaths.is_empty(),
+                "Missing E57 file path (or --stdin)"
+            );
+            let is_loadable = |path: &std::path::Path| path.is_file() && is_supported_file(path);
+
+            match args.filepaths.as_slice() {
+                // The viewer calls data loaders with a single path and tries the next loader
+                // on this exit code.
+                [filepath] if !is_loadable(filepath) && !filepath.is_dir() => {
+                    #[allow(clippy::exit)]
+                    std::process::exit(EXTERNAL_DATA_LOADER_INCOMPATIBLE_EXIT_CODE);
+                }
+                [_] => {}
+                filepaths => {
+                    if let Some(path) = filepaths.iter().find(|path| !is_loadable(path)) {
+                        anyhow::bail!("Not a supported point cloud file: {path:?}");
+                    }
+                }
+            }
+            args.filepaths.clone()
         }
-        Err(_) => None,
     };
-    
-    allowed_scans
-}
-
-fn main() -> Result<()> {
-    let args: Args = argh::from_env();
-
-    let is_file = args.filepath.is_file();
-    let is_e57 = extension(&args.filepath) == "e57";
 
-    if !is_file || !is_e57 {
-        #[allow(clippy::exit)]
-        std::process::exit(EXTERNAL_DATA_LOADER_INCOMPATIBLE_EXIT_CODE);
+    if let Some(format) = args.info {
+        return print_info(&filepaths, format);
     }
 
-    let mut reader = E57Reader::from_file(&args.filepath)
-        .with_context(|| format!("Failed to read E57 file: {:?}", args.filepath))?;
-
     let rec = {
-        
         let app_id = args
             .opened_application_id
             .as_deref()
             .unwrap_or(args.application_id.as_deref().unwrap_or("rerun_e57_loader"));
 
-        let mut rec = RecordingStreamBuilder::new(
-            app_id,
-        );
+        let mut rec = RecordingStreamBuilder::new(app_id);
 
         if let Some(recording_id) = &args.recording_id {
             rec = rec.recording_id(recording_id);
         } else if let Some(opened_recording_id) = &args.opened_recording_id {
             rec = rec.recording_id(opened_recording_id);
-            
         }
 
-        rec.stdout()?
+        // The batcher keeps the configuration of the `RERUN_FLUSH_*` environment variables that
+        // is not overridden.
+        if args.batch_flush_ms.is_some() || args.batch_max_mb.is_some() {
+            let mut config = rerun::log::ChunkBatcherConfig::from_env()?;
+            if let Some(ms) = args.batch_flush_ms {
+                config.flush_tick = Duration::from_millis(ms);
+            }
+            if let Some(mb) = args.batch_max_mb {
+                config.flush_num_bytes = mb * 1024 * 1024;
+            }
+            rec = rec.batcher_config(config);
+        }
+
+        // An address is only used for connecting, so it is never silently ignored.
+        let connect = args.connect || args.addr.is_some();
+        if args.export.is_some() {
+            anyhow::ensure!(
+                args.save.is_none() && !connect,
+                "--export cannot be used together with --save, --connect or --addr"
+            );
+            rerun::RecordingStream::disabled()
+        } else if let Some(path) = &args.save {
+            if connect {
+                anyhow::bail!("--save cannot be used together with --connect or --addr");
+            }
+            rec.save(path)
+                .with_context(|| format!("Failed to create recording file: {path:?}"))?
+        } else if connect {
+            let addr = args.addr.unwrap_or_else(rerun::default_server_addr);
+            rec.connect_tcp_opts(addr, rerun::default_flush_timeout())?
+        } else {
+            rec.stdout()?
+        }
     };
 
-    // if let Some(timepoint) = timepoint_from_args(&args) {
-    //     rec.set_timepoint(timepoint);
-    // }
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .context("Failed to configure the worker thread pool")?;
+    }
 
-    let allowed_scans = get_allowed_scans();
+    // Without a given recording ID every load logs to a new recording, in which nothing was
+    // logged yet, and stdin is read into a new temporary file every time.
+    anyhow::ensure!(
+        !args.resume || args.recording_id.is_some() || args.opened_recording_id.is_some(),
+        "--resume needs a --recording-id"
+    );
+    anyhow::ensure!(
+        !args.resume || !args.stdin,
+        "--resume cannot be used together with --stdin"
+    );
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    handle_ctrl_c(&cancel)?;
+
+    let options = args.load_options(Some(Arc::clone(&cancel)))?;
+
+    if diff {
+        let stats = diff_files(&filepaths[0], &filepaths[1], &options, &rec)?;
+        if !args.quiet {
+            eprintln!(
+                "Diff: {} voxels added, {} removed, {} moved, {} unchanged",
+                stats.added, stats.removed, stats.moved, stats.unchanged
+            );
+        }
+        return Ok(());
+    }
 
-    let entity_path_prefix = args
-        .entity_path_prefix
-        .as_deref()
-        .unwrap_or("e57_pointcloud");
+    // Returns whether some files, scans or points failed to load.
+    let load = || -> Result<bool> {
+        let stats = match filepaths.as_slice() {
+            [filepath] if filepath.is_dir() => {
+                load_e57_dir(filepath, args.recursive, &options, &rec)?
+            }
+            [filepath] => load_file(filepath, &options, &rec)?,
+            filepaths => load_files(filepaths, &options, &rec)?,
+        };
 
-    let pointclouds = reader.pointclouds();
-    for (index, pointcloud) in pointclouds.iter().enumerate() {
-        if !pointcloud.has_cartesian() {
-            println!("Point cloud #{index} has no XYZ data, skipping...");
-            continue;
+        if !args.quiet {
+            if cancel.load(Ordering::Relaxed) {
+                eprintln!("Cancelled, the points read until then were logged");
+            }
+            print_summary(&stats, options.invalid_points);
         }
-
-        if pointcloud.records < 1 {
-            println!("Point cloud #{index} is empty, skipping...");
-            continue;
+        if let Some(path) = &args.summary_json {
+            write_summary_json(&stats, path)?;
+        }
+        let partial = stats.failed_files > 0 || stats.failed_scans > 0 || stats.decode_errors > 0;
+        if args.strict && partial {
+            anyhow::bail!(
+                "{} files and {} scans failed to load and {} points failed to decode",
+                stats.failed_files,
+                stats.failed_scans,
+                stats.decode_errors
+            );
         }
+        Ok(partial)
+    };
 
-        if let Some(allowed_scans) = &allowed_scans {
-            if !allowed_scans.contains(&index) {
-                continue;
-            }
+    if !args.watch {
+        let partial = load()?;
+        let exit_code = if cancel.load(Ordering::Relaxed) {
+            CANCELLED_EXIT_CODE
+        } else if partial {
+            PARTIAL_LOAD_EXIT_CODE
+        } else {
+            return Ok(());
+        };
+        // Exiting skips the destructors, and with them the flush of the recording.
+        rec.flush_blocking();
+        #[allow(clippy::exit)]
+        std::process::exit(exit_code);
+    }
+    anyhow::ensure!(
+        !args.stdin && args.export.is_none() && !args.resume,
+        "--watch cannot be used together with --stdin, --export or --resume"
+    );
+
+    // A file that is still being written may fail to load, which is reported without
+    // ending the watch.
+    if let Err(e) = load() {
+        eprintln!("{e:#}");
+    }
+    // Files with their own roots are all cleared from the root of the recording.
+    let entity_path_prefix = match options.entity_layout {
+        EntityLayout::PerFile => "/",
+        _ => args
+            .entity_path_prefix
+            .as_deref()
+            .unwrap_or("e57_pointcloud"),
+    };
+    watch(&filepaths, args.recursive, args.quiet, &cancel, || {
+        // The entities of the previous load are cleared, since scans or points may be gone.
+        let clear = rec.log_with_static(
+            entity_path_prefix,
+            options.static_,
+            &rerun::Clear::recursive(),
+        );
+        if let Err(e) = clear
+            .map_err(anyhow::Error::from)
+            .and_then(|()| load().map(drop))
+        {
+            eprintln!("{e:#}");
         }
+    })?;
+    rec.flush_blocking();
+    #[allow(clippy::exit)]
+    std::process::exit(CANCELLED_EXIT_CODE);
+}
 
-        let iter = reader
-            .pointcloud_simple(pointcloud)
-            .context("Unable to get simple point cloud iterator")?;
-
-        let mut chunk_idx = 0;
-        let chunk_size = 1000000;
-
-        let mut buffer = Vec::with_capacity(chunk_size);
-        let mut color_buffer = Vec::with_capacity(chunk_size);
-
-        // if let Some(transform) = &pointcloud.transform {
-        //     let translation = &transform.translation;
-        //     let rotation = &transform.rotation;
-
-        //     let translation = Vec3D::new(
-        //         translation.x as f32,
-        //         translation.y as f32,
-        //         translation.z as f32,
-        //     );
-        //     let rotation = rerun::Rotation3D::Quaternion(RotationQuat(Quaternion([
-        //         rotation.x as f32,
-        //         rotation.y as f32,
-        //         rotation.z as f32,
-        //         rotation.w as f32,
-        //     ])));
-
-        //     let entity_path = format!("{entity_path_prefix}/scan_{index}");
-        //     rec.log_static(
-        //         entity_path,
-        //         &rerun::Transform3D::from_translation_rotation(translation, rotation),
-        //     )?;
-        // }
-
-        rec.set_time_seconds("default", 0);
-        if let Some(transform) = &pointcloud.transform {
-            let translation = &transform.translation;
-
-            let translation = [(
-                translation.x as f32,
-                translation.y as f32,
-                translation.z as f32,
-            )];
-            rec.log(
-                format!("{entity_path_prefix}/scan_{index}/point"),
-                &Points3D::new(translation)
-                    .with_colors([rerun::Color::from_rgb(255, 0, 0)])
-                    .with_radii([0.15_f32])
-                    .with_labels([format!("Scan {index}")])
-            )?;
+/// Prints the structure of the E57 files to stdout, without connecting to a viewer.
+fn print_info(filepaths: &[PathBuf], format: InfoFormat) -> Result<()> {
+    let infos = filepaths
+        .iter()
+        .map(|path| {
+            anyhow::ensure!(
+                path.extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("e57")),
+                "--info only supports E57 files: {path:?}"
+            );
+            inspect_e57(path)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    match format {
+        InfoFormat::Table => {
+            let tables: Vec<_> = infos.iter().map(ToString::to_string).collect();
+            print!("{}", tables.join("\n"));
         }
+        InfoFormat::Json => println!("{}", serde_json::to_string_pretty(&infos)?),
+    }
+    Ok(())
+}
 
-        for point_result in iter {
-            let p = match point_result {
-                Ok(p) => p,
-                Err(e) => {
-                    eprintln!("Skipping point due to error: {e}");
-                    continue;
-                }
-            };
-
-            if let CartesianCoordinate::Valid { x, y, z } = p.cartesian {
-                buffer.push(Vec3D::new(x as f32, y as f32, z as f32));
-                let color = match p.color {
-                    Some(color) => rerun::Color::from_rgb(
-                        (color.red * 255.0) as u8,
-                        (color.green * 255.0) as u8,
-                        (color.blue * 255.0) as u8,
-                    ),
-                    _ => rerun::Color::from_rgb(255, 255, 255),
-                };
-                color_buffer.push(color)
+/// Writes the stats with the reports of all scans as JSON to `path`, or to stdout for `-`.
+fn write_summary_json(stats: &Stats, path: &Path) -> Result<()> {
+    if path == Path::new("-") {
+        println!("{}", serde_json::to_string_pretty(stats)?);
+        return Ok(());
+    }
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create summary file: {path:?}"))?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), stats)
+        .with_context(|| format!("Failed to write summary file: {path:?}"))
+}
+
+/// Exit code after a load in which some files, scans or points failed to load, while the rest
+/// was logged.
+const PARTIAL_LOAD_EXIT_CODE: i32 = 3;
+
+/// Exit code after a load that was cancelled with Ctrl+C, as for a process killed by SIGINT.
+const CANCELLED_EXIT_CODE: i32 = 130;
+
+/// Sets `cancel` on the first Ctrl+C, so that the points read until then are still logged and
+/// flushed, and exits at once on the second one.
+fn handle_ctrl_c(cancel: &Arc<AtomicBool>) -> Result<()> {
+    let cancel = Arc::clone(cancel);
+    // SAFETY: The handler only swaps an atomic and calls `_exit`, which are both
+    // async-signal-safe.
+    unsafe {
+        signal_hook_registry::register(libc::SIGINT, move || {
+            if cancel.swap(true, Ordering::SeqCst) {
+                libc::_exit(CANCELLED_EXIT_CODE);
             }
+        })
+    }
+    .context("Failed to handle Ctrl+C")?;
+    Ok(())
+}
 
-            if buffer.len() >= chunk_size {
-                rec.log(
-                    format!("{entity_path_prefix}/scan_{index}/chunk_{chunk_idx}"),
-                    &rerun::Points3D::new(std::mem::take(&mut buffer))
-                        .with_colors(color_buffer.clone()),
-                )?;
+/// Quiet period after the last change before the files are loaded again, so that a file is
+/// not read while it is still being written.
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// Interval at which the watch checks whether it was cancelled.
+const WATCH_POLL: Duration = Duration::from_millis(100);
+
+/// Calls `reload` whenever one of `paths` changes, until `cancel` is set.
+fn watch(
+    paths: &[PathBuf],
+    recursive: bool,
+    quiet: bool,
+    cancel: &AtomicBool,
+    mut reload: impl FnMut(),
+) -> Result<()> {
+    let paths = paths
+        .iter()
+        .map(|path| {
+            path.canonicalize()
+                .with_context(|| format!("Failed to watch {path:?}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).context("Failed to create a file watcher")?;
+    // Files are watched through their directory, since exporters often replace a file
+    // instead of writing it in place, which would end a watch on the file itself.
+    for path in &paths {
+        let (watched, mode) = match path.parent() {
+            _ if path.is_dir() && recursive => (path.as_path(), RecursiveMode::Recursive),
+            _ if path.is_dir() => (path.as_path(), RecursiveMode::NonRecursive),
+            Some(parent) => (parent, RecursiveMode::NonRecursive),
+            None => (path.as_path(), RecursiveMode::NonRecursive),
+        };
+        watcher
+            .watch(watched, mode)
+            .with_context(|| format!("Failed to watch {watched:?}"))?;
+    }
 
-                buffer.clear();
-                color_buffer.clear();
-                chunk_idx += 1;
-            }
-        }
+    let is_relevant = |event: &notify::Event| {
+        !matches!(event.kind, EventKind::Access(_))
+            && event.paths.iter().any(|changed| {
+                paths.iter().any(|path| {
+                    changed == path
+                        || (path.is_dir()
+                            && changed.starts_with(path)
+                            && is_supported_file(changed))
+                })
+            })
+    };
 
-        if !buffer.is_empty() {
-            rec.log(
-                format!("{entity_path_prefix}/scan_{index}/chunk_{chunk_idx}"),
-                &rerun::Points3D::new(buffer).with_colors(color_buffer.clone()),
-            )?;
+    if !quiet {
+        eprintln!("Watching for changes, press Ctrl+C to stop...");
+    }
+    while !cancel.load(Ordering::Relaxed) {
+        let event = match receiver.recv_timeout(WATCH_POLL) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if !event.is_ok_and(|event| is_relevant(&event)) {
+            continue;
+        }
+        while receiver.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+        if !quiet {
+            eprintln!("Loading again after a change...");
         }
+        reload();
     }
-
     Ok(())
 }
 
-// fn timepoint_from_args(args: &Args) -> Option<rerun::TimePoint> {
-//     if args.time.is_empty() && args.sequence.is_empty() {
-//         return None;
-//     }
-
-//     let mut timepoint = rerun::TimePoint::default();
-
-//     for time_str in &args.time {
-//         if let Some((timeline_name, time)) = time_str.split_once('=') {
-
-//             let parsed_time = match time.parse::<i64>() {
-//                 Ok(parsed_time) => parsed_time,
-//                 Err(_) => {
-//                     eprintln!("Invalid time value: {time}");
-//                     return None
-//                 }
-//             };
-
-//             timepoint.insert(
-//                 rerun::Timeline::new_temporal(timeline_name),
-//                 parsed_time,
-//             );
-//         }
-//     }
-
-//     for seq_str in &args.sequence {
-
-        
-//         if let Some((seqline_name, seq)) = seq_str.split_once('=') {
-
-//             let parsed_time = match seq.parse::<i64>() {
-//                 Ok(parsed_time) => parsed_time,
-//                 Err(_) => {
-//                     eprintln!("Invalid time value: {seq}");
-//                     return None
-//                 }
-//             };
-            
-//             timepoint.insert(
-//                 rerun::Timeline::new_sequence(seqline_name),
-//                 parsed_time,
-//             );
-//         }
-//     }
-
-//     Some(timepoint)
-// }
+/// Prints what was loaded and what was left out to stderr.
+fn print_summary(stats: &Stats, invalid_points: InvalidPoints) {
+    let invalid_action = match invalid_points {
+        InvalidPoints::Skip => "skipped",
+        InvalidPoints::Zero => "logged at the origin",
+        InvalidPoints::LogSeparately => "logged separately",
+    };
+    eprintln!("Summary:");
+    if stats.failed_files > 0 {
+        eprintln!("  files failed:   {}", stats.failed_files);
+    }
+    eprintln!("  scans loaded:   {}", stats.scans);
+    eprintln!("  scans failed:   {}", stats.failed_scans);
+    if stats.skipped_scans > 0 {
+        eprintln!("  scans skipped:  {} (too few points)", stats.skipped_scans);
+    }
+    if stats.duplicate_scans > 0 {
+        eprintln!("  scans skipped:  {} (duplicates)", stats.duplicate_scans);
+    }
+    eprintln!("  points logged:  {}", stats.points);
+    eprintln!(
+        "  invalid points: {} ({invalid_action})",
+        stats.invalid_points
+    );
+    eprintln!("  decode errors:  {}", stats.decode_errors);
+    eprintln!("  images logged:  {}", stats.images);
+}
@@ -0,0 +1,125 @@
+//! Oriented bounding boxes of scans and clusters from their principal axes, for `--measure`.
+
+use glam::{DMat3, DQuat, DVec3};
+
+/// Sweeps of the Jacobi eigenvalue algorithm, which converges in a few for 3x3 matrices.
+const JACOBI_SWEEPS: usize = 16;
+
+/// Box around points along their principal axes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct OrientedBox {
+    pub center: DVec3,
+    /// Rotation from the box frame, whose X axis is the direction of the largest variance.
+    pub rotation: DQuat,
+    /// Extents along the principal axes, from the largest to the smallest variance.
+    pub size: DVec3,
+}
+
+/// Returns the box around the points along the eigenvectors of their covariance, or `None`
+/// without points.
+pub(crate) fn oriented_box(points: &[DVec3]) -> Option<OrientedBox> {
+    if points.is_empty() {
+        return None;
+    }
+    let mean = points.iter().sum::<DVec3>() / points.len() as f64;
+    let covariance = points.iter().fold(DMat3::ZERO, |sum, p| {
+        let d = *p - mean;
+        sum + DMat3::from_cols(d * d.x, d * d.y, d * d.z)
+    }) / points.len() as f64;
+    let axes = principal_axes(covariance);
+
+    let (mut min, mut max) = (DVec3::INFINITY, DVec3::NEG_INFINITY);
+    for p in points {
+        let local = axes.transpose() * (*p - mean);
+        min = min.min(local);
+        max = max.max(local);
+    }
+    Some(OrientedBox {
+        center: mean + axes * ((min + max) / 2.0),
+        rotation: DQuat::from_mat3(&axes),
+        size: max - min,
+    })
+}
+
+/// Returns the eigenvectors of a symmetric matrix as the columns of a rotation, in the order
+/// of decreasing eigenvalues.
+fn principal_axes(mut a: DMat3) -> DMat3 {
+    let mut vectors = DMat3::IDENTITY;
+    for _ in 0..JACOBI_SWEEPS {
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            let apq = a.col(q)[p];
+            if apq.abs() < 1e-15 {
+                continue;
+            }
+            // The rotation in the p-q plane that zeroes the off-diagonal element.
+            let theta = (a.col(q)[q] - a.col(p)[p]) / (2.0 * apq);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+            let mut rotation = DMat3::IDENTITY;
+            rotation.col_mut(p)[p] = c;
+            rotation.col_mut(p)[q] = -s;
+            rotation.col_mut(q)[p] = s;
+            rotation.col_mut(q)[q] = c;
+            a = rotation.transpose() * a * rotation;
+            vectors *= rotation;
+        }
+    }
+
+    let mut order = [0, 1, 2];
+    order.sort_by(|i, j| a.col(*j)[*j].total_cmp(&a.col(*i)[*i]));
+    let [x, y] = [order[0], order[1]].map(|i| vectors.col(i));
+    // The third axis makes the rotation right-handed.
+    DMat3::from_cols(x, y, x.cross(y))
+}
+
+/// Returns the boxes labeled with their names and dimensions, e.g. `scan_0: 12.50 x 8.20 x
+/// 3.10 m`.
+pub(crate) fn boxes(boxes: &[(String, OrientedBox)]) -> rerun::Boxes3D {
+    rerun::Boxes3D::from_centers_and_half_sizes(
+        boxes.iter().map(|(_, b)| b.center.as_vec3().to_array()),
+        boxes
+            .iter()
+            .map(|(_, b)| (b.size / 2.0).as_vec3().to_array()),
+    )
+    .with_quaternions(boxes.iter().map(|(_, b)| {
+        let q = b.rotation.as_quat();
+        rerun::Quaternion::from_xyzw([q.x, q.y, q.z, q.w])
+    }))
+    .with_labels(boxes.iter().map(|(name, b)| {
+        format!(
+            "{name}: {:.2} x {:.2} x {:.2} m",
+            b.size.x, b.size.y, b.size.z
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_a_rotated_box() {
+        // The corners of a 4 x 2 x 1 m box, rotated around Z and moved.
+        let rotation = DQuat::from_rotation_z(0.5);
+        let mut points = Vec::new();
+        for x in [-2.0, 2.0] {
+            for y in [-1.0, 1.0] {
+                for z in [-0.5, 0.5] {
+                    points.push(rotation * DVec3::new(x, y, z) + DVec3::new(10.0, 5.0, 1.0));
+                }
+            }
+        }
+        let b = oriented_box(&points).unwrap();
+        assert!(b.size.abs_diff_eq(DVec3::new(4.0, 2.0, 1.0), 1e-9), "{b:?}");
+        assert!(
+            b.center.abs_diff_eq(DVec3::new(10.0, 5.0, 1.0), 1e-9),
+            "{b:?}"
+        );
+        let x_axis = b.rotation * DVec3::X;
+        assert!(
+            x_axis.dot(rotation * DVec3::X).abs() > 1.0 - 1e-9,
+            "{x_axis}"
+        );
+    }
+}
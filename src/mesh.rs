@@ -0,0 +1,133 @@
+//! Surface meshes of structured scans, by greedy triangulation of their range image, for
+//! `--mesh`.
+
+use crate::grid::Grid;
+use anyhow::Result;
+use glam::Vec3;
+
+/// Longest edge of a triangle in meters, at any range and per meter of range, above which the
+/// neighbors are taken to be on different surfaces instead, e.g. at depth discontinuities.
+const MAX_EDGE: f32 = 0.1;
+const MAX_EDGE_PER_METER: f32 = 0.05;
+
+/// Collects the logged points of a structured scan by their row and column index.
+#[derive(Default)]
+pub(crate) struct RangeMesh {
+    vertices: Vec<(i64, i64, Vec3, f32, rerun::Color)>,
+}
+
+impl RangeMesh {
+    /// Adds a point with its logged position, its range from the scanner and its color.
+    pub fn insert(
+        &mut self,
+        row: i64,
+        column: i64,
+        position: Vec3,
+        range: f32,
+        color: rerun::Color,
+    ) {
+        self.vertices.push((row, column, position, range, color));
+    }
+
+    /// Returns the mesh and its number of vertices, or `None` if there are no triangles. The
+    /// normals face the scanner at its logged position. Fails for grids of more than
+    /// `MAX_GRID_CELLS` rows and columns.
+    pub fn into_mesh(self, scanner: Vec3) -> Result<Option<(rerun::Mesh3D, u64)>> {
+        let triangles = self.triangles()?;
+        if triangles.is_empty() {
+            return Ok(None);
+        }
+        let vertices = &self.vertices;
+
+        // Area-weighted vertex normals, which are flipped towards the scanner since the winding
+        // depends on the order of the rows and columns.
+        let mut normals = vec![Vec3::ZERO; vertices.len()];
+        for [a, b, c] in &triangles {
+            let [pa, pb, pc] = [a, b, c].map(|i| vertices[*i as usize].2);
+            let normal = (pb - pa).cross(pc - pa);
+            for i in [a, b, c] {
+                normals[*i as usize] += normal;
+            }
+        }
+
+        let mesh = rerun::Mesh3D::new(vertices.iter().map(|v| v.2.to_array()))
+            .with_vertex_colors(vertices.iter().map(|v| v.4))
+            .with_vertex_normals(normals.iter().zip(vertices).map(|(normal, vertex)| {
+                let normal = normal.normalize_or_zero();
+                let normal = if normal.dot(scanner - vertex.2) < 0.0 {
+                    -normal
+                } else {
+                    normal
+                };
+                normal.to_array()
+            }))
+            .with_triangle_indices(triangles);
+        Ok(Some((mesh, vertices.len() as u64)))
+    }
+
+    /// Triangulates every square of neighboring rows and columns into two triangles, skipping
+    /// the triangles with missing points or with too long edges.
+    fn triangles(&self) -> Result<Vec<[u32; 3]>> {
+        let cells = self.vertices.iter().map(|v| (v.0, v.1));
+        let Some(bounds) = Grid::bounding(cells, "--mesh")? else {
+            return Ok(Vec::new());
+        };
+        let (width, height) = (bounds.width, bounds.height);
+
+        let mut grid: Vec<Option<u32>> = vec![None; bounds.len()];
+        for (index, (row, column, ..)) in self.vertices.iter().enumerate() {
+            grid[bounds.index(*row, *column).expect("in the bounds")] = Some(index as u32);
+        }
+
+        let vertices = &self.vertices;
+        let is_surface = |triangle: [u32; 3]| {
+            let [a, b, c] = triangle.map(|i| vertices[i as usize]);
+            let range = a.3.min(b.3).min(c.3);
+            let longest =
+                a.2.distance(b.2)
+                    .max(b.2.distance(c.2))
+                    .max(c.2.distance(a.2));
+            longest <= MAX_EDGE.max(MAX_EDGE_PER_METER * range)
+        };
+        let mut triangles = Vec::new();
+        for row in 0..height.saturating_sub(1) {
+            for column in 0..width.saturating_sub(1) {
+                let at = |r: usize, c: usize| grid[(row + r) * width + column + c];
+                let (top_left, top_right) = (at(0, 0), at(0, 1));
+                let (bottom_left, bottom_right) = (at(1, 0), at(1, 1));
+                for triangle in [
+                    [top_left, bottom_left, top_right],
+                    [top_right, bottom_left, bottom_right],
+                ] {
+                    if let [Some(a), Some(b), Some(c)] = triangle {
+                        if is_surface([a, b, c]) {
+                            triangles.push([a, b, c]);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(triangles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_triangles_across_depth_discontinuities() {
+        // A 3 by 3 grid of points 1 cm apart 1 m from the scanner, with the last column 5 m
+        // further away.
+        let mut mesh = RangeMesh::default();
+        for row in 0..3 {
+            for column in 0..3 {
+                let depth = if column == 2 { 6.0 } else { 1.0 };
+                let position = Vec3::new(depth, column as f32 * 0.01, row as f32 * 0.01);
+                mesh.insert(row, column, position, depth, rerun::Color::WHITE);
+            }
+        }
+        assert_eq!(mesh.triangles().unwrap().len(), 4);
+        assert_eq!(mesh.into_mesh(Vec3::ZERO).unwrap().unwrap().1, 9);
+    }
+}
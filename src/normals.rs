@@ -0,0 +1,132 @@
+//! Estimates normals for points without them, from the principal axes of their neighborhood.
+
+use glam::{DMat3, DVec3, Vec3};
+use rayon::prelude::*;
+use rerun::Vec3D;
+
+use crate::kdtree::KdTree;
+use crate::NormalEstimation;
+
+/// Upper bound for the neighbors of a radius search, which keeps dense areas affordable.
+const MAX_RADIUS_NEIGHBORS: usize = 64;
+
+/// Fills in the missing normals of a chunk, using the other points of the chunk as neighbors.
+///
+/// The normal of a point is the direction of least variance of its neighborhood, oriented
+/// towards the origin of the scan, i.e. towards the scanner. Points with fewer than three
+/// neighbors or with collinear neighbors keep no normal.
+pub(crate) fn estimate_normals(
+    positions: &[Vec3D],
+    normals: &mut [Option<Vec3D>],
+    estimation: NormalEstimation,
+) {
+    if normals.iter().all(Option::is_some) {
+        return;
+    }
+
+    let points: Vec<[f32; 3]> = positions
+        .iter()
+        .map(|p| Vec3::from(*p).to_array())
+        .collect();
+    let tree = KdTree::new(&points);
+    let (k, max_distance) = match estimation {
+        NormalEstimation::Neighbors(k) => (k, f32::INFINITY),
+        NormalEstimation::Radius(radius) => (MAX_RADIUS_NEIGHBORS, radius as f32),
+    };
+
+    normals
+        .par_iter_mut()
+        .zip(&points)
+        .filter(|(normal, _)| normal.is_none())
+        .for_each(|(normal, point)| {
+            let neighbors = tree.nearest(*point, k, max_distance);
+            let neighborhood: Vec<DVec3> = neighbors
+                .iter()
+                .map(|n| Vec3::from(points[n.index as usize]).as_dvec3())
+                .collect();
+            *normal = plane_normal(&neighborhood).map(|n| {
+                let n = if n.dot(Vec3::from(*point).as_dvec3()) > 0.0 {
+                    -n
+                } else {
+                    n
+                };
+                Vec3D::from(n.as_vec3().to_array())
+            });
+        });
+}
+
+/// Returns the unit eigenvector of the smallest eigenvalue of the covariance of `points`.
+pub(crate) fn plane_normal(points: &[DVec3]) -> Option<DVec3> {
+    if points.len() < 3 {
+        return None;
+    }
+    let mean = points.iter().sum::<DVec3>() / points.len() as f64;
+    let covariance = points.iter().fold(DMat3::ZERO, |sum, p| {
+        let d = *p - mean;
+        sum + DMat3::from_cols(d * d.x, d * d.y, d * d.z)
+    }) / points.len() as f64;
+
+    let eigenvalue = smallest_eigenvalue(&covariance);
+    let m = covariance - DMat3::from_diagonal(DVec3::splat(eigenvalue));
+    // The eigenvector is orthogonal to the rows of `m`, which span a plane for a unique
+    // eigenvalue. The largest cross product of two rows is the most accurate, and close to
+    // zero if the points are collinear.
+    let (r0, r1, r2) = (m.row(0), m.row(1), m.row(2));
+    let scale = (covariance.x_axis.x + covariance.y_axis.y + covariance.z_axis.z).powi(2);
+    [r0.cross(r1), r0.cross(r2), r1.cross(r2)]
+        .into_iter()
+        .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        .filter(|n| n.length() > 1e-6 * scale)
+        .and_then(DVec3::try_normalize)
+}
+
+/// Closed-form smallest eigenvalue of a symmetric 3x3 matrix.
+fn smallest_eigenvalue(a: &DMat3) -> f64 {
+    let off_diagonal = a.y_axis.x.powi(2) + a.z_axis.x.powi(2) + a.z_axis.y.powi(2);
+    let diagonal = DVec3::new(a.x_axis.x, a.y_axis.y, a.z_axis.z);
+    if off_diagonal == 0.0 {
+        return diagonal.min_element();
+    }
+
+    let q = diagonal.element_sum() / 3.0;
+    let p = (((diagonal - q).length_squared() + 2.0 * off_diagonal) / 6.0).sqrt();
+    let b = (*a - DMat3::from_diagonal(DVec3::splat(q))) * (1.0 / p);
+    let phi = (b.determinant() / 2.0).clamp(-1.0, 1.0).acos() / 3.0;
+    q + 2.0 * p * (phi + 2.0 * std::f64::consts::PI / 3.0).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_normals_of_a_plane_towards_the_scanner() {
+        let positions: Vec<Vec3D> = (0..100)
+            .map(|i| Vec3D::new((i % 10) as f32 * 0.1, (i / 10) as f32 * 0.1, 2.0))
+            .collect();
+        let mut normals = vec![None; positions.len()];
+        normals[0] = Some(Vec3D::new(1.0, 0.0, 0.0));
+
+        estimate_normals(&positions, &mut normals, NormalEstimation::Neighbors(8));
+
+        assert_eq!(normals[0], Some(Vec3D::new(1.0, 0.0, 0.0)));
+        for normal in &normals[1..] {
+            let normal = Vec3::from(normal.unwrap());
+            assert!(normal.abs_diff_eq(Vec3::NEG_Z, 1e-4), "{normal}");
+        }
+
+        // Neighbors on a line or a radius without neighbors leave the normals undefined.
+        let mut normals = vec![None; positions.len()];
+        estimate_normals(
+            &positions[..10],
+            &mut normals[..10],
+            NormalEstimation::Neighbors(8),
+        );
+        estimate_normals(
+            &positions[10..],
+            &mut normals[10..],
+            NormalEstimation::Radius(0.05),
+        );
+        assert!(normals.iter().all(Option::is_none));
+    }
+}
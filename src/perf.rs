@@ -0,0 +1,106 @@
+//! Throughput of the loading, logged as scalars for `--log-perf`.
+
+use anyhow::Result;
+use rerun::RecordingStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Interval at which the rates are logged.
+const PERF_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Totals the workers add to, sampled by the thread of `PerfLogger`.
+#[derive(Debug, Default)]
+pub(crate) struct PerfCounters {
+    /// Records decoded from the file.
+    pub records: AtomicU64,
+    /// Points handed over to the recording, and their buffered size in bytes.
+    pub points: AtomicU64,
+    pub bytes: AtomicU64,
+}
+
+/// Logs the rates of the counters on a thread of its own, which has no timeline but the
+/// wall-clock `log_time` of the recording, so the rates are plotted over the time of the load.
+pub(crate) struct PerfLogger {
+    pub counters: Arc<PerfCounters>,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl PerfLogger {
+    pub fn spawn(rec: &RecordingStream, entity_path: String) -> Result<Self> {
+        let counters = Arc::new(PerfCounters::default());
+        let (stop, stopped) = mpsc::channel::<()>();
+        let rec = rec.clone();
+        let thread = std::thread::Builder::new()
+            .name("perf-logger".to_owned())
+            .spawn({
+                let counters = Arc::clone(&counters);
+                move || log_rates(&rec, &entity_path, &counters, &stopped)
+            })?;
+        Ok(Self {
+            counters,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// Logs the rates since the last interval and waits for the thread.
+    pub fn finish(mut self) -> Result<()> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<()> {
+        self.stop.take();
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Logs the rates of the counters every `PERF_INTERVAL`, and a last time when `stopped`
+/// disconnects.
+fn log_rates(
+    rec: &RecordingStream,
+    entity_path: &str,
+    counters: &PerfCounters,
+    stopped: &Receiver<()>,
+) -> Result<()> {
+    const NAMES: [&str; 3] = [
+        "records_per_second",
+        "points_per_second",
+        "bytes_per_second",
+    ];
+    let (mut last, mut last_totals) = (Instant::now(), [0; 3]);
+    loop {
+        let stop = !matches!(
+            stopped.recv_timeout(PERF_INTERVAL),
+            Err(RecvTimeoutError::Timeout)
+        );
+        let now = Instant::now();
+        let totals = [&counters.records, &counters.points, &counters.bytes]
+            .map(|counter| counter.load(Ordering::Relaxed));
+        let seconds = (now - last).as_secs_f64();
+        for ((name, total), previous) in NAMES.into_iter().zip(totals).zip(last_totals) {
+            rec.log(
+                format!("{entity_path}/{name}"),
+                &rerun::Scalar::new((total - previous) as f64 / seconds),
+            )?;
+        }
+        (last, last_totals) = (now, totals);
+        if stop {
+            return Ok(());
+        }
+    }
+}
+
+impl Drop for PerfLogger {
+    fn drop(&mut self) {
+        // Errors were already reported by `finish`, unless the load failed anyway.
+        let _ = self.join();
+    }
+}
@@ -0,0 +1,2399 @@
+//! The format-independent part of loading a scan: filtering, coloring, downsampling and
+//! logging the points in chunks.
+
+use anyhow::Result;
+use glam::{DAffine3, DMat3, DQuat, DVec3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rerun::external::arrow::array::{ArrayRef, Float32Array, Int64Array};
+use rerun::external::arrow::compute::cast;
+use rerun::external::arrow::datatypes::DataType;
+use rerun::{AsComponents, ComponentDescriptor, Loggable, Points3D, SerializedComponentBatch};
+use rerun::{RecordingStream, TimePoint, Vec3D};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::classification::ClassMap;
+use crate::clusters::{dbscan, ClusterId};
+use crate::colormap::scan_color;
+use crate::coverage::{Coverage, ScanCoverage};
+use crate::dem::Dem;
+use crate::export::Exporter;
+use crate::fields::ExtraScalars;
+use crate::grid::Grid;
+use crate::ground::{GroundModel, GroundSamples};
+use crate::measure::{oriented_box, OrientedBox};
+#[cfg(feature = "mesh")]
+use crate::mesh::RangeMesh;
+use crate::normals::estimate_normals;
+use crate::perf::{PerfCounters, PerfLogger};
+use crate::planes::detect_planes;
+use crate::resume::Checkpoint;
+use crate::samples::PointSamples;
+use crate::shading::RangeShading;
+use crate::stats::ScanStats;
+use crate::{
+    Bounds, ColorBy, Colormap, CropFrame, EntityLayout, FlushEvery, InvalidPoints, LoadOptions,
+    NormalEstimation, NormalsMode, PointRadius, Precision, Returns, ScanOrigins, ScanReport,
+    TimeSource,
+};
+
+/// A decoded point in the local frame of its scan, independent of the file format.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SourcePoint {
+    /// Position in the units of the file, converted to the logged 32-bit position by the
+    /// pipeline.
+    pub position: DVec3,
+    pub color: Option<rerun::Color>,
+    /// Raw intensity, normalized with the intensity range of the scan.
+    pub intensity: Option<f32>,
+    pub row: i64,
+    pub column: i64,
+    pub classification: Option<u8>,
+    pub normal: Option<Vec3D>,
+    /// Seconds since the acquisition start of the scan.
+    pub time: Option<f64>,
+    /// Zero-based return index and number of returns of the pulse, for multi-return scans.
+    pub returns: Option<(u32, u32)>,
+    /// Values of the `--extra-field` attributes.
+    pub extras: ExtraScalars,
+}
+
+/// Properties of a scan provided by the format-specific reader.
+pub(crate) struct ScanInfo {
+    pub index: usize,
+    /// Entity path of the scan, e.g. `{prefix}/scan_{index}`.
+    pub entity_path: String,
+    /// Attribute the points are colored by, picked from the color priority if one is given.
+    pub color_by: ColorBy,
+    pub log_intensity: bool,
+    pub intensity_range: Option<(f64, f64)>,
+    /// Whether the points have normals that are logged. Estimated normals are added by the
+    /// pipeline itself.
+    pub normals: bool,
+    /// Slots in `SourcePoint::extras` and names of the extra fields the scan has.
+    pub extra_fields: Vec<(usize, String)>,
+    /// Local bounding box, needed for levels of detail and `--bounds`.
+    pub bounds: Option<([f64; 3], [f64; 3])>,
+    /// Pose of the scan in file-level coordinates, unless transforms are disabled.
+    pub world: Option<(DVec3, DQuat)>,
+    /// Whether the points have row and column indices.
+    pub structured: bool,
+    /// Whether the points have a classification, which is logged as their class ids.
+    pub classified: bool,
+    /// Number of records, or 0 if the format only tells after reading them.
+    pub records: u64,
+    /// Start of the acquisition in seconds since the Unix epoch, if the format records it.
+    pub acquisition_time: Option<f64>,
+    /// Whether only the coarse preview of `--preview-first` is logged, to `{scan}/preview`.
+    pub preview: bool,
+    /// Shading of the point colors with `--shade`, by row and column.
+    pub shading: Option<RangeShading>,
+}
+
+/// Whether a point is one of the `returns` that are loaded. Points without a return index,
+/// e.g. of single-return scans, always are.
+fn is_selected_return(returns: Returns, point: Option<(u32, u32)>) -> bool {
+    match (returns, point) {
+        (Returns::All, _) | (_, None) => true,
+        (Returns::First, Some((index, _))) => index == 0,
+        (Returns::Last, Some((index, count))) => index + 1 >= count,
+    }
+}
+
+/// Tile, slice, class, ground segment and cluster of the points that are logged as their own
+/// entity, e.g. `tile_4_-1/slice_3/class_2/ground/cluster_0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Part {
+    tile: Option<(i64, i64)>,
+    slice: Option<i64>,
+    class: Option<u8>,
+    ground: Option<bool>,
+    cluster: Option<ClusterId>,
+}
+
+impl Part {
+    fn entity_path(self, points_path: &str) -> String {
+        let mut path = points_path.to_owned();
+        if let Some((x, y)) = self.tile {
+            path += &format!("/tile_{x}_{y}");
+        }
+        if let Some(slice) = self.slice {
+            path += &format!("/slice_{slice}");
+        }
+        if let Some(class) = self.class {
+            path += &format!("/class_{class}");
+        }
+        match self.ground {
+            Some(true) => path += "/ground",
+            Some(false) => path += "/non_ground",
+            None => {}
+        }
+        match self.cluster {
+            Some(ClusterId::Cluster(cluster)) => path += &format!("/cluster_{cluster}"),
+            Some(ClusterId::Noise) => path += "/noise",
+            None => {}
+        }
+        path
+    }
+}
+
+/// Filters, colors, downsamples and logs the points of a single scan.
+pub(crate) struct ScanPipeline<'a> {
+    loader: &'a Loader<'a>,
+    info: ScanInfo,
+    /// Local position in meters of the origin of the scan entity.
+    origin: DVec3,
+    /// Rotation and translation from the local frame in meters to the logged points.
+    points_from_local: (DQuat, DVec3),
+    /// Transform from the local frame in the units of the file to the logged points.
+    logged_from_file: DAffine3,
+    /// Records waiting to be transformed as a batch, with whether they are invalid points that
+    /// are logged separately, and the buffer of their transformed positions.
+    pending: Vec<(SourcePoint, bool)>,
+    pending_positions: Vec<Vec3D>,
+    /// Entity the chunks are logged under, the scan itself or `{prefix}/merged`.
+    points_path: String,
+    colormap: Colormap,
+    chunk_size: usize,
+    sink: PointSink<'a>,
+    /// Sinks of the slices and classes the points are split into.
+    part_sinks: BTreeMap<Part, PointSink<'a>>,
+    lods: Vec<PointSink<'a>>,
+    /// Receives the invalid points with `InvalidPoints::LogSeparately`.
+    invalid_sink: Option<PointSink<'a>>,
+    /// Thread the chunks of all sinks are logged on, unless they are exported or limited by a
+    /// memory budget. It is declared after the sinks, so their writers are dropped first.
+    logger: Option<Arc<ChunkLogger>>,
+    issues: Issues,
+    range_image: Option<RangeImage>,
+    panorama: Option<Panorama>,
+    /// Lowest heights of the records for `--segment-ground`, until the ground model is built
+    /// from them.
+    ground_samples: Option<GroundSamples>,
+    ground: Option<GroundModel>,
+    /// Logged positions for `--detect-planes` and `--measure`.
+    samples: Option<PointSamples>,
+    /// Points of `--cluster` with their part, which are logged once the scan is clustered.
+    clustered: Option<Vec<(LoggedPoint, ExtraScalars, Part)>>,
+    /// Voxels of the points in the region, with `--coverage-heatmap`.
+    coverage: Option<ScanCoverage>,
+    /// Heights of the ground points in the region, with `--dem`.
+    dem: Option<Dem>,
+    /// Logged points projected onto the `--section`, with their colors.
+    section_points: Option<Vec<([f32; 2], rerun::Color)>>,
+    /// Points of structured scans with `--mesh`, which are logged as a mesh instead.
+    #[cfg(feature = "mesh")]
+    mesh: Option<RangeMesh>,
+    rng: StdRng,
+    point_idx: usize,
+    unreported: u64,
+    /// Number of records pushed so far, for the scan report.
+    records: u64,
+    /// Local bounds in meters of the points that are logged.
+    logged_bounds: Option<(DVec3, DVec3)>,
+    /// Histograms of the points in the region, with `--stats`.
+    stats: Option<ScanStats>,
+    /// With `--max-points`, the number of points the scan may still log, and the number of
+    /// records that are still to come.
+    quota: Option<(u64, u64)>,
+    started: Instant,
+}
+
+impl<'a> ScanPipeline<'a> {
+    /// Sets the timepoint of the current worker and logs the scan transform, and the scan
+    /// bounds if requested.
+    pub fn new(loader: &'a Loader<'a>, mut info: ScanInfo) -> Result<Self> {
+        let &Loader { rec, options, .. } = loader;
+        info.normals |= options.estimate_normals.is_some() && options.normals != NormalsMode::Skip;
+        // The pipeline works in meters, like the options.
+        if let Some(scale) = options.scale {
+            info.world = info.world.map(|(t, q)| (t * scale, q));
+            info.bounds = info
+                .bounds
+                .map(|(min, max)| (min.map(|v| v * scale), max.map(|v| v * scale)));
+        }
+        let pose = info.world;
+        if let Some((t, _)) = pose.filter(|_| options.show_trajectory && !info.preview) {
+            loader
+                .trajectory
+                .lock()
+                .expect("trajectory poisoned")
+                .push((info.acquisition_time, info.index, t));
+        }
+        // Scans without a pose are in file-level coordinates, so their points are logged
+        // relative to the center instead. Far from that origin, the points are logged relative
+        // to the center of the scan bounds, to keep the precision of the 32-bit positions.
+        let mut origin = match info.world {
+            Some(_) => DVec3::ZERO,
+            None => loader.center,
+        };
+        if let Some((min, max)) = info.bounds {
+            let middle = (DVec3::from(min) + DVec3::from(max)) / 2.0;
+            if middle.distance(origin) > LOCAL_ORIGIN_DISTANCE {
+                origin = middle;
+            }
+        }
+        if origin != DVec3::ZERO {
+            // From here on, the pose is the one of the logged points.
+            info.world = Some(match info.world {
+                Some((t, q)) => (t + q * origin, q),
+                None => (origin, DQuat::IDENTITY),
+            });
+            loader
+                .origins
+                .lock()
+                .expect("origins poisoned")
+                .insert(info.index, origin);
+            info.bounds = info.bounds.map(|(min, max)| {
+                (
+                    [0, 1, 2].map(|i| min[i] - origin[i]),
+                    [0, 1, 2].map(|i| max[i] - origin[i]),
+                )
+            });
+        }
+
+        // Merged points are in file-level coordinates relative to the center, so the pose of
+        // the scan is applied here, and `info.world` becomes the pose of the merged points.
+        let scan_world = info.world;
+        let (points_from_local, points_path) = if options.merge_scans {
+            let (t, q) = pose.unwrap_or((DVec3::ZERO, DQuat::IDENTITY));
+            info.world = (loader.center != DVec3::ZERO).then_some((loader.center, DQuat::IDENTITY));
+            (
+                (q, t - loader.center),
+                format!("{}/merged", loader.entity_path_prefix),
+            )
+        } else if info.preview {
+            (
+                (DQuat::IDENTITY, -origin),
+                format!("{}/preview", info.entity_path),
+            )
+        } else {
+            ((DQuat::IDENTITY, -origin), info.entity_path.clone())
+        };
+
+        let bytes_per_point = bytes_per_point(
+            info.log_intensity,
+            info.normals,
+            info.classified,
+            options.log_row_col && info.structured,
+            info.extra_fields.len(),
+        );
+        let chunk_size = match (options.chunk_size, options.chunk_bytes) {
+            (Some(chunk_size), _) => chunk_size,
+            (None, Some(chunk_bytes)) => (chunk_bytes / bytes_per_point).max(1),
+            (None, None) => DEFAULT_CHUNK_SIZE,
+        };
+        // The preview is flushed while the scan is decoded, instead of in one chunk at its end.
+        let chunk_size = if info.preview {
+            chunk_size.min(PREVIEW_CHUNK_SIZE)
+        } else {
+            chunk_size
+        };
+        // The budget only covers the buffers of the writers, so chunks that wait to be logged
+        // would not count against it.
+        let logger = (loader.export.is_none() && loader.budget.is_none())
+            .then(|| ChunkLogger::spawn(rec, options, loader.flusher.clone()).map(Arc::new))
+            .transpose()?;
+        let mut sink = PointSink {
+            voxel_grid: options.voxel_size.map(VoxelGrid::new),
+            writer: ChunkWriter::new(
+                loader,
+                &info,
+                points_path.clone(),
+                chunk_size,
+                logger.clone(),
+            ),
+        };
+
+        // Coarser levels of detail are octree levels over the scan bounds, each halving
+        // the cell size. The finest level receives the full-resolution points.
+        let mut lods = Vec::new();
+        if let Some(lod_levels) = options.lod_levels.filter(|_| !info.preview) {
+            if let Some((min, max)) = info.bounds {
+                let extent = (0..3).map(|i| max[i] - min[i]).fold(f64::EPSILON, f64::max);
+                for level in 0..lod_levels - 1 {
+                    let cell_size = extent / 2_f64.powi((LOD_BASE_DEPTH + level) as i32);
+                    let mut writer = ChunkWriter::new(
+                        loader,
+                        &info,
+                        format!("{points_path}/lod_{level}"),
+                        chunk_size,
+                        logger.clone(),
+                    );
+                    // Voxels do not keep the extra fields, which the finest level has.
+                    writer.extra_fields.clear();
+                    writer.extras.clear();
+                    lods.push(PointSink {
+                        voxel_grid: Some(VoxelGrid::new(cell_size)),
+                        writer,
+                    });
+                }
+                sink.writer.entity_path = format!("{points_path}/lod_{}", lod_levels - 1);
+            }
+        }
+
+        // Seeding per scan keeps the selection independent of the order the workers run in.
+        let rng = match options.seed {
+            Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(info.index as u64)),
+            None => StdRng::from_entropy(),
+        };
+
+        let colormap = options.colormap.unwrap_or(match info.color_by {
+            ColorBy::Height | ColorBy::Range | ColorBy::Scan | ColorBy::Return => Colormap::Turbo,
+            _ => Colormap::Grayscale,
+        });
+
+        // The time cursor is thread-local, so every worker has to set it on its own.
+        if let Some(timepoint) = &options.timepoint {
+            rec.set_timepoint(timepoint.clone());
+        }
+        if options.timeline == Some(TimeSource::Acquisition) {
+            set_acquisition_time(rec, info.acquisition_time);
+        }
+
+        if let Some(transform) = crate::root_transform(options, loader.center, scan_world) {
+            rec.log_with_static(info.entity_path.as_str(), options.static_, &transform)?;
+        }
+
+        // The bounds are logged before the points, to show the coverage of the scan right away.
+        if let Some((min, max)) = info.bounds.filter(|_| options.bounds && !info.preview) {
+            rec.log_with_static(
+                format!("{}/bounds", info.entity_path),
+                options.static_,
+                &rerun::Boxes3D::from_mins_and_sizes(
+                    [min.map(|v| v as f32)],
+                    [[0, 1, 2].map(|i| (max[i] - min[i]) as f32)],
+                )
+                .with_colors([rerun::Color::from_rgb(255, 200, 0)])
+                .with_labels([format!("Scan {}", info.index)]),
+            )?;
+        }
+
+        let (rotation, translation) = points_from_local;
+        let logged_from_file = DAffine3::from_rotation_translation(rotation, translation)
+            * DAffine3::from_scale(DVec3::splat(options.scale.unwrap_or(1.0)));
+
+        let quota = options
+            .max_points
+            .filter(|_| !info.preview)
+            .map(|max_points| {
+                let share = max_points as u128 * info.records as u128 / loader.total.max(1) as u128;
+                (share as u64, info.records)
+            });
+
+        let stats = (options.stats && !info.preview).then(ScanStats::default);
+
+        Ok(Self {
+            loader,
+            range_image: (options.depth_image && info.structured && !info.preview)
+                .then(RangeImage::default),
+            panorama: (options.reconstruct_panorama && info.structured && !info.preview)
+                .then(Panorama::default),
+            ground_samples: ((options.segment_ground || options.dem.is_some()) && !info.preview)
+                .then(GroundSamples::default),
+            ground: None,
+            samples: ((options.detect_planes.is_some() || options.measure) && !info.preview)
+                .then(PointSamples::default),
+            clustered: (options.cluster.is_some() && !info.preview).then(Vec::new),
+            coverage: (loader.coverage.is_some() && !info.preview).then(ScanCoverage::default),
+            dem: options.dem.filter(|_| !info.preview).map(Dem::new),
+            section_points: (options.section.is_some() && !info.preview).then(Vec::new),
+            #[cfg(feature = "mesh")]
+            mesh: (options.mesh && info.structured && !info.preview).then(RangeMesh::default),
+            info,
+            origin,
+            points_from_local,
+            logged_from_file,
+            pending: Vec::with_capacity(TRANSFORM_BATCH),
+            pending_positions: Vec::with_capacity(TRANSFORM_BATCH),
+            points_path,
+            colormap,
+            chunk_size,
+            sink,
+            part_sinks: BTreeMap::new(),
+            lods,
+            invalid_sink: None,
+            logger,
+            issues: Issues::default(),
+            rng,
+            point_idx: 0,
+            unreported: 0,
+            records: 0,
+            logged_bounds: None,
+            stats,
+            quota,
+            started: Instant::now(),
+        })
+    }
+
+    fn count_record(&mut self) {
+        // The records of the preview are decoded again by the full pass, which reports them.
+        if self.info.preview {
+            return;
+        }
+        self.records += 1;
+        self.unreported += 1;
+        if self.unreported == PROGRESS_BATCH {
+            self.loader.add_records(self.unreported);
+            self.unreported = 0;
+        }
+    }
+
+    /// Counts a record that could not be decoded.
+    pub fn skip_error(&mut self, error: impl std::fmt::Display) {
+        self.count_record();
+        self.issues.decode_errors += 1;
+        if self.issues.first_error.is_none() {
+            self.issues.first_error = Some(format!("{}: {error}", self.info.entity_path));
+        }
+    }
+
+    /// Handles a record without a valid position as configured by `--invalid-points`. The
+    /// position of `p` is its direction if it has one, or the origin otherwise.
+    pub fn push_invalid(&mut self, p: SourcePoint) -> Result<()> {
+        self.count_record();
+        if self.info.preview || !self.in_window(&p) {
+            return Ok(());
+        }
+        self.issues.invalid_points += 1;
+        match self.loader.options.invalid_points {
+            InvalidPoints::Skip => Ok(()),
+            InvalidPoints::Zero => self.queue(
+                SourcePoint {
+                    position: DVec3::ZERO,
+                    ..p
+                },
+                false,
+            ),
+            InvalidPoints::LogSeparately => self.queue(p, true),
+        }
+    }
+
+    pub fn push(&mut self, p: SourcePoint) -> Result<()> {
+        self.count_record();
+        if !self.in_window(&p) {
+            return Ok(());
+        }
+        self.queue(p, false)
+    }
+
+    /// Whether the ground model of `--segment-ground` and `--dem` needs the records of the
+    /// scan before they are pushed.
+    pub fn samples_ground(&self) -> bool {
+        self.ground_samples.is_some()
+    }
+
+    /// Adds the position of a record in the units of the file to the ground model.
+    pub fn sample_ground(&mut self, position: DVec3) {
+        let logged = self.logged_from_file.transform_point3(position).as_vec3();
+        let file_position = self.file_position(Vec3D::from(logged.to_array()));
+        let up_axis = self.loader.options.up_axis;
+        if let Some(samples) = &mut self.ground_samples {
+            let horizontal = up_axis.horizontal().map(|i| file_position[i]);
+            samples.insert(horizontal, file_position[up_axis.index()]);
+        }
+    }
+
+    /// Builds the ground model from the sampled records, which splits the pushed points into
+    /// `ground` and `non_ground` entities with `--segment-ground`.
+    pub fn build_ground(&mut self) -> Result<()> {
+        self.ground = match self.ground_samples.take() {
+            Some(samples) => samples.into_model()?,
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// Whether the record is in the `--rows` and `--cols` of a structured scan.
+    fn in_window(&self, p: &SourcePoint) -> bool {
+        let options = self.loader.options;
+        let rows = options.rows.as_ref();
+        let columns = options.columns.as_ref();
+        !self.info.structured
+            || (rows.is_none_or(|rows| rows.contains(&p.row))
+                && columns.is_none_or(|columns| columns.contains(&p.column)))
+    }
+
+    fn queue(&mut self, p: SourcePoint, invalid: bool) -> Result<()> {
+        self.pending.push((p, invalid));
+        if self.pending.len() == TRANSFORM_BATCH {
+            self.push_pending()?;
+        }
+        Ok(())
+    }
+
+    /// Transforms the positions of the queued records at once, and passes them on in order.
+    fn push_pending(&mut self) -> Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        let mut positions = std::mem::take(&mut self.pending_positions);
+        positions.clear();
+        transform_points(
+            &self.logged_from_file,
+            pending.iter().map(|(p, _)| p.position),
+            &mut positions,
+        );
+        for ((p, invalid), &position) in pending.iter().zip(&positions) {
+            match invalid {
+                true => self.push_separately(position, p)?,
+                false => self.push_point(position, p)?,
+            }
+        }
+        self.pending = pending;
+        self.pending.clear();
+        self.pending_positions = positions;
+        Ok(())
+    }
+
+    /// Logs an invalid point with `InvalidPoints::LogSeparately`.
+    fn push_separately(&mut self, position: Vec3D, p: &SourcePoint) -> Result<()> {
+        let point = self.logged_point(position, p);
+        let (loader, info, chunk_size) = (self.loader, &self.info, self.chunk_size);
+        let (entity_path, logger) = (format!("{}/invalid", self.points_path), &self.logger);
+        self.invalid_sink
+            .get_or_insert_with(|| PointSink {
+                voxel_grid: None,
+                writer: ChunkWriter::new(loader, info, entity_path, chunk_size, logger.clone()),
+            })
+            .push(point, &p.extras)
+    }
+
+    /// Colors a point, and rotates its normal like the logged positions.
+    fn logged_point(&self, position: Vec3D, p: &SourcePoint) -> LoggedPoint {
+        LoggedPoint {
+            position,
+            color: match &self.info.shading {
+                Some(shading) => shading.apply(self.color(position, p), p.row, p.column),
+                None => self.color(position, p),
+            },
+            intensity: p.intensity,
+            normal: self.to_logged_normal(p.normal),
+            time: p.time,
+            class: p.classification,
+            row_column: Some((p.row, p.column)),
+        }
+    }
+
+    /// Rotates a local normal like the logged positions.
+    fn to_logged_normal(&self, normal: Option<Vec3D>) -> Option<Vec3D> {
+        let (rotation, _) = self.points_from_local;
+        if rotation == DQuat::IDENTITY {
+            return normal;
+        }
+        normal.map(|n| Vec3D::from((rotation.as_quat() * glam::Vec3::from(n)).to_array()))
+    }
+
+    /// Returns the position of the scanner in the frame of the scan entity, for its marker.
+    pub fn scanner_position(&self) -> Vec3D {
+        Vec3D::from((-self.origin).as_vec3().to_array())
+    }
+
+    /// Logs the marker of the scanner position, as configured with `--show-scan-origins`.
+    /// The scan pose is logged as a transform, so the marker is in the frame of the scan.
+    pub fn log_scan_origin(&self) -> Result<()> {
+        let &Loader { rec, options, .. } = self.loader;
+        let (entity_path, index) = (&self.info.entity_path, self.info.index);
+        match options.show_scan_origins {
+            ScanOrigins::Point => rec.log_with_static(
+                format!("{entity_path}/point"),
+                options.static_,
+                &Points3D::new([self.scanner_position()])
+                    .with_colors([rerun::Color::from_rgb(255, 0, 0)])
+                    .with_radii([0.15_f32])
+                    .with_labels([format!("Scan {index}")]),
+            )?,
+            ScanOrigins::Axes => rec.log_with_static(
+                format!("{entity_path}/origin"),
+                options.static_,
+                &rerun::Arrows3D::from_vectors(
+                    [glam::Vec3::X, glam::Vec3::Y, glam::Vec3::Z].map(|v| v * ORIGIN_AXIS_LENGTH),
+                )
+                .with_origins([self.scanner_position(); 3])
+                .with_colors([
+                    rerun::Color::from_rgb(255, 0, 0),
+                    rerun::Color::from_rgb(0, 255, 0),
+                    rerun::Color::from_rgb(0, 0, 255),
+                ])
+                .with_labels([format!("Scan {index}")]),
+            )?,
+            ScanOrigins::None => {}
+        }
+        Ok(())
+    }
+
+    /// Returns a logged position in the local frame of the scan.
+    fn local(&self, position: Vec3D) -> DVec3 {
+        let (rotation, translation) = self.points_from_local;
+        rotation.inverse() * (glam::Vec3::from(position).as_dvec3() - translation)
+    }
+
+    fn push_point(&mut self, position: Vec3D, p: &SourcePoint) -> Result<()> {
+        let options = self.loader.options;
+        if !self.in_region(position) || !is_selected_return(options.returns, p.returns) {
+            return Ok(());
+        }
+
+        // The range image always uses the full resolution, since it is indexed by row and column.
+        let local = self.local(position);
+        let range = local.length() as f32;
+        if let Some(range_image) = &mut self.range_image {
+            range_image.insert(p.row, p.column, range);
+        }
+        if let Some((panorama, color)) = self.panorama.as_mut().zip(p.color) {
+            panorama.insert(p.row, p.column, color);
+        }
+        // The coverage and the statistics cover all points in the region, regardless of the
+        // subsampling.
+        if self.coverage.is_some() {
+            let file_position = self.file_position(position);
+            if let Some(coverage) = &mut self.coverage {
+                coverage.insert(file_position);
+            }
+        }
+        if self.dem.is_some() {
+            let file_position = self.file_position(position);
+            let horizontal = options.up_axis.horizontal().map(|i| file_position[i]);
+            let height = file_position[options.up_axis.index()];
+            if let Some((dem, ground)) = self.dem.as_mut().zip(self.ground.as_ref()) {
+                if ground.is_ground(horizontal, height) {
+                    dem.insert(horizontal, height);
+                }
+            }
+        }
+        if let Some(stats) = &mut self.stats {
+            let intensity = p
+                .intensity
+                .zip(self.info.intensity_range)
+                .map(|(intensity, range)| normalize_scalar(intensity, range));
+            stats.insert(local.length(), intensity);
+        }
+        if options.no_points {
+            return Ok(());
+        }
+
+        self.point_idx += 1;
+        if self.info.preview && !(self.point_idx - 1).is_multiple_of(PREVIEW_EVERY_NTH) {
+            return Ok(());
+        }
+        if let Some(n) = options.every_nth {
+            if !(self.point_idx - 1).is_multiple_of(n) {
+                return Ok(());
+            }
+        }
+        if let Some(ratio) = options.subsample {
+            if !self.rng.gen_bool(ratio) {
+                return Ok(());
+            }
+        }
+        if let Some((needed, remaining)) = &mut self.quota {
+            // Selection sampling keeps a random subset of exactly the quota if all records
+            // get here, and less if some are cropped or subsampled.
+            let keep = *remaining > 0 && self.rng.gen_range(0..*remaining) < *needed;
+            *remaining = remaining.saturating_sub(1);
+            if !keep {
+                return Ok(());
+            }
+            *needed -= 1;
+        }
+
+        self.logged_bounds = Some(match self.logged_bounds {
+            Some((min, max)) => (min.min(local), max.max(local)),
+            None => (local, local),
+        });
+
+        if let Some(samples) = &mut self.samples {
+            samples.insert(glam::Vec3::from(position).as_dvec3(), &mut self.rng);
+        }
+
+        let point = self.logged_point(position, p);
+        if self.info.preview {
+            return self.sink.push(point, &p.extras);
+        }
+        if let Some(section) = &options.section {
+            let projected = section.project(self.file_position(position) - self.loader.center);
+            if let Some(section_points) = &mut self.section_points {
+                section_points.push((projected, point.color));
+            }
+        }
+        #[cfg(feature = "mesh")]
+        if let Some(mesh) = &mut self.mesh {
+            if let Some((row, column)) = point.row_column {
+                mesh.insert(row, column, glam::Vec3::from(position), range, point.color);
+            }
+            return Ok(());
+        }
+        for lod in &mut self.lods {
+            lod.push(point, &p.extras)?;
+        }
+
+        let file_position = self.file_position(position);
+        let part = Part {
+            tile: options.tile_size.map(|size| {
+                let [x, y] = options.up_axis.horizontal().map(|i| file_position[i]);
+                ((x / size).floor() as i64, (y / size).floor() as i64)
+            }),
+            slice: options.slice_z.map(|thickness| {
+                (file_position[options.up_axis.index()] / thickness).floor() as i64
+            }),
+            class: p.classification.filter(|_| options.split_by_class),
+            ground: self
+                .ground
+                .as_ref()
+                .filter(|_| options.segment_ground)
+                .map(|ground| {
+                    let horizontal = options.up_axis.horizontal().map(|i| file_position[i]);
+                    ground.is_ground(horizontal, file_position[options.up_axis.index()])
+                }),
+            cluster: None,
+        };
+        if let Some(clustered) = &mut self.clustered {
+            clustered.push((point, p.extras, part));
+            return Ok(());
+        }
+        self.push_part(part, point, &p.extras)
+    }
+
+    /// Pushes a point to the sink of its part.
+    fn push_part(&mut self, part: Part, point: LoggedPoint, extras: &ExtraScalars) -> Result<()> {
+        match part {
+            Part {
+                tile: None,
+                slice: None,
+                class: None,
+                ground: None,
+                cluster: None,
+            } => self.sink.push(point, extras),
+            part => {
+                let (loader, info, chunk_size) = (self.loader, &self.info, self.chunk_size);
+                let (points_path, logger) = (&self.sink.writer.entity_path, &self.logger);
+                self.part_sinks
+                    .entry(part)
+                    .or_insert_with(|| PointSink {
+                        voxel_grid: loader.options.voxel_size.map(VoxelGrid::new),
+                        writer: ChunkWriter::new(
+                            loader,
+                            info,
+                            part.entity_path(points_path),
+                            chunk_size,
+                            logger.clone(),
+                        ),
+                    })
+                    .push(point, extras)
+            }
+        }
+    }
+
+    /// Clusters the buffered points of `--cluster`, pushes them to the sinks of their clusters
+    /// in their colors, and logs the number of points of every cluster.
+    fn push_clusters(&mut self) -> Result<()> {
+        let (Some(clustered), Some(clustering)) =
+            (self.clustered.take(), self.loader.options.cluster)
+        else {
+            return Ok(());
+        };
+        let positions: Vec<DVec3> = clustered
+            .iter()
+            .map(|(point, _, _)| glam::Vec3::from(point.position).as_dvec3())
+            .collect();
+        let ids = dbscan(&positions, clustering.eps, clustering.min_points);
+        if self.loader.options.measure {
+            self.log_cluster_boxes(&positions, &ids)?;
+        }
+        let mut sizes = BTreeMap::new();
+        for ((mut point, extras, mut part), id) in clustered.into_iter().zip(ids) {
+            if let ClusterId::Cluster(cluster) = id {
+                point.color = scan_color(cluster);
+            }
+            *sizes.entry(id).or_insert(0u64) += 1;
+            part.cluster = Some(id);
+            self.push_part(part, point, &extras)?;
+        }
+
+        let summary = sizes
+            .into_iter()
+            .map(|(id, points)| match id {
+                ClusterId::Cluster(cluster) => format!("- **cluster_{cluster}:** {points} points"),
+                ClusterId::Noise => format!("- **noise:** {points} points"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let &Loader { rec, options, .. } = self.loader;
+        rec.log_with_static(
+            format!("{}/clusters", self.info.entity_path),
+            options.static_,
+            &rerun::TextDocument::from_markdown(summary),
+        )?;
+        Ok(())
+    }
+
+    /// Logs the oriented bounding boxes of the clusters of `--measure`, without the noise.
+    fn log_cluster_boxes(&self, positions: &[DVec3], ids: &[ClusterId]) -> Result<()> {
+        let mut clusters: BTreeMap<usize, Vec<DVec3>> = BTreeMap::new();
+        for (position, id) in positions.iter().zip(ids) {
+            if let ClusterId::Cluster(cluster) = id {
+                clusters.entry(*cluster).or_default().push(*position);
+            }
+        }
+        let measured: Vec<(String, OrientedBox)> = clusters
+            .into_iter()
+            .filter_map(|(cluster, points)| {
+                oriented_box(&points).map(|measured| (format!("cluster_{cluster}"), measured))
+            })
+            .collect();
+        if measured.is_empty() {
+            return Ok(());
+        }
+        let &Loader { rec, options, .. } = self.loader;
+        rec.log_with_static(
+            format!("{}/measurements", self.info.entity_path),
+            options.static_,
+            &crate::measure::boxes(&measured),
+        )?;
+        Ok(())
+    }
+
+    /// Returns a logged position in file-level coordinates for posed scans.
+    fn file_position(&self, position: Vec3D) -> DVec3 {
+        let local = glam::Vec3::from(position).as_dvec3();
+        self.info.world.map_or(local, |(t, q)| q * local + t)
+    }
+
+    /// Colors a point by the configured attribute.
+    fn color(&self, position: Vec3D, p: &SourcePoint) -> rerun::Color {
+        let &Loader {
+            options,
+            color_range,
+            ..
+        } = self.loader;
+        let scalar = match (
+            self.info.color_by,
+            color_range,
+            p.intensity,
+            self.info.intensity_range,
+        ) {
+            (ColorBy::Height, Some(range), _, _) => {
+                let height = self.file_position(position)[options.up_axis.index()];
+                Some(normalize_scalar(height as f32, range))
+            }
+            (ColorBy::Range, Some(range), _, _) => Some(normalize_scalar(
+                self.local(position).length() as f32,
+                range,
+            )),
+            (_, _, Some(intensity), Some(range))
+                if self.info.color_by == ColorBy::Intensity || p.color.is_none() =>
+            {
+                Some(normalize_scalar(intensity, range))
+            }
+            _ => None,
+        };
+        if let (ColorBy::Return, Some((index, _))) = (self.info.color_by, p.returns) {
+            return self.colormap.label_color(index);
+        }
+        match (self.info.color_by, p.classification, scalar, p.color) {
+            (ColorBy::Scan, _, _, _) if options.scan_palette => scan_color(self.info.index),
+            (ColorBy::Scan, _, _, _) => self.colormap.label_color(self.info.index as u32),
+            (ColorBy::Classification, Some(class), _, _) => {
+                self.loader.classes.color(class, options.colormap)
+            }
+            (_, _, Some(scalar), _) => self.colormap.color(scalar),
+            (_, _, None, Some(color)) => color,
+            _ if options.scan_palette => scan_color(self.info.index),
+            _ => rerun::Color::from_rgb(255, 255, 255),
+        }
+    }
+
+    /// Whether a point passes the crop box and the range limits.
+    fn in_region(&self, position: Vec3D) -> bool {
+        let options = self.loader.options;
+        if let Some(section) = &options.section {
+            if !section.contains(self.file_position(position)) {
+                return false;
+            }
+        }
+        if options.crop_box.is_none() && options.min_range.is_none() && options.max_range.is_none()
+        {
+            return true;
+        }
+
+        let p = match (options.crop_frame, self.info.world) {
+            (CropFrame::World, Some((t, q))) => q * glam::Vec3::from(position).as_dvec3() + t,
+            _ => self.local(position),
+        };
+        if let Some((min, max)) = options.crop_box {
+            if !(DVec3::from(min).cmple(p).all() && p.cmple(DVec3::from(max)).all()) {
+                return false;
+            }
+        }
+        let range = p.length();
+        options.min_range.is_none_or(|min| range >= min)
+            && options.max_range.is_none_or(|max| range <= max)
+    }
+
+    /// Logs all remaining points and returns the number of logged points.
+    pub fn finish(mut self) -> Result<u64> {
+        self.push_pending()?;
+        self.push_clusters()?;
+        let &Loader { rec, options, .. } = self.loader;
+        if self.info.preview {
+            let points = self.sink.finish()?;
+            if let Some(logger) = self.logger.and_then(Arc::into_inner) {
+                logger.finish()?;
+            }
+            return Ok(points);
+        }
+        self.loader.add_records(self.unreported);
+        let (invalid_points, decode_errors) =
+            (self.issues.invalid_points, self.issues.decode_errors);
+        self.loader
+            .issues
+            .lock()
+            .expect("issues poisoned")
+            .merge(self.issues);
+
+        let mut points = self.sink.finish()?;
+        for part_sink in self.part_sinks.into_values() {
+            points += part_sink.finish()?;
+        }
+        if let Some(invalid_sink) = self.invalid_sink {
+            points += invalid_sink.finish()?;
+        }
+
+        for lod in self.lods {
+            lod.finish()?;
+        }
+        // All writers are gone, so this waits for the last chunks to be logged.
+        if let Some(logger) = self.logger.and_then(Arc::into_inner) {
+            logger.finish()?;
+        }
+
+        if let Some(stats) = &self.stats {
+            stats.log(rec, options.static_, &self.info.entity_path)?;
+        }
+        if let Some((scan, coverage)) = self.coverage.zip(self.loader.coverage.as_ref()) {
+            coverage.lock().expect("coverage poisoned").add(scan);
+        }
+        if let Some((scan, dem)) = self.dem.zip(self.loader.dem.as_ref()) {
+            dem.lock().expect("DEM poisoned").add(scan);
+        }
+        if let Some(depth_image) = self
+            .range_image
+            .map(RangeImage::into_depth_image)
+            .transpose()?
+            .flatten()
+        {
+            rec.log_with_static(
+                format!("{}/depth_image", self.info.entity_path),
+                options.static_,
+                &depth_image,
+            )?;
+        }
+        if let Some(panorama) = self
+            .panorama
+            .map(Panorama::into_image)
+            .transpose()?
+            .flatten()
+        {
+            rec.log_with_static(
+                format!("{}/panorama", self.info.entity_path),
+                options.static_,
+                &panorama,
+            )?;
+        }
+        #[cfg(feature = "mesh")]
+        if let Some((mesh, vertices)) = self
+            .mesh
+            .map(|mesh| mesh.into_mesh(self.points_from_local.1.as_vec3()))
+            .transpose()?
+            .flatten()
+        {
+            rec.log_with_static(
+                format!("{}/mesh", self.info.entity_path),
+                options.static_,
+                &mesh,
+            )?;
+            points += vertices;
+        }
+        if let Some(section_points) = self.section_points.filter(|points| !points.is_empty()) {
+            let (positions, colors): (Vec<[f32; 2]>, Vec<rerun::Color>) =
+                section_points.into_iter().unzip();
+            rec.log_with_static(
+                format!(
+                    "section_2d/{}",
+                    self.info.entity_path.trim_start_matches('/')
+                ),
+                options.static_,
+                &rerun::Points2D::new(positions).with_colors(colors),
+            )?;
+        }
+        if let Some(samples) = &self.samples {
+            if let Some(n) = options.detect_planes {
+                let planes = detect_planes(samples, n, &mut self.rng);
+                if !planes.is_empty() {
+                    rec.log_with_static(
+                        format!("{}/planes", self.info.entity_path),
+                        options.static_,
+                        &crate::planes::boxes(&planes),
+                    )?;
+                }
+            }
+            // Clusters are measured exactly as they are pushed instead.
+            if options.measure && options.cluster.is_none() {
+                if let Some(measured) = oriented_box(samples.points()) {
+                    rec.log_with_static(
+                        format!("{}/measurements", self.info.entity_path),
+                        options.static_,
+                        &crate::measure::boxes(&[(format!("Scan {}", self.info.index), measured)]),
+                    )?;
+                }
+            }
+        }
+
+        // The full resolution points replace the preview.
+        if options.preview_first {
+            rec.log_with_static(
+                format!("{}/preview", self.info.entity_path),
+                options.static_,
+                &rerun::Clear::recursive(),
+            )?;
+        }
+
+        self.loader
+            .reports
+            .lock()
+            .expect("reports poisoned")
+            .push(ScanReport {
+                file: self.loader.path.to_owned(),
+                index: self.info.index,
+                entity_path: self.info.entity_path,
+                records: self.records,
+                points,
+                skipped: self.records.saturating_sub(points),
+                invalid_points,
+                decode_errors,
+                bounds: self.logged_bounds.map(|(min, max)| Bounds {
+                    min: min.to_array(),
+                    max: max.to_array(),
+                }),
+                duration: self.started.elapsed().as_secs_f64(),
+            });
+
+        // The points of a cancelled scan may be incomplete, so it is loaded again on resume.
+        if let Some(checkpoint) = &self.loader.checkpoint {
+            if !crate::is_cancelled(options) {
+                checkpoint.mark_logged(self.info.index)?;
+            }
+        }
+        Ok(points)
+    }
+}
+
+/// Every how many points of a scan are logged by the preview of `--preview-first`.
+const PREVIEW_EVERY_NTH: usize = 100;
+/// Most points of a chunk of the preview, i.e. of a million points of the scan.
+const PREVIEW_CHUNK_SIZE: usize = 10_000;
+
+/// Number of records a scan pipeline transforms at once.
+const TRANSFORM_BATCH: usize = 4096;
+
+/// Converts local positions in the units of the file to logged positions, rounded to 32 bits.
+///
+/// The scale, rotation and translation are combined into one affine transform up front, so
+/// each point takes a single 64-bit matrix multiplication and addition instead of scaling it
+/// and rotating it by a quaternion. The points are transformed one by one in 64 bits, since
+/// the translation can be large, e.g. in UTM coordinates, and only the result is rounded.
+pub fn transform_points(
+    logged_from_file: &DAffine3,
+    positions: impl IntoIterator<Item = DVec3>,
+    logged: &mut Vec<Vec3D>,
+) {
+    logged.extend(positions.into_iter().map(|position| {
+        Vec3D::from(
+            logged_from_file
+                .transform_point3(position)
+                .as_vec3()
+                .to_array(),
+        )
+    }));
+}
+
+/// Logs a warning as a text log entry of `{prefix}/warnings`. A warning that cannot be logged
+/// is only printed, since it is about another error already.
+pub(crate) fn warn(rec: &RecordingStream, options: &LoadOptions, prefix: &str, message: &str) {
+    let _ = rec.log_with_static(
+        format!("{prefix}/warnings"),
+        options.static_,
+        &rerun::TextLog::new(message).with_level(rerun::TextLogLevel::WARN),
+    );
+}
+
+/// Returns the last segment of the entity path of a chunk, `chunk_{index}` unless the
+/// `--entity-template` has a `{chunk}` segment.
+fn chunk_name(options: &LoadOptions, index: usize) -> String {
+    options
+        .entity_template
+        .as_ref()
+        .and_then(|template| template.chunk_name(index))
+        .unwrap_or_else(|| format!("chunk_{index}"))
+}
+
+/// Name of the timeline of `TimeSource::Acquisition`.
+pub(crate) const ACQUISITION_TIMELINE: &str = "acquisition";
+
+/// Sets the acquisition time of the current thread, or clears the one of a previous scan if
+/// `time` is `None`.
+pub(crate) fn set_acquisition_time(rec: &RecordingStream, time: Option<f64>) {
+    match time {
+        Some(seconds) => rec.set_time_seconds(ACQUISITION_TIMELINE, seconds),
+        None => rec.disable_timeline(ACQUISITION_TIMELINE),
+    }
+}
+
+pub(crate) fn normalize_scalar(value: f32, (min, max): (f64, f64)) -> f32 {
+    if max > min {
+        ((value as f64 - min) / (max - min)).clamp(0.0, 1.0) as f32
+    } else {
+        0.0
+    }
+}
+
+/// Points of a chunk and their other components, ready to be logged.
+struct Chunk {
+    entity_path: String,
+    points: Vec<Vec3D>,
+    colors: Vec<rerun::Color>,
+    scalars: Vec<(String, Vec<Option<f32>>)>,
+    normals: Option<(NormalsMode, Vec<Option<Vec3D>>)>,
+    /// Classes of the points, empty if the scan is not classified.
+    class_ids: Vec<u16>,
+    /// Integer components with their names, i.e. the rows and columns of `--log-row-col`.
+    indices: Vec<(String, Vec<Option<i64>>)>,
+    /// Precision the scalars are logged with.
+    precision: Precision,
+}
+
+/// Number of levels per channel of `--quantize-colors`.
+const COLOR_LEVELS: u16 = 32;
+
+/// Rounds the channels of a color to the nearest of `COLOR_LEVELS` levels from 0 to 255.
+fn quantize_color(color: rerun::Color) -> rerun::Color {
+    let quantize = |channel: u8| {
+        let level = (channel as u16 * (COLOR_LEVELS - 1) + 127) / 255;
+        (level * 255 / (COLOR_LEVELS - 1)) as u8
+    };
+    let [r, g, b, a] = color.to_array();
+    rerun::Color::from_unmultiplied_rgba(quantize(r), quantize(g), quantize(b), a)
+}
+
+fn log_chunk(
+    rec: &RecordingStream,
+    static_: bool,
+    point_radius: Option<PointRadius>,
+    flusher: Option<&Flusher>,
+    chunk: Chunk,
+) -> Result<()> {
+    let Chunk {
+        entity_path,
+        points,
+        colors,
+        scalars,
+        normals,
+        class_ids,
+        indices,
+        precision,
+    } = chunk;
+    // Arrows are logged for a subset of the points only, since they quickly clutter the view.
+    if let Some((NormalsMode::Arrows, normals)) = &normals {
+        let (origins, vectors): (Vec<_>, Vec<_>) = points
+            .iter()
+            .zip(normals)
+            .step_by(NORMAL_ARROW_STRIDE)
+            .filter_map(|(origin, normal)| {
+                let [x, y, z] = normal.as_ref()?.0.map(|v| v * NORMAL_ARROW_LENGTH);
+                Some((*origin, Vec3D::new(x, y, z)))
+            })
+            .unzip();
+        rec.log_with_static(
+            format!("{entity_path}/normals"),
+            static_,
+            &rerun::Arrows3D::from_vectors(vectors)
+                .with_origins(origins)
+                .with_colors([rerun::Color::from_rgb(0, 160, 255)]),
+        )?;
+    }
+
+    let mut points = Points3D::new(points).with_colors(colors);
+    // With the class ids, the annotation context names the classes of selected points.
+    if !class_ids.is_empty() {
+        points = points.with_class_ids(class_ids);
+    }
+    // A single radius applies to all points of the chunk.
+    if let Some(radius) = point_radius {
+        points = points.with_radii([rerun::Radius::from(radius)]);
+    }
+    let mut components: Vec<&dyn AsComponents> = vec![&points];
+
+    let scalars = scalars
+        .into_iter()
+        .map(|(name, values)| {
+            let mut values: ArrayRef = Arc::new(Float32Array::from(values));
+            if precision == Precision::F16 {
+                values = cast(&values, &DataType::Float16)?;
+            }
+            Ok(SerializedComponentBatch::new(
+                values,
+                ComponentDescriptor::new(name),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for scalars in &scalars {
+        components.push(scalars);
+    }
+    let indices: Vec<_> = indices
+        .into_iter()
+        .map(|(name, values)| {
+            SerializedComponentBatch::new(
+                Arc::new(Int64Array::from(values)),
+                ComponentDescriptor::new(name),
+            )
+        })
+        .collect();
+    for indices in &indices {
+        components.push(indices);
+    }
+
+    let normals = match normals {
+        Some((NormalsMode::Log, normals)) => Some(SerializedComponentBatch::new(
+            rerun::components::Vector3D::to_arrow_opt(
+                normals
+                    .into_iter()
+                    .map(|normal| normal.map(rerun::components::Vector3D)),
+            )?,
+            ComponentDescriptor::new("rerun_e57.Normal"),
+        )),
+        _ => None,
+    };
+    if let Some(normals) = &normals {
+        components.push(normals);
+    }
+
+    rec.log_with_static(entity_path, static_, &components)?;
+    if let Some(flusher) = flusher {
+        flusher.chunk_logged(rec);
+    }
+
+    Ok(())
+}
+
+/// Flushes the recording every `--flush-every` chunks or seconds, so that the logged chunks
+/// are sent right away instead of when the batcher of the recording is full or due.
+pub(crate) struct Flusher {
+    every: FlushEvery,
+    chunks: AtomicU64,
+    last: Mutex<Instant>,
+}
+
+impl Flusher {
+    fn new(every: FlushEvery) -> Self {
+        Self {
+            every,
+            chunks: AtomicU64::new(0),
+            last: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn chunk_logged(&self, rec: &RecordingStream) {
+        let due = match self.every {
+            FlushEvery::Chunks(chunks) => {
+                (self.chunks.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(chunks)
+            }
+            FlushEvery::Interval(interval) => {
+                let mut last = self.last.lock().expect("flusher poisoned");
+                let due = last.elapsed() >= interval;
+                if due {
+                    *last = Instant::now();
+                }
+                due
+            }
+        };
+        // The flush only hands the batched rows to the sink, so it does not wait for them.
+        if due {
+            rec.flush_async();
+        }
+    }
+}
+
+/// Number of chunks that can wait for the logging thread of a scan before its worker blocks.
+const LOG_QUEUE: usize = 2;
+
+/// Logs the chunks of a scan on a thread of its own, so the serialization of a chunk overlaps
+/// with decoding the points of the next one.
+struct ChunkLogger {
+    sender: Option<SyncSender<(TimePoint, Chunk)>>,
+    thread: Mutex<Option<JoinHandle<Result<()>>>>,
+}
+
+impl ChunkLogger {
+    fn spawn(
+        rec: &RecordingStream,
+        options: &LoadOptions,
+        flusher: Option<Arc<Flusher>>,
+    ) -> Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel::<(TimePoint, Chunk)>(LOG_QUEUE);
+        let (rec, static_, point_radius) = (rec.clone(), options.static_, options.point_radius);
+        let thread = std::thread::Builder::new()
+            .name("chunk-logger".to_owned())
+            .spawn(move || {
+                for (timepoint, chunk) in receiver {
+                    // The time cursor is thread-local, so it is the one of the worker that
+                    // filled the chunk.
+                    rec.reset_time();
+                    rec.set_timepoint(timepoint);
+                    log_chunk(&rec, static_, point_radius, flusher.as_deref(), chunk)?;
+                }
+                Ok(())
+            })?;
+        Ok(Self {
+            sender: Some(sender),
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// Queues a chunk logged at `timepoint`, or returns the error the thread stopped with.
+    fn log(&self, timepoint: TimePoint, chunk: Chunk) -> Result<()> {
+        let sender = self.sender.as_ref().expect("sender exists until drop");
+        match sender.send((timepoint, chunk)) {
+            Ok(()) => Ok(()),
+            Err(_) => self
+                .join()
+                .and_then(|()| Err(anyhow::anyhow!("The chunk logging thread stopped"))),
+        }
+    }
+
+    /// Waits until all queued chunks are logged.
+    fn finish(mut self) -> Result<()> {
+        self.sender = None;
+        self.join()
+    }
+
+    fn join(&self) -> Result<()> {
+        let thread = self.thread.lock().expect("logger poisoned").take();
+        match thread.map(JoinHandle::join) {
+            None => Ok(()),
+            Some(Ok(result)) => result,
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl Drop for ChunkLogger {
+    /// Logs the queued chunks of a scan that failed, which are still part of the recording.
+    fn drop(&mut self) {
+        self.sender = None;
+        let _ = self.join();
+    }
+}
+
+/// State shared by all scan workers of a single load call.
+pub(crate) struct Loader<'a> {
+    pub rec: &'a RecordingStream,
+    pub path: &'a Path,
+    pub options: &'a LoadOptions,
+    pub entity_path_prefix: &'a str,
+    pub progress: Progress,
+    pub budget: Option<MemoryBudget>,
+    /// Number of records of all scans that are loaded.
+    pub total: u64,
+    pub color_range: Option<(f64, f64)>,
+    /// Offset of `--center` in meters, subtracted from the file-level coordinates.
+    pub center: DVec3,
+    pub export: Option<Exporter>,
+    /// Invalid points and decode errors of all finished scans.
+    pub issues: Mutex<Issues>,
+    /// Local origins in meters of the logged points of the scans whose points are not
+    /// relative to their scanner, by scan index.
+    pub origins: Mutex<HashMap<usize, DVec3>>,
+    /// Number of chunks logged to `{prefix}/merged` by all scans, which share its entities.
+    pub merged_chunks: AtomicUsize,
+    /// Reports of all finished scans.
+    pub reports: Mutex<Vec<ScanReport>>,
+    /// Names and colors of the classes.
+    pub classes: ClassMap,
+    /// Logs the throughput with `--log-perf`.
+    pub perf: Option<PerfLogger>,
+    /// Acquisition times, indices and positions in meters of the posed scans, for
+    /// `--show-trajectory`.
+    pub trajectory: Mutex<Vec<(Option<f64>, usize, DVec3)>>,
+    /// Scans logged to the recording so far, with `--resume`.
+    pub checkpoint: Option<Checkpoint>,
+    /// Flushes the recording with `--flush-every`.
+    pub flusher: Option<Arc<Flusher>>,
+    /// Stations of the scans by scan index, with `--group-stations`.
+    pub stations: Vec<Option<usize>>,
+    /// Entity paths of the scans by scan index from the `--entity-template`, all different.
+    pub template_paths: Vec<String>,
+    /// Voxels of all finished scans, with `--coverage-heatmap`.
+    pub coverage: Option<Mutex<Coverage>>,
+    /// Ground heights of all finished scans, with `--dem`.
+    pub dem: Option<Mutex<Dem>>,
+}
+
+impl<'a> Loader<'a> {
+    /// Creates the shared state for loading `total` records from the file at `path`.
+    pub fn new(
+        rec: &'a RecordingStream,
+        path: &'a Path,
+        options: &'a LoadOptions,
+        entity_path_prefix: &'a str,
+        total: u64,
+        color_range: Option<(f64, f64)>,
+        center: DVec3,
+    ) -> Result<Self> {
+        // All loaders convert the points to the right-handed Z-up frame of the E57 standard.
+        rec.log_with_static(
+            entity_path_prefix,
+            options.static_,
+            &rerun::ViewCoordinates::RIGHT_HAND_Z_UP(),
+        )?;
+        // The center is a transform of the prefix, so the scans keep their place in the
+        // recording while their points stay small.
+        if center != DVec3::ZERO {
+            rec.log_with_static(
+                entity_path_prefix,
+                options.static_,
+                &rerun::Transform3D::from_translation(
+                    (crate::axes_conversion(options) * center).as_vec3(),
+                ),
+            )?;
+        }
+        // The annotation context names the classes in the viewer, for all scans of the prefix.
+        let classes = ClassMap::read(options.class_map.as_deref())?;
+        if options.color_by == ColorBy::Classification
+            || options.split_by_class
+            || options
+                .color_priority
+                .iter()
+                .flatten()
+                .any(|c| *c == ColorBy::Classification)
+        {
+            rec.log_with_static(
+                entity_path_prefix,
+                options.static_,
+                &classes.annotation_context(options.colormap),
+            )?;
+        }
+        // Merged points are in the axes of the file, like the poses of the scans.
+        let axes = crate::axes_conversion(options);
+        if options.merge_scans && axes != DMat3::IDENTITY {
+            rec.log_with_static(
+                format!("{entity_path_prefix}/merged"),
+                options.static_,
+                &rerun::Transform3D::from_mat3x3(axes.as_mat3()),
+            )?;
+        }
+        Ok(Self {
+            rec,
+            path,
+            options,
+            entity_path_prefix,
+            progress: Progress::new(total, options.quiet),
+            budget: options.max_memory.map(MemoryBudget::new),
+            total,
+            color_range,
+            center,
+            export: options
+                .export
+                .as_deref()
+                .map(|path| Exporter::new(path, options.log_intensity))
+                .transpose()?,
+            issues: Mutex::default(),
+            origins: Mutex::default(),
+            merged_chunks: AtomicUsize::new(0),
+            reports: Mutex::default(),
+            classes,
+            perf: options
+                .log_perf
+                .then(|| PerfLogger::spawn(rec, format!("{entity_path_prefix}/perf")))
+                .transpose()?,
+            trajectory: Mutex::default(),
+            stations: Vec::new(),
+            template_paths: Vec::new(),
+            coverage: options.coverage_heatmap.then(Mutex::default),
+            dem: options.dem.map(|cell_size| Mutex::new(Dem::new(cell_size))),
+            flusher: options
+                .flush_every
+                .map(|every| Arc::new(Flusher::new(every))),
+            // A recording without an ID is disabled, so there is nothing to resume.
+            checkpoint: match rec.store_info() {
+                Some(info) if options.resume => Some(Checkpoint::open(
+                    path,
+                    info.store_id.to_string(),
+                    entity_path_prefix,
+                )?),
+                _ => None,
+            },
+        })
+    }
+
+    /// Returns whether the scan was logged by the load that is resumed.
+    pub fn is_logged(&self, index: usize) -> bool {
+        self.checkpoint
+            .as_ref()
+            .is_some_and(|checkpoint| checkpoint.is_logged(index))
+    }
+
+    /// Returns whether the scan was logged by the load that is resumed, and reports that it
+    /// is skipped.
+    pub fn was_logged(&self, index: usize) -> bool {
+        let logged = self.is_logged(index);
+        if logged && !self.options.quiet {
+            eprintln!("Skipping scan #{index}, which was already logged");
+        }
+        logged
+    }
+
+    /// Counts decoded records for the progress and the throughput.
+    fn add_records(&self, records: u64) {
+        self.progress.add(records);
+        if let Some(perf) = &self.perf {
+            perf.counters.records.fetch_add(records, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the entity path of a scan, under its station with `--group-stations` or from
+    /// the `--entity-template`.
+    pub fn scan_path(&self, index: usize) -> String {
+        crate::scan_path(
+            self.options,
+            self.path,
+            self.entity_path_prefix,
+            &self.stations,
+            &self.template_paths,
+            index,
+        )
+    }
+
+    /// Sends the default blueprint if requested, for the given scans.
+    pub fn send_blueprint(&self, scans: impl IntoIterator<Item = usize>) -> Result<()> {
+        if !self.options.send_blueprint {
+            return Ok(());
+        }
+        let prefix = self.entity_path_prefix;
+        let scans: Vec<usize> = scans.into_iter().collect();
+        let hidden: Vec<String> = self
+            .options
+            .visible_scans
+            .iter()
+            .flat_map(|visible| visible.hidden(scans.iter().copied()))
+            .map(|index| self.scan_path(index))
+            .collect();
+        crate::blueprint::send_blueprint(self.rec, self.options, prefix, &hidden)
+    }
+
+    /// Prints a warning about data that could not be loaded, and logs it to the recording as
+    /// `{prefix}/warnings`, so the recording shows that it is incomplete.
+    pub fn warn(&self, message: &str) {
+        eprintln!("{message}");
+        warn(self.rec, self.options, self.entity_path_prefix, message);
+    }
+
+    /// Logs the line through the scanner positions, in the order of the acquisition times if
+    /// all scans have one, and in the order of the scans otherwise.
+    fn log_trajectory(&mut self) -> Result<()> {
+        let mut scans = std::mem::take(self.trajectory.get_mut().expect("trajectory poisoned"));
+        if scans.len() < 2 {
+            return Ok(());
+        }
+        if scans.iter().all(|(time, ..)| time.is_some()) {
+            scans.sort_by(|(a, ..), (b, ..)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        } else {
+            scans.sort_by_key(|(_, index, _)| *index);
+        }
+
+        // The positions are relative to the center, in the axes of the prefix.
+        let axes = crate::axes_conversion(self.options);
+        let strip: Vec<glam::Vec3> = scans
+            .iter()
+            .map(|(_, _, t)| (axes * (*t - self.center)).as_vec3())
+            .collect();
+        self.rec.log_with_static(
+            format!("{}/trajectory", self.entity_path_prefix),
+            self.options.static_,
+            &rerun::LineStrips3D::new([strip])
+                .with_colors([rerun::Color::from_rgb(255, 200, 0)])
+                .with_labels(["Trajectory"]),
+        )?;
+        Ok(())
+    }
+
+    /// Logs the centroids of the voxels of all scans, colored by the number of scans with
+    /// points in them, and a legend of the colors.
+    fn log_coverage(&mut self) -> Result<()> {
+        let Some(coverage) = self.coverage.take() else {
+            return Ok(());
+        };
+        let coverage = coverage.into_inner().expect("coverage poisoned");
+        let axes = crate::axes_conversion(self.options);
+        let Some((points, max_scans)) = coverage.into_points(axes, self.center) else {
+            return Ok(());
+        };
+        let path = format!("{}/coverage", self.entity_path_prefix);
+        self.rec
+            .log_with_static(format!("{path}/voxels"), self.options.static_, &points)?;
+        self.rec.log_with_static(
+            path,
+            self.options.static_,
+            &rerun::TextDocument::from_markdown(format!(
+                "**Coverage:** scans with points in every voxel, from 1 (blue) to {max_scans} (red)"
+            )),
+        )?;
+        Ok(())
+    }
+
+    /// Logs the elevation model of `--dem` as a heightmap on `dem/{prefix}`, with the transform
+    /// from its pixels to file-level coordinates.
+    fn log_dem(&mut self) -> Result<()> {
+        let Some(dem) = self.dem.take() else {
+            return Ok(());
+        };
+        let dem = dem.into_inner().expect("DEM poisoned");
+        let Some((image, transform)) = dem.into_raster()? else {
+            self.warn("No ground points for --dem, which only E57, LAS and PLY scans have");
+            return Ok(());
+        };
+        let path = format!("dem/{}", self.entity_path_prefix.trim_start_matches('/'));
+        self.rec
+            .log_with_static(path.as_str(), self.options.static_, &transform)?;
+        self.rec
+            .log_with_static(path, self.options.static_, &image)?;
+        Ok(())
+    }
+
+    /// Prints the final progress, writes the export file, if any, and returns the invalid
+    /// points and decode errors of all scans, and their reports in the order of their indices.
+    pub fn finish(mut self) -> Result<(Issues, Vec<ScanReport>)> {
+        self.progress.finish();
+        if let Some(perf) = self.perf.take() {
+            perf.finish()?;
+        }
+        let issues = std::mem::take(self.issues.get_mut().expect("issues poisoned"));
+        // Decode errors are reported even with `--quiet`, since points are missing.
+        if let Some(first_error) = &issues.first_error {
+            self.warn(&format!(
+                "Skipped {} points due to decode errors, the first one in {first_error}",
+                issues.decode_errors
+            ));
+        }
+        self.log_trajectory()?;
+        self.log_coverage()?;
+        self.log_dem()?;
+        if let (Some(export), Some(path)) = (self.export, &self.options.export) {
+            let points = export.finish()?;
+            if !self.options.quiet {
+                eprintln!("Exported {points} points to {path:?}");
+            }
+        }
+        let mut reports = self.reports.into_inner().expect("reports poisoned");
+        reports.sort_by_key(|report| report.index);
+        Ok((issues, reports))
+    }
+}
+
+/// Points of a scan that could not be logged as they are.
+#[derive(Debug, Default)]
+pub(crate) struct Issues {
+    pub invalid_points: u64,
+    pub decode_errors: u64,
+    /// The first decode error, prefixed with the entity path of its scan.
+    pub first_error: Option<String>,
+}
+
+impl Issues {
+    fn merge(&mut self, other: Issues) {
+        self.invalid_points += other.invalid_points;
+        self.decode_errors += other.decode_errors;
+        self.first_error = self.first_error.take().or(other.first_error);
+    }
+}
+
+/// Number of points per logged chunk if neither a chunk size nor a chunk byte size is given.
+const DEFAULT_CHUNK_SIZE: usize = 1_000_000;
+
+/// Number of records a worker decodes before it reports them to the shared progress.
+const PROGRESS_BATCH: u64 = 100_000;
+
+/// Minimum time between two progress lines.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Tracks the records decoded by all workers and periodically prints the progress to stderr.
+pub(crate) struct Progress {
+    total: u64,
+    processed: AtomicU64,
+    start: Instant,
+    last_report: Mutex<Instant>,
+    quiet: bool,
+}
+
+impl Progress {
+    pub fn new(total: u64, quiet: bool) -> Self {
+        let start = Instant::now();
+        Self {
+            total,
+            processed: AtomicU64::new(0),
+            start,
+            last_report: Mutex::new(start),
+            quiet,
+        }
+    }
+
+    fn add(&self, records: u64) {
+        let processed = self.processed.fetch_add(records, Ordering::Relaxed) + records;
+        if self.quiet || self.total == 0 {
+            return;
+        }
+
+        // Another worker is already reporting, so there is nothing to add.
+        let Ok(mut last_report) = self.last_report.try_lock() else {
+            return;
+        };
+        if last_report.elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+        *last_report = Instant::now();
+
+        let fraction = processed as f64 / self.total as f64;
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let eta = elapsed / fraction - elapsed;
+        eprintln!(
+            "Loading: {:5.1}% ({processed}/{} points), ETA {eta:.0}s",
+            fraction * 100.0,
+            self.total
+        );
+    }
+
+    pub fn finish(&self) {
+        if !self.quiet {
+            eprintln!(
+                "Loaded {} points in {:.1}s",
+                self.processed.load(Ordering::Relaxed),
+                self.start.elapsed().as_secs_f64()
+            );
+        }
+    }
+}
+
+/// Octree depth of the coarsest level of detail, i.e. `2^depth` cells along the longest axis.
+const LOD_BASE_DEPTH: u32 = 5;
+
+/// Distance in meters from the origin of a scan beyond which its points are logged relative
+/// to the center of its bounds. Up to there, 32-bit positions keep sub-millimeter precision.
+const LOCAL_ORIGIN_DISTANCE: f64 = 1000.0;
+
+/// Only every n-th normal of a chunk is logged in `NormalsMode::Arrows`.
+const NORMAL_ARROW_STRIDE: usize = 100;
+
+/// Length of the logged normal arrows in meters.
+const NORMAL_ARROW_LENGTH: f32 = 0.1;
+
+/// Length in meters of the axes of a scanner position with `ScanOrigins::Axes`.
+const ORIGIN_AXIS_LENGTH: f32 = 1.0;
+
+/// Size of a buffered point: a position (3 x f32) and a color (4 x u8), plus an f32 for the
+/// intensity, 3 x f32 for the normal, a u16 for the class id, 2 x `Option<i64>` for the row and
+/// column and an `Option<f32>` for every extra field.
+fn bytes_per_point(
+    log_intensity: bool,
+    normals: bool,
+    classified: bool,
+    row_col: bool,
+    extra_fields: usize,
+) -> usize {
+    16 + if log_intensity { 4 } else { 0 }
+        + if normals { 12 } else { 0 }
+        + if classified { 2 } else { 0 }
+        + if row_col { 32 } else { 0 }
+        + 8 * extra_fields
+}
+
+/// Number of points a chunk writer reserves from the memory budget at once.
+const BUDGET_BLOCK: usize = 65_536;
+
+/// Shared upper bound for the point buffers of all chunk writers.
+///
+/// A writer that cannot reserve more memory logs its buffer early as a smaller chunk
+/// and waits for the recording stream to drain, so the bound may only be exceeded by
+/// one block per worker.
+pub(crate) struct MemoryBudget {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    fn try_reserve(&self, bytes: usize) -> bool {
+        self.used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                (used + bytes <= self.limit).then_some(used + bytes)
+            })
+            .is_ok()
+    }
+
+    fn reserve(&self, bytes: usize) {
+        self.used.fetch_add(bytes, Ordering::AcqRel);
+    }
+
+    fn release(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::AcqRel);
+    }
+}
+
+/// Buffers the points of a scan and logs them as `chunk_N` entities of a fixed size.
+struct ChunkWriter<'a> {
+    rec: &'a RecordingStream,
+    options: &'a LoadOptions,
+    export: Option<&'a Exporter>,
+    world: Option<(DVec3, DQuat)>,
+    intensity_range: Option<(f64, f64)>,
+    entity_path: String,
+    chunk_size: usize,
+    chunk_idx: usize,
+    /// Counter of the chunk indices shared by the writers of all scans with `--merge-scans`.
+    shared_chunks: Option<&'a AtomicUsize>,
+    points: u64,
+    log_intensity: bool,
+    /// How the normals are logged, if the scan has any.
+    normals_mode: Option<NormalsMode>,
+    estimate_normals: Option<NormalEstimation>,
+    /// Length of the time windows of `--per-point-time`, the acquisition start they are
+    /// relative to and the window of the buffered points.
+    time_window: Option<f64>,
+    time_origin: f64,
+    window_idx: Option<i64>,
+    positions: Vec<Vec3D>,
+    colors: Vec<rerun::Color>,
+    intensities: Vec<Option<f32>>,
+    normals: Vec<Option<Vec3D>>,
+    /// Whether the points have a classification, which is logged as their class ids.
+    classified: bool,
+    class_ids: Vec<u16>,
+    /// Whether the row and column indices are logged, and their buffers.
+    log_row_col: bool,
+    rows: Vec<Option<i64>>,
+    columns: Vec<Option<i64>>,
+    /// Slots in `ExtraScalars` and names of the logged extra fields, and their buffered values.
+    extra_fields: Vec<(usize, String)>,
+    extras: Vec<Vec<Option<f32>>>,
+    budget: Option<&'a MemoryBudget>,
+    reserved_points: usize,
+    /// Logs the chunks on another thread, or on the calling one if `None`.
+    logger: Option<Arc<ChunkLogger>>,
+    perf: Option<&'a PerfCounters>,
+    flusher: Option<&'a Flusher>,
+}
+
+impl<'a> ChunkWriter<'a> {
+    fn new(
+        loader: &'a Loader<'a>,
+        info: &ScanInfo,
+        entity_path: String,
+        chunk_size: usize,
+        logger: Option<Arc<ChunkLogger>>,
+    ) -> Self {
+        let budget = loader.budget.as_ref();
+        Self {
+            rec: loader.rec,
+            options: loader.options,
+            export: loader.export.as_ref(),
+            world: info.world,
+            intensity_range: info.intensity_range,
+            entity_path,
+            chunk_size,
+            chunk_idx: 0,
+            shared_chunks: loader.options.merge_scans.then_some(&loader.merged_chunks),
+            points: 0,
+            log_intensity: info.log_intensity,
+            normals_mode: info.normals.then_some(loader.options.normals),
+            estimate_normals: loader.options.estimate_normals,
+            time_window: loader.options.per_point_time,
+            time_origin: info.acquisition_time.unwrap_or_default(),
+            window_idx: None,
+            positions: Vec::new(),
+            colors: Vec::new(),
+            intensities: Vec::new(),
+            normals: Vec::new(),
+            classified: info.classified,
+            class_ids: Vec::new(),
+            log_row_col: loader.options.log_row_col && info.structured,
+            rows: Vec::new(),
+            columns: Vec::new(),
+            extra_fields: info.extra_fields.clone(),
+            extras: vec![Vec::new(); info.extra_fields.len()],
+            budget,
+            reserved_points: 0,
+            logger,
+            perf: loader.perf.as_ref().map(|perf| &*perf.counters),
+            flusher: loader.flusher.as_deref(),
+        }
+    }
+
+    fn push(&mut self, point: LoggedPoint, extras: &ExtraScalars) -> Result<()> {
+        // Points are usually stored in acquisition order, so a new window starts a new chunk.
+        // Points without a time stamp stay in the current window.
+        if let (Some(window), Some(time)) = (self.time_window, point.time) {
+            let window_idx = (time / window).floor() as i64;
+            if self.window_idx != Some(window_idx) {
+                self.flush()?;
+                self.window_idx = Some(window_idx);
+            }
+        }
+
+        if let Some(budget) = self.budget {
+            if self.positions.len() == self.reserved_points {
+                let bytes = BUDGET_BLOCK * self.bytes_per_point();
+                if !budget.try_reserve(bytes) {
+                    self.flush()?;
+                    self.rec.flush_blocking();
+                    budget.reserve(bytes);
+                }
+                self.reserved_points += BUDGET_BLOCK;
+                self.reserve(BUDGET_BLOCK);
+            }
+        } else if self.positions.is_empty() {
+            // The buffers of a logged chunk are handed over to the recording, so each chunk
+            // allocates new ones, but at their full size instead of growing point by point.
+            self.reserve(self.chunk_size);
+        }
+
+        self.positions.push(point.position);
+        self.colors.push(point.color);
+        self.points += 1;
+        if self.log_intensity {
+            self.intensities.push(point.intensity);
+        }
+        if self.normals_mode.is_some() {
+            self.normals.push(point.normal);
+        }
+        if self.classified {
+            self.class_ids.push(point.class.unwrap_or_default().into());
+        }
+        if self.log_row_col {
+            self.rows.push(point.row_column.map(|(row, _)| row));
+            self.columns
+                .push(point.row_column.map(|(_, column)| column));
+        }
+        for ((slot, _), values) in self.extra_fields.iter().zip(&mut self.extras) {
+            values.push(extras[*slot]);
+        }
+
+        if self.positions.len() >= self.chunk_size {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Logs all buffered points as the next chunk, or adds them to the export.
+    fn flush(&mut self) -> Result<()> {
+        if self.positions.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(export) = self.export {
+            export.write(
+                self.world,
+                self.intensity_range,
+                &self.positions,
+                &self.colors,
+                &self.intensities,
+            )?;
+            self.positions.clear();
+            self.colors.clear();
+            self.intensities.clear();
+            self.normals.clear();
+            self.class_ids.clear();
+            self.rows.clear();
+            self.columns.clear();
+            self.extras.iter_mut().for_each(Vec::clear);
+        } else {
+            if let Some(estimation) = self
+                .estimate_normals
+                .filter(|_| self.normals_mode.is_some())
+            {
+                estimate_normals(&self.positions, &mut self.normals, estimation);
+            }
+            if let (Some(window), Some(window_idx)) = (self.time_window, self.window_idx) {
+                let start = self.time_origin + window_idx as f64 * window;
+                set_acquisition_time(self.rec, Some(start));
+            }
+            if let Some(perf) = self.perf {
+                let points = self.positions.len() as u64;
+                perf.points.fetch_add(points, Ordering::Relaxed);
+                perf.bytes
+                    .fetch_add(points * self.bytes_per_point() as u64, Ordering::Relaxed);
+            }
+            if self.options.quantize_colors {
+                for color in &mut self.colors {
+                    *color = quantize_color(*color);
+                }
+            }
+            let chunk = Chunk {
+                entity_path: match (self.options.entity_layout, self.shared_chunks) {
+                    (EntityLayout::Flat, _) => format!("{}/points", self.entity_path),
+                    (_, Some(chunks)) => format!(
+                        "{}/{}",
+                        self.entity_path,
+                        chunk_name(self.options, chunks.fetch_add(1, Ordering::Relaxed))
+                    ),
+                    (_, None) => format!(
+                        "{}/{}",
+                        self.entity_path,
+                        chunk_name(self.options, self.chunk_idx)
+                    ),
+                },
+                points: std::mem::take(&mut self.positions),
+                colors: std::mem::take(&mut self.colors),
+                scalars: self.scalars(),
+                normals: self
+                    .normals_mode
+                    .map(|mode| (mode, std::mem::take(&mut self.normals))),
+                class_ids: std::mem::take(&mut self.class_ids),
+                indices: self.indices(),
+                precision: self.options.precision,
+            };
+            match &self.logger {
+                Some(logger) => logger.log(self.rec.now(), chunk)?,
+                None => log_chunk(
+                    self.rec,
+                    self.options.static_,
+                    self.options.point_radius,
+                    self.flusher,
+                    chunk,
+                )?,
+            }
+        }
+        self.chunk_idx += 1;
+
+        if let Some(budget) = self.budget {
+            budget.release(self.reserved_points * self.bytes_per_point());
+            self.reserved_points = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Reserves space for `additional` points in all buffers that are in use.
+    fn reserve(&mut self, additional: usize) {
+        self.positions.reserve_exact(additional);
+        self.colors.reserve_exact(additional);
+        if self.log_intensity {
+            self.intensities.reserve_exact(additional);
+        }
+        if self.normals_mode.is_some() {
+            self.normals.reserve_exact(additional);
+        }
+        if self.classified {
+            self.class_ids.reserve_exact(additional);
+        }
+        if self.log_row_col {
+            self.rows.reserve_exact(additional);
+            self.columns.reserve_exact(additional);
+        }
+        for values in &mut self.extras {
+            values.reserve_exact(additional);
+        }
+    }
+
+    /// Takes the buffered intensities and extra fields, with the names of their components.
+    fn scalars(&mut self) -> Vec<(String, Vec<Option<f32>>)> {
+        let intensities = self.log_intensity.then(|| {
+            (
+                "rerun_e57.Intensity".to_owned(),
+                std::mem::take(&mut self.intensities),
+            )
+        });
+        let extras = self
+            .extra_fields
+            .iter()
+            .zip(&mut self.extras)
+            .map(|((_, name), values)| (format!("rerun_e57.{name}"), std::mem::take(values)));
+        intensities.into_iter().chain(extras).collect()
+    }
+
+    /// Takes the buffered row and column indices, with the names of their components.
+    fn indices(&mut self) -> Vec<(String, Vec<Option<i64>>)> {
+        if !self.log_row_col {
+            return Vec::new();
+        }
+        vec![
+            ("rerun_e57.Row".to_owned(), std::mem::take(&mut self.rows)),
+            (
+                "rerun_e57.Column".to_owned(),
+                std::mem::take(&mut self.columns),
+            ),
+        ]
+    }
+
+    fn bytes_per_point(&self) -> usize {
+        bytes_per_point(
+            self.log_intensity,
+            self.normals_mode.is_some(),
+            self.classified,
+            self.log_row_col,
+            self.extra_fields.len(),
+        )
+    }
+}
+
+/// A colored point in the frame of the logged points, with the attributes that are logged.
+#[derive(Debug, Clone, Copy)]
+struct LoggedPoint {
+    position: Vec3D,
+    color: rerun::Color,
+    intensity: Option<f32>,
+    normal: Option<Vec3D>,
+    /// Seconds since the acquisition start of the scan.
+    time: Option<f64>,
+    class: Option<u8>,
+    /// Row and column index of the point, which voxels do not have.
+    row_column: Option<(i64, i64)>,
+}
+
+/// Points on their way to a chunk writer, optionally reduced by a voxel grid first.
+struct PointSink<'a> {
+    voxel_grid: Option<VoxelGrid>,
+    writer: ChunkWriter<'a>,
+}
+
+impl PointSink<'_> {
+    fn push(&mut self, point: LoggedPoint, extras: &ExtraScalars) -> Result<()> {
+        match &mut self.voxel_grid {
+            Some(grid) => {
+                grid.insert(point);
+                Ok(())
+            }
+            None => self.writer.push(point, extras),
+        }
+    }
+
+    /// Logs all remaining points and returns the number of logged points.
+    fn finish(mut self) -> Result<u64> {
+        if let Some(grid) = self.voxel_grid.take() {
+            let extras = ExtraScalars::default();
+            for point in grid.into_points() {
+                self.writer.push(point, &extras)?;
+            }
+        }
+        self.writer.flush()?;
+
+        Ok(self.writer.points)
+    }
+}
+
+/// Collects the ranges of a structured scan by their row and column index.
+#[derive(Default)]
+struct RangeImage {
+    pixels: Vec<(i64, i64, f32)>,
+}
+
+impl RangeImage {
+    fn insert(&mut self, row: i64, column: i64, range: f32) {
+        self.pixels.push((row, column, range));
+    }
+
+    /// Returns a depth image in meters with one pixel per row/column, where missing points are zero.
+    fn into_depth_image(self) -> Result<Option<rerun::DepthImage>> {
+        let cells = self.pixels.iter().map(|p| (p.0, p.1));
+        let Some(grid) = Grid::bounding(cells, "--as-depth-image")? else {
+            return Ok(None);
+        };
+
+        let mut depth = vec![0.0_f32; grid.len()];
+        for (row, column, range) in self.pixels {
+            depth[grid.index(row, column).expect("in the bounds")] = range;
+        }
+
+        let bytes: Vec<u8> = depth.iter().flat_map(|d| d.to_le_bytes()).collect();
+        Ok(Some(
+            rerun::DepthImage::from_data_type_and_bytes(
+                bytes,
+                [grid.width as u32, grid.height as u32],
+                rerun::datatypes::ChannelDatatype::F32,
+            )
+            .with_meter(1.0),
+        ))
+    }
+}
+
+/// Collects the colors of a structured scan by their row and column index, which are the
+/// elevation and azimuth steps of the scanner.
+#[derive(Default)]
+struct Panorama {
+    pixels: Vec<(i64, i64, rerun::Color)>,
+}
+
+impl Panorama {
+    fn insert(&mut self, row: i64, column: i64, color: rerun::Color) {
+        self.pixels.push((row, column, color));
+    }
+
+    /// Returns an equirectangular RGB image with one pixel per row/column, where missing points
+    /// are black, or `None` for scans without colors.
+    fn into_image(self) -> Result<Option<rerun::Image>> {
+        let cells = self.pixels.iter().map(|p| (p.0, p.1));
+        let Some(grid) = Grid::bounding(cells, "--reconstruct-panorama")? else {
+            return Ok(None);
+        };
+
+        let mut rgb = vec![0_u8; grid.len() * 3];
+        for (row, column, color) in self.pixels {
+            let pixel = grid.index(row, column).expect("in the bounds");
+            let [r, g, b, _] = color.to_array();
+            rgb[pixel * 3..pixel * 3 + 3].copy_from_slice(&[r, g, b]);
+        }
+        Ok(Some(rerun::Image::from_rgb24(
+            rgb,
+            [grid.width as u32, grid.height as u32],
+        )))
+    }
+}
+
+/// Accumulated points falling into a single voxel.
+#[derive(Default)]
+struct Voxel {
+    count: u64,
+    position: [f64; 3],
+    color: [u64; 3],
+    intensity: f64,
+    intensity_count: u64,
+    normal: [f64; 3],
+    normal_count: u64,
+    class: Option<u8>,
+}
+
+/// Sparse voxel grid that reduces all points inside a voxel to their centroid and average color.
+struct VoxelGrid {
+    size: f64,
+    voxels: HashMap<[i64; 3], Voxel>,
+}
+
+impl VoxelGrid {
+    fn new(size: f64) -> Self {
+        Self {
+            size,
+            voxels: HashMap::new(),
+        }
+    }
+
+    fn insert(&mut self, point: LoggedPoint) {
+        let LoggedPoint {
+            position,
+            color,
+            intensity,
+            normal,
+            class,
+            ..
+        } = point;
+        let key = [
+            (position.x() as f64 / self.size).floor() as i64,
+            (position.y() as f64 / self.size).floor() as i64,
+            (position.z() as f64 / self.size).floor() as i64,
+        ];
+        let [r, g, b, _] = color.to_array();
+
+        let voxel = self.voxels.entry(key).or_default();
+        voxel.count += 1;
+        voxel.position[0] += position.x() as f64;
+        voxel.position[1] += position.y() as f64;
+        voxel.position[2] += position.z() as f64;
+        voxel.color[0] += r as u64;
+        voxel.color[1] += g as u64;
+        voxel.color[2] += b as u64;
+        if let Some(intensity) = intensity {
+            voxel.intensity += intensity as f64;
+            voxel.intensity_count += 1;
+        }
+        if let Some(normal) = normal {
+            for (sum, v) in voxel.normal.iter_mut().zip(normal.0) {
+                *sum += v as f64;
+            }
+            voxel.normal_count += 1;
+        }
+        // Classes cannot be averaged, so a voxel keeps the first one.
+        if voxel.class.is_none() {
+            voxel.class = class;
+        }
+    }
+
+    /// Returns one representative point per voxel.
+    fn into_points(self) -> impl Iterator<Item = LoggedPoint> {
+        self.voxels.into_values().map(|voxel| {
+            let n = voxel.count as f64;
+            let position = Vec3D::new(
+                (voxel.position[0] / n) as f32,
+                (voxel.position[1] / n) as f32,
+                (voxel.position[2] / n) as f32,
+            );
+            let color = rerun::Color::from_rgb(
+                (voxel.color[0] / voxel.count) as u8,
+                (voxel.color[1] / voxel.count) as u8,
+                (voxel.color[2] / voxel.count) as u8,
+            );
+            let intensity = (voxel.intensity_count > 0)
+                .then(|| (voxel.intensity / voxel.intensity_count as f64) as f32);
+            // The averaged normal is renormalized, unless the normals cancel each other out.
+            let normal = DVec3::from(voxel.normal)
+                .try_normalize()
+                .filter(|_| voxel.normal_count > 0)
+                .map(|n| Vec3D::new(n.x as f32, n.y as f32, n.z as f32));
+            LoggedPoint {
+                position,
+                color,
+                intensity,
+                normal,
+                time: None,
+                class: voxel.class,
+                row_column: None,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_budget_rejects_reservations_over_the_limit() {
+        let budget = MemoryBudget::new(100);
+        assert!(budget.try_reserve(60));
+        assert!(!budget.try_reserve(60));
+        budget.release(60);
+        assert!(budget.try_reserve(100));
+    }
+
+    #[test]
+    fn quantizes_color_channels() {
+        let color = quantize_color(rerun::Color::from_rgb(0, 130, 255));
+        assert_eq!(color.to_array(), [0, 131, 255, 255]);
+    }
+
+    #[test]
+    fn selects_first_or_last_returns() {
+        let pulse = [(0, 3), (1, 3), (2, 3)].map(Some);
+        let selected = |returns| pulse.map(|point| is_selected_return(returns, point));
+        assert_eq!(selected(Returns::All), [true, true, true]);
+        assert_eq!(selected(Returns::First), [true, false, false]);
+        assert_eq!(selected(Returns::Last), [false, false, true]);
+        assert!(is_selected_return(Returns::Last, None));
+    }
+}
@@ -0,0 +1,145 @@
+//! Detection of the dominant planes of a scan with RANSAC, for `--detect-planes`.
+
+use glam::{DMat3, DQuat, DVec3};
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::normals::plane_normal;
+use crate::samples::PointSamples;
+
+/// Number of random planes tried for every detected plane.
+const ITERATIONS: usize = 500;
+/// Distance in meters of the inliers to their plane.
+const INLIER_DISTANCE: f64 = 0.03;
+/// Fewest sampled inliers of a plane, below which no more planes are detected.
+const MIN_INLIERS: usize = 50;
+/// Thickness in meters of the logged boxes.
+const THICKNESS: f32 = 0.01;
+
+/// Plane fitted to the inliers of a scan, with the rectangle they cover.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Plane {
+    pub center: DVec3,
+    /// Rotation from the box frame, whose Z axis is the normal of the plane.
+    pub rotation: DQuat,
+    pub half_size: [f64; 2],
+    /// Estimated number of points of the scan on the plane.
+    pub inliers: u64,
+}
+
+/// Detects up to `n` planes in the sampled points, one after the other in the points that are
+/// not on an earlier one, in decreasing number of inliers.
+pub(crate) fn detect_planes(samples: &PointSamples, n: usize, rng: &mut StdRng) -> Vec<Plane> {
+    let scale = samples.scale();
+    let mut points = samples.points().to_vec();
+    let mut planes = Vec::new();
+    while planes.len() < n && points.len() >= MIN_INLIERS {
+        let Some(normal) = best_plane(&points, rng) else {
+            break;
+        };
+        let (inliers, outliers): (Vec<DVec3>, Vec<DVec3>) = points
+            .iter()
+            .partition(|p| (**p - normal.1).dot(normal.0).abs() <= INLIER_DISTANCE);
+        if inliers.len() < MIN_INLIERS {
+            break;
+        }
+        planes.push(fit_plane(&inliers, (inliers.len() as f64 * scale) as u64));
+        points = outliers;
+    }
+    planes
+}
+
+/// Returns the normal and a point of the random plane through three points with the most
+/// inliers, if any three points span a plane.
+fn best_plane(points: &[DVec3], rng: &mut StdRng) -> Option<(DVec3, DVec3)> {
+    let mut best: Option<((DVec3, DVec3), usize)> = None;
+    for _ in 0..ITERATIONS {
+        let [a, b, c] = [(); 3].map(|_| points[rng.gen_range(0..points.len())]);
+        let Some(normal) = (b - a).cross(c - a).try_normalize() else {
+            continue;
+        };
+        let inliers = points
+            .iter()
+            .filter(|p| (**p - a).dot(normal).abs() <= INLIER_DISTANCE)
+            .count();
+        if best.is_none_or(|(_, most)| inliers > most) {
+            best = Some(((normal, a), inliers));
+        }
+    }
+    best.map(|(plane, _)| plane)
+}
+
+/// Fits a plane to its inliers by least squares, and bounds them in the plane.
+fn fit_plane(inliers: &[DVec3], count: u64) -> Plane {
+    let normal = plane_normal(inliers).unwrap_or(DVec3::Z);
+    let u = normal.any_orthonormal_vector();
+    let v = normal.cross(u);
+    let mean = inliers.iter().sum::<DVec3>() / inliers.len() as f64;
+    let (mut min, mut max) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+    for p in inliers {
+        let d = *p - mean;
+        for (i, coordinate) in [d.dot(u), d.dot(v)].into_iter().enumerate() {
+            min[i] = min[i].min(coordinate);
+            max[i] = max[i].max(coordinate);
+        }
+    }
+    Plane {
+        center: mean + u * (min[0] + max[0]) / 2.0 + v * (min[1] + max[1]) / 2.0,
+        rotation: DQuat::from_mat3(&DMat3::from_cols(u, v, normal)),
+        half_size: [0, 1].map(|i| (max[i] - min[i]) / 2.0),
+        inliers: count,
+    }
+}
+
+/// Returns the planes as thin boxes, labeled with their number of points.
+pub(crate) fn boxes(planes: &[Plane]) -> rerun::Boxes3D {
+    rerun::Boxes3D::from_centers_and_half_sizes(
+        planes.iter().map(|plane| plane.center.as_vec3().to_array()),
+        planes.iter().map(|plane| {
+            let [u, v] = plane.half_size.map(|h| h as f32);
+            [u, v, THICKNESS / 2.0]
+        }),
+    )
+    .with_quaternions(planes.iter().map(|plane| {
+        let q = plane.rotation.as_quat();
+        rerun::Quaternion::from_xyzw([q.x, q.y, q.z, q.w])
+    }))
+    .with_labels(
+        planes
+            .iter()
+            .enumerate()
+            .map(|(index, plane)| format!("Plane {index}: {} points", plane.inliers)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn detects_a_floor_and_a_wall() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut samples = PointSamples::default();
+        for i in 0..40 {
+            for j in 0..40 {
+                let (a, b) = (i as f64 * 0.1, j as f64 * 0.1);
+                // A 4 by 4 m floor, and a 4 by 2 m wall at x = 0.
+                samples.insert(DVec3::new(a, b, 0.0), &mut rng);
+                if j < 20 {
+                    samples.insert(DVec3::new(0.0, a, 0.05 + b), &mut rng);
+                }
+            }
+        }
+        let planes = detect_planes(&samples, 3, &mut rng);
+        assert_eq!(planes.len(), 2);
+        assert_eq!(planes[0].inliers, 1600);
+        let normal = planes[0].rotation * DVec3::Z;
+        assert!(normal.abs().abs_diff_eq(DVec3::Z, 1e-6), "{normal}");
+        assert!(planes[0]
+            .center
+            .abs_diff_eq(DVec3::new(1.95, 1.95, 0.0), 1e-6));
+        let normal = planes[1].rotation * DVec3::Z;
+        assert!(normal.abs().abs_diff_eq(DVec3::X, 1e-6), "{normal}");
+    }
+}
@@ -0,0 +1,389 @@
+//! Loads ASCII and binary PLY point clouds with the `ply-rs` crate.
+//!
+//! The `vertex` element is logged like an E57 scan as `{prefix}/scan_0`; other elements such
+//! as faces are skipped.
+
+use anyhow::{Context, Result};
+use glam::DVec3;
+use ply_rs::parser::Parser;
+use ply_rs::ply::{
+    DefaultElement, Encoding, Header, Property, PropertyAccess, PropertyType, ScalarType,
+};
+use rerun::{RecordingStream, Vec3D};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::fields::is_classification_name;
+use crate::pipeline::{Loader, ScanInfo, ScanPipeline, SourcePoint};
+use crate::{
+    bounds_color_range, center_offset, geometric_color_by, scan_color_by, validate_options, Center,
+    IntensityRange, LoadOptions, NormalsMode, Stats,
+};
+
+/// Property names used for intensities, after removing the `scalar_` prefix of CloudCompare.
+const INTENSITY_NAMES: [&str; 2] = ["intensity", "reflectance"];
+
+/// Property names of the normal components.
+const NORMAL_NAMES: [[&str; 2]; 3] = [["nx", "normal_x"], ["ny", "normal_y"], ["nz", "normal_z"]];
+
+/// The vertex properties used by the point pipeline.
+#[derive(Debug, Default, Clone)]
+struct PlyVertex {
+    position: [f64; 3],
+    color: [Option<u8>; 3],
+    intensity: Option<f64>,
+    classification: Option<u8>,
+    normal: [Option<f64>; 3],
+}
+
+impl PropertyAccess for PlyVertex {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn set_property(&mut self, name: String, property: Property) {
+        let Some(value) = scalar_value(&property) else {
+            return;
+        };
+        let name = name.to_ascii_lowercase();
+        let name = name.strip_prefix("scalar_").unwrap_or(&name);
+        if let Some(axis) = NORMAL_NAMES.iter().position(|names| names.contains(&name)) {
+            self.normal[axis] = Some(value);
+            return;
+        }
+        match name {
+            "x" => self.position[0] = value,
+            "y" => self.position[1] = value,
+            "z" => self.position[2] = value,
+            "red" | "r" => self.color[0] = Some(color_channel(&property, value)),
+            "green" | "g" => self.color[1] = Some(color_channel(&property, value)),
+            "blue" | "b" => self.color[2] = Some(color_channel(&property, value)),
+            _ if INTENSITY_NAMES.contains(&name) => self.intensity = Some(value),
+            _ if is_classification_name(name) => {
+                self.classification = Some(value.round().clamp(0.0, 255.0) as u8);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl PlyVertex {
+    fn into_point(self) -> SourcePoint {
+        SourcePoint {
+            position: DVec3::from(self.position),
+            color: match self.color {
+                [Some(r), Some(g), Some(b)] => Some(rerun::Color::from_rgb(r, g, b)),
+                _ => None,
+            },
+            intensity: self.intensity.map(|i| i as f32),
+            row: -1,
+            column: -1,
+            classification: self.classification,
+            normal: match self.normal {
+                [Some(x), Some(y), Some(z)] => Some(Vec3D::new(x as f32, y as f32, z as f32)),
+                _ => None,
+            },
+            time: None,
+            returns: None,
+            extras: Default::default(),
+        }
+    }
+}
+
+fn scalar_value(property: &Property) -> Option<f64> {
+    Some(match *property {
+        Property::Char(v) => v as f64,
+        Property::UChar(v) => v as f64,
+        Property::Short(v) => v as f64,
+        Property::UShort(v) => v as f64,
+        Property::Int(v) => v as f64,
+        Property::UInt(v) => v as f64,
+        Property::Float(v) => v as f64,
+        Property::Double(v) => v,
+        _ => return None,
+    })
+}
+
+/// Converts a color channel to 8 bits, since PLY writers use 8-bit, 16-bit and float colors.
+fn color_channel(property: &Property, value: f64) -> u8 {
+    match property {
+        Property::UShort(v) => (v >> 8) as u8,
+        Property::Float(_) | Property::Double(_) => (value.clamp(0.0, 1.0) * 255.0).round() as u8,
+        _ => value.clamp(0.0, 255.0) as u8,
+    }
+}
+
+/// Returns the value range of an integer intensity type, or `0..1` for floating point types.
+fn type_limits(data_type: &PropertyType) -> Option<(f64, f64)> {
+    let PropertyType::Scalar(scalar) = data_type else {
+        return None;
+    };
+    Some(match scalar {
+        ScalarType::Char => (i8::MIN as f64, i8::MAX as f64),
+        ScalarType::UChar => (0.0, u8::MAX as f64),
+        ScalarType::Short => (i16::MIN as f64, i16::MAX as f64),
+        ScalarType::UShort => (0.0, u16::MAX as f64),
+        ScalarType::Int => (i32::MIN as f64, i32::MAX as f64),
+        ScalarType::UInt => (0.0, u32::MAX as f64),
+        ScalarType::Float | ScalarType::Double => (0.0, 1.0),
+    })
+}
+
+/// Reads the header of the PLY file and calls `f` for every vertex, without buffering them,
+/// until the loading is cancelled.
+fn read_vertices(
+    path: &Path,
+    options: &LoadOptions,
+    mut f: impl FnMut(PlyVertex) -> Result<()>,
+) -> Result<Header> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("Failed to read PLY file: {path:?}"))?,
+    );
+    let vertex_parser = Parser::<PlyVertex>::new();
+    let other_parser = Parser::<DefaultElement>::new();
+    let header = vertex_parser
+        .read_header(&mut reader)
+        .with_context(|| format!("Failed to read PLY header: {path:?}"))?;
+
+    let mut line = String::new();
+    for element in header.elements.values() {
+        let is_vertex = element.name == "vertex";
+        for _ in 0..element.count {
+            if !is_vertex {
+                // Elements are stored one after another, so the ones before the vertices
+                // have to be decoded to be skipped.
+                match header.encoding {
+                    Encoding::Ascii => {
+                        line.clear();
+                        reader.read_line(&mut line)?;
+                    }
+                    Encoding::BinaryBigEndian => {
+                        other_parser.read_big_endian_element(&mut reader, element)?;
+                    }
+                    Encoding::BinaryLittleEndian => {
+                        other_parser.read_little_endian_element(&mut reader, element)?;
+                    }
+                }
+                continue;
+            }
+            if crate::is_cancelled(options) {
+                break;
+            }
+
+            let vertex = match header.encoding {
+                Encoding::Ascii => {
+                    line.clear();
+                    reader.read_line(&mut line)?;
+                    vertex_parser.read_ascii_element(&line, element)
+                }
+                Encoding::BinaryBigEndian => {
+                    vertex_parser.read_big_endian_element(&mut reader, element)
+                }
+                Encoding::BinaryLittleEndian => {
+                    vertex_parser.read_little_endian_element(&mut reader, element)
+                }
+            }
+            .context("Failed to read PLY vertex")?;
+            f(vertex)?;
+        }
+        if is_vertex {
+            break;
+        }
+    }
+
+    Ok(header)
+}
+
+/// Renders the PLY header as a Markdown table.
+fn ply_metadata(header: &Header, vertices: usize) -> String {
+    let mut markdown = String::from("| Field | Value |\n| --- | --- |\n");
+    markdown += &format!("| Encoding | {} |\n", header.encoding);
+    markdown += &format!("| Points | {vertices} |\n");
+    for comment in &header.comments {
+        markdown += &format!("| Comment | {} |\n", comment.replace('|', "\\|"));
+    }
+    markdown
+}
+
+/// Loads the PLY file at `path` and logs its vertices to `rec` as `{prefix}/scan_0`.
+pub fn load_ply(path: &Path, options: &LoadOptions, rec: &RecordingStream) -> Result<Stats> {
+    validate_options(options)?;
+
+    let entity_path_prefix: &str = &crate::entity_path_prefix(options, path, "ply_pointcloud");
+    let index = 0;
+    if !crate::is_scan_selected(options, index, None, None) {
+        return Ok(Stats::default());
+    }
+
+    let header = {
+        let mut reader = BufReader::new(
+            File::open(path).with_context(|| format!("Failed to read PLY file: {path:?}"))?,
+        );
+        Parser::<PlyVertex>::new()
+            .read_header(&mut reader)
+            .with_context(|| format!("Failed to read PLY header: {path:?}"))?
+    };
+    let vertex_element = header
+        .elements
+        .get("vertex")
+        .with_context(|| format!("PLY file has no vertex element: {path:?}"))?;
+    let vertices = vertex_element.count;
+    if (vertices as u64) < crate::min_points(options) {
+        return Ok(Stats {
+            skipped_scans: 1,
+            ..Default::default()
+        });
+    }
+    let has_normals = NORMAL_NAMES.iter().all(|names| {
+        vertex_element
+            .properties
+            .keys()
+            .any(|name| names.contains(&name.to_ascii_lowercase().as_str()))
+    });
+    let intensity_property = vertex_element.properties.values().find(|property| {
+        let name = property.name.to_ascii_lowercase();
+        INTENSITY_NAMES.contains(&name.strip_prefix("scalar_").unwrap_or(&name))
+    });
+    let has_property = |is_name: fn(&str) -> bool| {
+        vertex_element
+            .properties
+            .keys()
+            .any(|name| is_name(&name.to_ascii_lowercase()))
+    };
+    let color_by = scan_color_by(
+        options,
+        has_property(|name| matches!(name, "red" | "r")),
+        intensity_property.is_some(),
+        has_property(is_classification_name),
+    );
+
+    // The bounds and the intensity range of the vertices need a first pass, since PLY
+    // headers do not store them.
+    let needs_bounds = options.bounds
+        || options.lod_levels.is_some()
+        || options.center == Some(Center::Auto)
+        || (options.color_range.is_none() && geometric_color_by(options).is_some());
+    let needs_intensity_range =
+        options.intensity_range == IntensityRange::Auto && intensity_property.is_some();
+    let mut bounds: Option<([f64; 3], [f64; 3])> = None;
+    let mut auto_intensity_range: Option<(f64, f64)> = None;
+    if needs_bounds || needs_intensity_range {
+        read_vertices(path, options, |vertex| {
+            let p = vertex.position;
+            bounds = Some(match bounds {
+                Some((min, max)) => (
+                    [0, 1, 2].map(|i| min[i].min(p[i])),
+                    [0, 1, 2].map(|i| max[i].max(p[i])),
+                ),
+                None => (p, p),
+            });
+            if let Some(i) = vertex.intensity {
+                auto_intensity_range = Some(match auto_intensity_range {
+                    Some((lo, hi)) => (lo.min(i), hi.max(i)),
+                    None => (i, i),
+                });
+            }
+            Ok(())
+        })?;
+    }
+
+    let color_range = match options.color_range {
+        Some(range) => Some(range),
+        None => bounds_color_range(options, bounds.map(|bounds| (bounds, None))),
+    };
+    let intensity_range = match options.intensity_range {
+        IntensityRange::Limits => intensity_property.and_then(|p| type_limits(&p.data_type)),
+        IntensityRange::Auto => auto_intensity_range,
+        IntensityRange::Fixed(min, max) => Some((min, max)),
+    };
+
+    let loader = Loader::new(
+        rec,
+        path,
+        options,
+        entity_path_prefix,
+        vertices as u64,
+        color_range,
+        center_offset(options, bounds.map(|bounds| (bounds, None))),
+    )?;
+    if loader.was_logged(index) {
+        return Ok(Stats::default());
+    }
+    loader.send_blueprint([index])?;
+    let entity_path = loader.scan_path(index);
+    let mut pipeline = ScanPipeline::new(
+        &loader,
+        ScanInfo {
+            index,
+            entity_path: entity_path.clone(),
+            color_by,
+            log_intensity: options.log_intensity && intensity_property.is_some(),
+            intensity_range,
+            normals: options.normals != NormalsMode::Skip && has_normals,
+            extra_fields: Vec::new(),
+            bounds,
+            world: None,
+            structured: false,
+            classified: has_property(is_classification_name),
+            records: vertices as u64,
+            acquisition_time: None,
+            preview: false,
+            shading: None,
+        },
+    )?;
+
+    rec.log_with_static(
+        entity_path.as_str(),
+        options.static_,
+        &rerun::TextDocument::from_markdown(ply_metadata(&header, vertices)),
+    )?;
+
+    if pipeline.samples_ground() {
+        read_vertices(path, options, |vertex| {
+            pipeline.sample_ground(DVec3::from(vertex.position));
+            Ok(())
+        })?;
+        pipeline.build_ground()?;
+    }
+    read_vertices(path, options, |vertex| pipeline.push(vertex.into_point()))?;
+    let points = pipeline.finish()?;
+    let (issues, reports) = loader.finish()?;
+
+    Ok(Stats {
+        scans: 1,
+        points,
+        invalid_points: issues.invalid_points,
+        decode_errors: issues.decode_errors,
+        reports,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_vertex_properties_to_points() {
+        let mut vertex = PlyVertex::new();
+        vertex.set_property("x".to_owned(), Property::Float(1.0));
+        vertex.set_property("y".to_owned(), Property::Double(2.0));
+        vertex.set_property("z".to_owned(), Property::Int(3));
+        vertex.set_property("red".to_owned(), Property::UChar(255));
+        vertex.set_property("green".to_owned(), Property::UShort(32768));
+        vertex.set_property("blue".to_owned(), Property::Float(0.0));
+        vertex.set_property("scalar_Intensity".to_owned(), Property::Float(0.5));
+        vertex.set_property("face_indices".to_owned(), Property::ListInt(vec![1, 2]));
+        for (name, value) in [("nx", 0.0), ("ny", 0.0), ("nz", 1.0)] {
+            vertex.set_property(name.to_owned(), Property::Float(value));
+        }
+
+        let point = vertex.into_point();
+        assert_eq!(point.position, DVec3::new(1.0, 2.0, 3.0));
+        assert_eq!(point.color, Some(rerun::Color::from_rgb(255, 128, 0)));
+        assert_eq!(point.intensity, Some(0.5));
+        assert_eq!(point.classification, None);
+        assert_eq!(point.normal, Some(Vec3D::new(0.0, 0.0, 1.0)));
+    }
+}
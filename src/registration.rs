@@ -0,0 +1,369 @@
+//! Fine registration of the scans of a file with point-to-plane ICP, for
+//! `--refine-registration`.
+//!
+//! Every scan is aligned to the scans before it, starting from its stored pose, so the first
+//! scan keeps its pose and the others are refined in turn. Scans are registered with a sample
+//! of their points, in file-level coordinates relative to the first scan, which keeps the
+//! precision of the kd-tree for geo-referenced files.
+
+use anyhow::{Context, Result};
+use e57::{E57Reader, PointCloud, Quaternion, Transform, Translation};
+use glam::{DQuat, DVec3, Vec3};
+use rayon::prelude::*;
+use rerun::RecordingStream;
+use std::io::{Read, Seek};
+
+use crate::kdtree::KdTree;
+use crate::normals::plane_normal;
+use crate::LoadOptions;
+
+/// Number of points of every scan that it is registered with.
+const SAMPLES: u64 = 20_000;
+/// Neighbors of the normals of the sampled points.
+const NORMAL_NEIGHBORS: usize = 10;
+/// Distance in meters up to which points of two scans correspond.
+const MAX_DISTANCE: f32 = 0.5;
+const MAX_ITERATIONS: usize = 30;
+/// Rotation in radians and translation in meters of an iteration at which ICP has converged.
+const CONVERGED: f64 = 1e-6;
+/// Fewest corresponding points for the scans to overlap.
+const MIN_CORRESPONDENCES: usize = 100;
+
+/// Rigid transform found by ICP, with its point-to-plane residuals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Alignment {
+    pub rotation: DQuat,
+    pub translation: DVec3,
+    pub correspondences: usize,
+    /// Root mean square of the point-to-plane distances in meters, before and after.
+    pub rms_before: f64,
+    pub rms_after: f64,
+}
+
+/// Points with normals that scans are aligned to.
+#[derive(Default)]
+pub(crate) struct Target {
+    points: Vec<[f32; 3]>,
+    normals: Vec<Option<DVec3>>,
+}
+
+impl Target {
+    /// Adds points with their normals.
+    pub fn extend(&mut self, points: &[DVec3], normals: &[Option<DVec3>]) {
+        self.points
+            .extend(points.iter().map(|p| p.as_vec3().to_array()));
+        self.normals.extend_from_slice(normals);
+    }
+
+    /// Aligns the source points to the target with point-to-plane ICP, or returns `None` if
+    /// too few of them are close to the target.
+    pub fn align(&self, source: &[DVec3]) -> Option<Alignment> {
+        let tree = KdTree::new(&self.points);
+        let (mut rotation, mut translation) = (DQuat::IDENTITY, DVec3::ZERO);
+        let (_, rms_before) = self.residuals(&tree, source, rotation, translation)?;
+        for _ in 0..MAX_ITERATIONS {
+            let Some((delta_rotation, delta_translation)) =
+                self.step(&tree, source, rotation, translation)
+            else {
+                break;
+            };
+            rotation = (delta_rotation * rotation).normalize();
+            translation = delta_rotation * translation + delta_translation;
+            if delta_rotation.to_scaled_axis().length() < CONVERGED
+                && delta_translation.length() < CONVERGED
+            {
+                break;
+            }
+        }
+        let (correspondences, rms_after) = self.residuals(&tree, source, rotation, translation)?;
+        Some(Alignment {
+            rotation,
+            translation,
+            correspondences,
+            rms_before,
+            rms_after,
+        })
+    }
+
+    /// Returns the corresponding target points and normals of the transformed source points.
+    fn correspondences(
+        &self,
+        tree: &KdTree,
+        source: &[DVec3],
+        rotation: DQuat,
+        translation: DVec3,
+    ) -> Vec<(DVec3, DVec3, DVec3)> {
+        source
+            .par_iter()
+            .filter_map(|p| {
+                let p = rotation * *p + translation;
+                let nearest = tree.nearest(p.as_vec3().to_array(), 1, MAX_DISTANCE);
+                let index = nearest.first()?.index as usize;
+                let normal = self.normals[index]?;
+                Some((p, Vec3::from(self.points[index]).as_dvec3(), normal))
+            })
+            .collect()
+    }
+
+    /// Returns the number of correspondences and their RMS point-to-plane distance.
+    fn residuals(
+        &self,
+        tree: &KdTree,
+        source: &[DVec3],
+        rotation: DQuat,
+        translation: DVec3,
+    ) -> Option<(usize, f64)> {
+        let pairs = self.correspondences(tree, source, rotation, translation);
+        if pairs.len() < MIN_CORRESPONDENCES {
+            return None;
+        }
+        let sum: f64 = pairs
+            .iter()
+            .map(|(p, q, n)| (*p - *q).dot(*n).powi(2))
+            .sum();
+        Some((pairs.len(), (sum / pairs.len() as f64).sqrt()))
+    }
+
+    /// Solves the linearized point-to-plane problem for the small rotation and translation
+    /// that move the transformed source points onto the planes of their correspondences.
+    fn step(
+        &self,
+        tree: &KdTree,
+        source: &[DVec3],
+        rotation: DQuat,
+        translation: DVec3,
+    ) -> Option<(DQuat, DVec3)> {
+        let pairs = self.correspondences(tree, source, rotation, translation);
+        if pairs.len() < MIN_CORRESPONDENCES {
+            return None;
+        }
+        let mut ata = [[0.0; 6]; 6];
+        let mut atb = [0.0; 6];
+        for (p, q, n) in &pairs {
+            let residual = (*p - *q).dot(*n);
+            let c = p.cross(*n);
+            let row = [c.x, c.y, c.z, n.x, n.y, n.z];
+            for i in 0..6 {
+                for j in 0..6 {
+                    ata[i][j] += row[i] * row[j];
+                }
+                atb[i] -= row[i] * residual;
+            }
+        }
+        let x = solve(ata, atb)?;
+        Some((
+            DQuat::from_scaled_axis(DVec3::new(x[0], x[1], x[2])),
+            DVec3::new(x[3], x[4], x[5]),
+        ))
+    }
+}
+
+/// Solves a 6x6 linear system with Gaussian elimination, or returns `None` if it is singular,
+/// e.g. when all correspondences are on a single plane.
+fn solve(mut a: [[f64; 6]; 6], mut b: [f64; 6]) -> Option<[f64; 6]> {
+    for column in 0..6 {
+        let pivot =
+            (column..6).max_by(|i, j| a[*i][column].abs().total_cmp(&a[*j][column].abs()))?;
+        if a[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        let pivot_row = a[column];
+        for row in column + 1..6 {
+            let factor = a[row][column] / pivot_row[column];
+            for (value, pivot) in a[row][column..].iter_mut().zip(&pivot_row[column..]) {
+                *value -= factor * pivot;
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+    let mut x = [0.0; 6];
+    for row in (0..6).rev() {
+        let sum: f64 = (row + 1..6).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Estimates the normals of points from their nearest neighbors.
+pub(crate) fn estimate_normals(points: &[DVec3]) -> Vec<Option<DVec3>> {
+    let positions: Vec<[f32; 3]> = points.iter().map(|p| p.as_vec3().to_array()).collect();
+    let tree = KdTree::new(&positions);
+    positions
+        .par_iter()
+        .map(|p| {
+            let neighborhood: Vec<DVec3> = tree
+                .nearest(*p, NORMAL_NEIGHBORS, MAX_DISTANCE)
+                .iter()
+                .map(|n| Vec3::from(positions[n.index as usize]).as_dvec3())
+                .collect();
+            plane_normal(&neighborhood)
+        })
+        .collect()
+}
+
+/// Refines the poses of the selected point clouds in place, and logs a report of the
+/// residuals on `{prefix}/registration`.
+pub(crate) fn refine_registration<T: Read + Seek>(
+    reader: &mut E57Reader<T>,
+    rec: &RecordingStream,
+    options: &LoadOptions,
+    prefix: &str,
+    pointclouds: &mut [PointCloud],
+    selected: &[usize],
+) -> Result<()> {
+    let Some(&first) = selected.first() else {
+        return Ok(());
+    };
+    let pose = |pointcloud: &PointCloud| {
+        pointcloud
+            .transform
+            .as_ref()
+            .map_or((DVec3::ZERO, DQuat::IDENTITY), crate::pose)
+    };
+    let origin = pose(&pointclouds[first]).0;
+
+    let mut target = Target::default();
+    let mut report = String::from(
+        "| Scan | Correspondences | RMS before (m) | RMS after (m) |\n| --- | --- | --- | --- |\n",
+    );
+    for &index in selected {
+        let (translation, rotation) = pose(&pointclouds[index]);
+        let mut points = scan_samples(reader, &pointclouds[index])?;
+        for p in &mut points {
+            *p = rotation * *p + translation - origin;
+        }
+        let normals = estimate_normals(&points);
+
+        let alignment = if index == first {
+            None
+        } else {
+            target.align(&points)
+        };
+        match alignment {
+            Some(alignment) => {
+                let rotation = alignment.rotation * rotation;
+                let translation =
+                    alignment.rotation * (translation - origin) + alignment.translation + origin;
+                pointclouds[index].transform = Some(Transform {
+                    rotation: Quaternion {
+                        w: rotation.w,
+                        x: rotation.x,
+                        y: rotation.y,
+                        z: rotation.z,
+                    },
+                    translation: Translation {
+                        x: translation.x,
+                        y: translation.y,
+                        z: translation.z,
+                    },
+                });
+                report += &format!(
+                    "| {index} | {} | {:.4} | {:.4} |\n",
+                    alignment.correspondences, alignment.rms_before, alignment.rms_after
+                );
+                if !options.quiet {
+                    eprintln!(
+                        "Refined the pose of point cloud #{index}: RMS {:.4} m -> {:.4} m over {} points",
+                        alignment.rms_before, alignment.rms_after, alignment.correspondences
+                    );
+                }
+                let (points, normals) = transformed(&points, &normals, &alignment);
+                target.extend(&points, &normals);
+            }
+            None => {
+                if index == first {
+                    report += &format!("| {index} | reference | | |\n");
+                } else {
+                    report += &format!("| {index} | no overlap | | |\n");
+                    if !options.quiet {
+                        eprintln!("Point cloud #{index} does not overlap the earlier scans, keeping its pose");
+                    }
+                }
+                target.extend(&points, &normals);
+            }
+        }
+    }
+
+    rec.log_with_static(
+        format!("{prefix}/registration"),
+        options.static_,
+        &rerun::TextDocument::from_markdown(report),
+    )?;
+    Ok(())
+}
+
+/// Returns the points and normals moved by an alignment.
+fn transformed(
+    points: &[DVec3],
+    normals: &[Option<DVec3>],
+    alignment: &Alignment,
+) -> (Vec<DVec3>, Vec<Option<DVec3>>) {
+    (
+        points
+            .iter()
+            .map(|p| alignment.rotation * *p + alignment.translation)
+            .collect(),
+        normals
+            .iter()
+            .map(|n| n.map(|n| alignment.rotation * n))
+            .collect(),
+    )
+}
+
+/// Returns a sample of evenly spaced records of a scan, in its local coordinates.
+fn scan_samples<T: Read + Seek>(
+    reader: &mut E57Reader<T>,
+    pointcloud: &PointCloud,
+) -> Result<Vec<DVec3>> {
+    let mut iter = reader
+        .pointcloud_simple(pointcloud)
+        .context("Unable to get simple point cloud iterator")?;
+    iter.apply_pose(false);
+    iter.spherical_to_cartesian(false);
+    iter.normalize_intensity(false);
+    iter.intensity_to_color(false);
+
+    let every = (pointcloud.records / SAMPLES).max(1) as usize;
+    Ok(iter
+        .step_by(every)
+        .filter_map(|p| crate::point_position(&p.ok()?))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_a_shifted_corner() {
+        // A floor and two walls of a 4 m room corner, sampled every 10 cm.
+        let mut points = Vec::new();
+        for i in 0..40 {
+            for j in 0..40 {
+                let (a, b) = (i as f64 * 0.1, j as f64 * 0.1);
+                points.extend([
+                    DVec3::new(a, b, 0.0),
+                    DVec3::new(0.0, a, b),
+                    DVec3::new(a, 0.0, b),
+                ]);
+            }
+        }
+        let mut target = Target::default();
+        target.extend(&points, &estimate_normals(&points));
+
+        let rotation = DQuat::from_rotation_z(0.02);
+        let translation = DVec3::new(0.1, -0.05, 0.03);
+        let source: Vec<DVec3> = points.iter().map(|p| rotation * *p + translation).collect();
+        let alignment = target.align(&source).unwrap();
+        assert!(alignment.rms_before > 0.01, "{alignment:?}");
+        assert!(alignment.rms_after < 1e-4, "{alignment:?}");
+        let corrected = alignment.rotation * (rotation * DVec3::new(2.0, 2.0, 0.0) + translation)
+            + alignment.translation;
+        assert!(
+            corrected.abs_diff_eq(DVec3::new(2.0, 2.0, 0.0), 1e-3),
+            "{corrected}"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Sidecar checkpoint of the scans that were logged, for `--resume`.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Contents of the sidecar file, which only apply to the recording and prefix they were
+/// written for.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct CheckpointFile {
+    recording_id: String,
+    entity_path_prefix: String,
+    /// Indices of the scans whose points were all logged.
+    scans: BTreeSet<usize>,
+}
+
+/// Scans of a file that were logged to a recording, kept in `{file}.resume.json` so that an
+/// interrupted load can be continued with the same recording ID.
+#[derive(Debug)]
+pub(crate) struct Checkpoint {
+    path: PathBuf,
+    state: Mutex<CheckpointFile>,
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint of the file at `path`, which starts over if it was written for
+    /// another recording or prefix.
+    pub fn open(path: &Path, recording_id: String, entity_path_prefix: &str) -> Result<Self> {
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(".resume.json");
+        let path = PathBuf::from(sidecar);
+
+        let previous = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<CheckpointFile>(&bytes)
+                .with_context(|| format!("Failed to read checkpoint: {path:?}"))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => CheckpointFile::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read checkpoint: {path:?}"))
+            }
+        };
+        let scans = if previous.recording_id == recording_id
+            && previous.entity_path_prefix == entity_path_prefix
+        {
+            previous.scans
+        } else {
+            BTreeSet::new()
+        };
+        Ok(Self {
+            path,
+            state: Mutex::new(CheckpointFile {
+                recording_id,
+                entity_path_prefix: entity_path_prefix.to_owned(),
+                scans,
+            }),
+        })
+    }
+
+    /// Returns whether all points of the scan were logged by an earlier load.
+    pub fn is_logged(&self, index: usize) -> bool {
+        self.state.lock().unwrap().scans.contains(&index)
+    }
+
+    /// Records that all points of the scan were logged, replacing the sidecar file at once so
+    /// that an interrupted write cannot corrupt it.
+    pub fn mark_logged(&self, index: usize) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.scans.insert(index);
+
+        let dir = self.path.parent().filter(|dir| !dir.as_os_str().is_empty());
+        let mut file = tempfile::NamedTempFile::new_in(dir.unwrap_or(Path::new(".")))
+            .with_context(|| format!("Failed to write checkpoint: {:?}", self.path))?;
+        serde_json::to_writer_pretty(&mut file, &*state)?;
+        file.flush()?;
+        file.persist(&self.path)
+            .with_context(|| format!("Failed to write checkpoint: {:?}", self.path))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_over_for_another_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scan.e57");
+
+        let checkpoint = Checkpoint::open(&path, "a".to_owned(), "e57_pointcloud").unwrap();
+        checkpoint.mark_logged(2).unwrap();
+        assert!(dir.path().join("scan.e57.resume.json").exists());
+
+        let resumed = Checkpoint::open(&path, "a".to_owned(), "e57_pointcloud").unwrap();
+        assert!(resumed.is_logged(2));
+        assert!(!resumed.is_logged(0));
+        let other = Checkpoint::open(&path, "b".to_owned(), "e57_pointcloud").unwrap();
+        assert!(!other.is_logged(2));
+    }
+}
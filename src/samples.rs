@@ -0,0 +1,39 @@
+//! Uniform samples of the logged points of a scan, for the analyses that need all of them at
+//! once, like `--detect-planes` and `--measure`.
+
+use glam::DVec3;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+/// Number of points of a scan that are kept.
+const SAMPLES: usize = 100_000;
+
+/// Uniform sample of the positions of a scan, kept with reservoir sampling.
+#[derive(Default)]
+pub(crate) struct PointSamples {
+    points: Vec<DVec3>,
+    seen: u64,
+}
+
+impl PointSamples {
+    pub fn insert(&mut self, position: DVec3, rng: &mut StdRng) {
+        self.seen += 1;
+        if self.points.len() < SAMPLES {
+            self.points.push(position);
+        } else {
+            let index = rng.gen_range(0..self.seen);
+            if let Some(point) = self.points.get_mut(index as usize) {
+                *point = position;
+            }
+        }
+    }
+
+    pub fn points(&self) -> &[DVec3] {
+        &self.points
+    }
+
+    /// Returns the number of points that every sampled point stands for.
+    pub fn scale(&self) -> f64 {
+        self.seen as f64 / self.points.len().max(1) as f64
+    }
+}
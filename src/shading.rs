@@ -0,0 +1,126 @@
+//! Shading of structured scans from the gradients of their range image, for `--shade`.
+
+use crate::grid::Grid;
+use anyhow::Result;
+use glam::DVec3;
+
+/// Brightness of the surfaces that are seen edge-on, so that they still show their colors.
+const AMBIENT: f32 = 0.25;
+
+/// Brightness of every row and column of a structured scan, lit from the scanner.
+pub(crate) struct RangeShading {
+    grid: Grid,
+    shades: Vec<f32>,
+}
+
+impl RangeShading {
+    /// Computes the shading from the local positions of the points by their row and column.
+    ///
+    /// The normal of a point is the cross product of the differences to its neighbors in the
+    /// next and previous row and column, and it is lit by the cosine between its normal and
+    /// the direction to the scanner. Points without neighbors are not shaded. Returns `None`
+    /// without points, and fails for grids of more than `MAX_GRID_CELLS` rows and columns.
+    pub fn new(points: impl IntoIterator<Item = (i64, i64, DVec3)>) -> Result<Option<Self>> {
+        let points: Vec<(i64, i64, DVec3)> = points.into_iter().collect();
+        let Some(grid) = Grid::bounding(points.iter().map(|p| (p.0, p.1)), "--shade")? else {
+            return Ok(None);
+        };
+        let (width, height) = (grid.width, grid.height);
+
+        let mut positions = vec![None; grid.len()];
+        for (row, column, position) in points {
+            positions[grid.index(row, column).expect("in the bounds")] = Some(position);
+        }
+        let at = |row: Option<usize>, column: Option<usize>| {
+            let (row, column) = (row?, column?);
+            (row < height && column < width)
+                .then(|| positions[row * width + column])
+                .flatten()
+        };
+
+        let mut shades = vec![1.0; grid.len()];
+        for row in 0..height {
+            for column in 0..width {
+                let Some(position) = positions[row * width + column] else {
+                    continue;
+                };
+                let along_row = difference(
+                    at(Some(row), column.checked_add(1)),
+                    at(Some(row), column.checked_sub(1)),
+                    position,
+                );
+                let along_column = difference(
+                    at(row.checked_add(1), Some(column)),
+                    at(row.checked_sub(1), Some(column)),
+                    position,
+                );
+                let normal = along_row.zip(along_column).map(|(a, b)| a.cross(b));
+                if let Some(normal) = normal.and_then(DVec3::try_normalize) {
+                    let cos = normal.dot(position.normalize_or_zero()).abs() as f32;
+                    shades[row * width + column] = AMBIENT + (1.0 - AMBIENT) * cos;
+                }
+            }
+        }
+        Ok(Some(Self { grid, shades }))
+    }
+
+    /// Returns the brightness from 0 to 1 of the point at a row and column.
+    pub fn shade(&self, row: i64, column: i64) -> f32 {
+        self.grid
+            .index(row, column)
+            .map_or(1.0, |index| self.shades[index])
+    }
+
+    /// Darkens a color by the shade of its point.
+    pub fn apply(&self, color: rerun::Color, row: i64, column: i64) -> rerun::Color {
+        let shade = self.shade(row, column);
+        let [r, g, b, a] = color.to_array();
+        let [r, g, b] = [r, g, b].map(|c| (c as f32 * shade).round() as u8);
+        rerun::Color::from_unmultiplied_rgba(r, g, b, a)
+    }
+}
+
+/// Returns the central difference of the neighbors of a point, or the one-sided difference to
+/// the neighbor there is.
+fn difference(next: Option<DVec3>, previous: Option<DVec3>, position: DVec3) -> Option<DVec3> {
+    match (next, previous) {
+        (Some(next), Some(previous)) => Some(next - previous),
+        (Some(next), None) => Some(next - position),
+        (None, Some(previous)) => Some(position - previous),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lights_surfaces_facing_the_scanner() {
+        // A wall 10 m in front of the scanner, and a floor 1 m below it seen at a grazing angle.
+        let wall = (0..3).flat_map(|row| {
+            (0..3).map(move |column| {
+                (
+                    row,
+                    column,
+                    DVec3::new(10.0, column as f64 * 0.1, row as f64 * 0.1),
+                )
+            })
+        });
+        let shading = RangeShading::new(wall).unwrap().unwrap();
+        assert!(shading.shade(1, 1) > 0.99);
+
+        let floor = (0..3).flat_map(|row| {
+            (0..3).map(move |column| {
+                (
+                    row,
+                    column,
+                    DVec3::new(10.0 + row as f64, column as f64 * 0.1, -1.0),
+                )
+            })
+        });
+        let shading = RangeShading::new(floor).unwrap().unwrap();
+        assert!(shading.shade(1, 1) < 0.4);
+        assert_eq!(shading.shade(5, 5), 1.0);
+    }
+}
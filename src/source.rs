@@ -0,0 +1,49 @@
+//! Opens E57 files with buffered reads, or memory-mapped with `--mmap`.
+
+use anyhow::{Context, Result};
+use e57::E57Reader;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Bytes of an E57 file.
+pub(crate) enum FileSource {
+    Buffered(BufReader<File>),
+    /// The whole file mapped into memory, which saves the copies and system calls of buffered
+    /// reads, since the readers seek back and forth between the pages of different sections.
+    Mapped(Cursor<Mmap>),
+}
+
+impl Read for FileSource {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Buffered(reader) => reader.read(buf),
+            Self::Mapped(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for FileSource {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Self::Buffered(reader) => reader.seek(pos),
+            Self::Mapped(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+/// Opens the E57 file at `path`, memory-mapped if `mmap` is set.
+pub(crate) fn open_e57(path: &Path, mmap: bool) -> Result<E57Reader<FileSource>> {
+    let file = File::open(path).with_context(|| format!("Failed to open E57 file: {path:?}"))?;
+    let source = if mmap {
+        // SAFETY: The map is only read, and the file must not be modified while it is loaded,
+        // which is why `--mmap` cannot be combined with `--watch`.
+        let map = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("Failed to memory-map E57 file: {path:?}"))?;
+        FileSource::Mapped(Cursor::new(map))
+    } else {
+        FileSource::Buffered(BufReader::new(file))
+    };
+    E57Reader::new(source).with_context(|| format!("Failed to read E57 file: {path:?}"))
+}
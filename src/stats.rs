@@ -0,0 +1,118 @@
+//! Histograms of the intensities and ranges of a scan, for `--stats`.
+
+use anyhow::Result;
+use rerun::RecordingStream;
+
+/// Number of bins of the intensity histogram, over the normalized intensities from 0 to 1.
+const INTENSITY_BINS: usize = 32;
+
+/// Width in meters of the finest range bins, which are merged until there are at most
+/// `MAX_RANGE_BINS` of them.
+const RANGE_BIN: f64 = 0.1;
+const MAX_RANGE_BINS: usize = 100;
+
+/// Distribution of the intensities and ranges of the points of a scan.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScanStats {
+    points: u64,
+    range_sum: f64,
+    range_max: f64,
+    /// Number of points per `RANGE_BIN`, growing with the largest range.
+    ranges: Vec<u64>,
+    intensity_points: u64,
+    intensity_sum: f64,
+    intensities: Vec<u64>,
+}
+
+impl ScanStats {
+    /// Adds a point with its range in meters and its intensity normalized to 0 to 1.
+    pub fn insert(&mut self, range: f64, intensity: Option<f32>) {
+        self.points += 1;
+        self.range_sum += range;
+        self.range_max = self.range_max.max(range);
+        let bin = (range / RANGE_BIN) as usize;
+        if bin >= self.ranges.len() {
+            self.ranges.resize(bin + 1, 0);
+        }
+        self.ranges[bin] += 1;
+
+        if let Some(intensity) = intensity {
+            if self.intensities.is_empty() {
+                self.intensities = vec![0; INTENSITY_BINS];
+            }
+            self.intensity_points += 1;
+            self.intensity_sum += intensity as f64;
+            let bin = ((intensity * INTENSITY_BINS as f32) as usize).min(INTENSITY_BINS - 1);
+            self.intensities[bin] += 1;
+        }
+    }
+
+    /// Returns the range histogram with at most `MAX_RANGE_BINS` bins, and their width in
+    /// meters.
+    fn range_histogram(&self) -> (Vec<u64>, f64) {
+        let mut bins = self.ranges.clone();
+        let mut width = RANGE_BIN;
+        while bins.len() > MAX_RANGE_BINS {
+            bins = bins.chunks(2).map(|pair| pair.iter().sum()).collect();
+            width *= 2.0;
+        }
+        (bins, width)
+    }
+
+    /// Logs the histograms as bar charts under `{entity_path}/stats`, with a summary.
+    pub fn log(&self, rec: &RecordingStream, static_: bool, entity_path: &str) -> Result<()> {
+        if self.points == 0 {
+            return Ok(());
+        }
+
+        let (ranges, range_bin) = self.range_histogram();
+        let mut summary = format!(
+            "**Points:** {}\n\n**Range:** mean {:.3} m, max {:.3} m, histogram bins of {range_bin:.1} m",
+            self.points,
+            self.range_sum / self.points as f64,
+            self.range_max,
+        );
+        rec.log_with_static(
+            format!("{entity_path}/stats/range"),
+            static_,
+            &rerun::BarChart::new(ranges),
+        )?;
+
+        if self.intensity_points > 0 {
+            summary += &format!(
+                "\n\n**Intensity:** mean {:.3} of the intensity range, histogram bins of 1/{INTENSITY_BINS}",
+                self.intensity_sum / self.intensity_points as f64,
+            );
+            rec.log_with_static(
+                format!("{entity_path}/stats/intensity"),
+                static_,
+                &rerun::BarChart::new(self.intensities.clone()),
+            )?;
+        }
+
+        rec.log_with_static(
+            format!("{entity_path}/stats"),
+            static_,
+            &rerun::TextDocument::from_markdown(summary),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_range_bins_up_to_the_limit() {
+        let mut stats = ScanStats::default();
+        stats.insert(0.05, Some(0.0));
+        stats.insert(25.0, Some(1.0));
+        let (bins, width) = stats.range_histogram();
+        assert_eq!(width, 0.4);
+        assert_eq!(bins.len(), 63);
+        assert_eq!((bins[0], bins[62]), (1, 1));
+        assert_eq!(stats.intensities[0], 1);
+        assert_eq!(stats.intensities[INTENSITY_BINS - 1], 1);
+    }
+}
@@ -0,0 +1,241 @@
+//! Validates the page checksums of an E57 file before it is loaded, for `--verify`.
+//!
+//! E57 files consist of pages, usually of 1024 bytes, that end with a CRC-32C checksum of the
+//! rest of the page. The `e57` crate only checks the pages it reads and stops at the first
+//! corrupt one, so all pages are checked here to report every corrupt section at once.
+
+use anyhow::{Context, Result};
+use e57::{E57Reader, Header, Image, Projection};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Size of the checksum at the end of every page.
+const CHECKSUM_SIZE: usize = 4;
+
+/// Consecutive corrupt pages within the same section of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CorruptPages {
+    pub first_page: u64,
+    pub last_page: u64,
+    /// Byte offset of the first corrupt page.
+    pub offset: u64,
+    /// End of the last corrupt page, which is shorter than a page for truncated files.
+    pub end: u64,
+    /// Section the pages belong to, e.g. `binary section of scan 2`.
+    pub section: String,
+}
+
+/// Checks the checksums of all pages of the E57 file at `path` and fails with a report of the
+/// corrupt pages, if there are any.
+pub(crate) fn verify_e57(path: &Path) -> Result<()> {
+    let corrupt = corrupt_pages(path)?;
+    if corrupt.is_empty() {
+        return Ok(());
+    }
+
+    let mut report = format!("Corrupt pages in E57 file {path:?}:");
+    for pages in &corrupt {
+        let range = if pages.first_page == pages.last_page {
+            format!("page {}", pages.first_page)
+        } else {
+            format!("pages {}-{}", pages.first_page, pages.last_page)
+        };
+        report += &format!(
+            "\n  {range} at bytes {}..{}: {}",
+            pages.offset, pages.end, pages.section
+        );
+    }
+    anyhow::bail!(report)
+}
+
+/// Returns the corrupt pages of the E57 file at `path`, grouped by section.
+pub(crate) fn corrupt_pages(path: &Path) -> Result<Vec<CorruptPages>> {
+    let mut file = BufReader::new(
+        File::open(path).with_context(|| format!("Failed to read E57 file: {path:?}"))?,
+    );
+    let header =
+        Header::read(&mut file).with_context(|| format!("Failed to read E57 header: {path:?}"))?;
+    let page_size = usize::try_from(header.page_size)
+        .ok()
+        .filter(|&size| size > CHECKSUM_SIZE)
+        .with_context(|| format!("Invalid E57 page size {}: {path:?}", header.page_size))?;
+    let sections = sections(path, &header);
+
+    // The header was read from the first page, which is checked like all others.
+    let mut file = BufReader::new(
+        File::open(path).with_context(|| format!("Failed to read E57 file: {path:?}"))?,
+    );
+    let table = crc32c_table();
+    let mut page = vec![0_u8; page_size];
+    let mut corrupt: Vec<CorruptPages> = Vec::new();
+    for index in 0_u64.. {
+        let length = read_page(&mut file, &mut page)
+            .with_context(|| format!("Failed to read page {index} of {path:?}"))?;
+        if length == 0 {
+            break;
+        }
+        let (data, checksum) = page.split_at(page_size - CHECKSUM_SIZE);
+        // Unlike all other values, the checksums are stored in big endian order.
+        let valid = length == page_size && checksum == crc32c(&table, data).to_be_bytes();
+        if valid {
+            continue;
+        }
+
+        let offset = index * page_size as u64;
+        let section = if length == page_size {
+            section_of(&sections, offset)
+        } else {
+            format!("truncated after {length} bytes")
+        };
+        match corrupt.last_mut() {
+            Some(last) if last.last_page + 1 == index && last.section == section => {
+                last.last_page = index;
+                last.end = offset + length as u64;
+            }
+            _ => corrupt.push(CorruptPages {
+                first_page: index,
+                last_page: index,
+                offset,
+                end: offset + length as u64,
+                section,
+            }),
+        }
+        if length < page_size {
+            break;
+        }
+    }
+    Ok(corrupt)
+}
+
+/// Returns a description of the truncation if the E57 file at `path` is shorter than its
+/// header says.
+pub(crate) fn truncation(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let length = file.metadata().ok()?.len();
+    let header = Header::read(&mut file).ok()?;
+    (length < header.phys_length).then(|| {
+        format!(
+            "E57 file is truncated after {length} of {} bytes",
+            header.phys_length
+        )
+    })
+}
+
+/// Reads a page, or what is left of it at the end of the file, and returns its length.
+fn read_page(reader: &mut impl Read, page: &mut [u8]) -> std::io::Result<usize> {
+    let mut length = 0;
+    while length < page.len() {
+        match reader.read(&mut page[length..])? {
+            0 => break,
+            n => length += n,
+        }
+    }
+    Ok(length)
+}
+
+/// Returns the physical offsets at which the sections of the file start, with their names,
+/// sorted by offset. Only the header and the XML section are known if the XML cannot be read.
+fn sections(path: &Path, header: &Header) -> Vec<(u64, String)> {
+    let mut sections = vec![
+        (0, "file header".to_owned()),
+        (header.phys_xml_offset, "XML section".to_owned()),
+    ];
+    if let Ok(reader) = E57Reader::from_file(path) {
+        for (index, pointcloud) in reader.pointclouds().iter().enumerate() {
+            sections.push((
+                pointcloud.file_offset,
+                format!("binary section of scan {index}"),
+            ));
+        }
+        for (index, image) in reader.images().iter().enumerate() {
+            if let Some(offset) = image_offset(image) {
+                sections.push((offset, format!("blob of image {index}")));
+            }
+        }
+    }
+    sections.sort_by_key(|(offset, _)| *offset);
+    sections
+}
+
+/// Returns the offset of the image data of an image.
+fn image_offset(image: &Image) -> Option<u64> {
+    let blob = match (&image.projection, &image.visual_reference) {
+        (Some(Projection::Pinhole(pinhole)), _) => &pinhole.blob,
+        (Some(Projection::Spherical(spherical)), _) => &spherical.blob,
+        (Some(Projection::Cylindrical(cylindrical)), _) => &cylindrical.blob,
+        (None, Some(visual)) => &visual.blob,
+        (None, None) => return None,
+    };
+    Some(blob.data.offset)
+}
+
+/// Returns the name of the last section that starts at or before `offset`.
+fn section_of(sections: &[(u64, String)], offset: u64) -> String {
+    sections
+        .iter()
+        .rev()
+        .find(|(start, _)| *start <= offset)
+        .map_or_else(|| "unknown section".to_owned(), |(_, name)| name.clone())
+}
+
+/// Returns the lookup table of the CRC-32C (Castagnoli) checksum.
+fn crc32c_table() -> [u32; 256] {
+    let mut table = [0_u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut value = i as u32;
+        for _ in 0..8 {
+            value = match value & 1 {
+                0 => value >> 1,
+                _ => (value >> 1) ^ 0x82F6_3B78,
+            };
+        }
+        *entry = value;
+    }
+    table
+}
+
+fn crc32c(table: &[u32; 256], data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_crc32c() {
+        assert_eq!(crc32c(&crc32c_table(), b"123456789"), 0xE306_9283);
+    }
+
+    #[test]
+    fn detects_truncated_files() {
+        let mut header = Header::default();
+        header.phys_length = 4096;
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        header.write(&mut file).unwrap();
+        assert_eq!(
+            truncation(file.path()).as_deref(),
+            Some("E57 file is truncated after 48 of 4096 bytes")
+        );
+
+        header.phys_length = 48;
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        header.write(&mut file).unwrap();
+        assert_eq!(truncation(file.path()), None);
+    }
+
+    #[test]
+    fn finds_section_of_offset() {
+        let sections = vec![
+            (0, "file header".to_owned()),
+            (1024, "binary section of scan 0".to_owned()),
+            (8192, "XML section".to_owned()),
+        ];
+        assert_eq!(section_of(&sections, 0), "file header");
+        assert_eq!(section_of(&sections, 4096), "binary section of scan 0");
+        assert_eq!(section_of(&sections, 8192), "XML section");
+    }
+}
@@ -0,0 +1,144 @@
+//! Synthetic E57 files for the integration tests and the benchmarks, which include this module
+//! with `#[path]`, and the recording they are loaded into.
+
+// Every test crate and benchmark includes this module, but none uses all of it.
+#![allow(dead_code)]
+
+use e57::{E57Writer, Quaternion, Record, RecordValue, Transform, Translation};
+use rerun::external::re_log_types::{LogMsg, StoreKind};
+use rerun::log::Chunk;
+use rerun::{Component, RecordingStream, RecordingStreamBuilder};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Shape of a synthetic E57 file.
+#[derive(Debug, Clone, Copy)]
+pub struct Synthetic {
+    pub scans: usize,
+    /// Number of points of each scan.
+    pub points: usize,
+    /// Whether the points have RGB colors, in addition to positions and intensities.
+    pub color: bool,
+}
+
+/// Writes an E57 file with scans of points on a wavy square, each with a pose 10 m further
+/// along the X axis, named `Scan N`.
+pub fn write_synthetic_e57(path: &Path, synthetic: Synthetic) -> e57::Result<()> {
+    let mut writer = E57Writer::from_file(path, "synthetic")?;
+    let mut prototype = vec![
+        Record::CARTESIAN_X_F32,
+        Record::CARTESIAN_Y_F32,
+        Record::CARTESIAN_Z_F32,
+        Record::INTENSITY_UNIT_F32,
+    ];
+    if synthetic.color {
+        prototype.extend([
+            Record::COLOR_RED_U8,
+            Record::COLOR_GREEN_U8,
+            Record::COLOR_BLUE_U8,
+        ]);
+    }
+
+    let side = (synthetic.points as f64).sqrt().ceil().max(1.0) as usize;
+    for scan in 0..synthetic.scans {
+        let mut pointcloud =
+            writer.add_pointcloud(&format!("synthetic-{scan}"), prototype.clone())?;
+        pointcloud.set_name(Some(format!("Scan {scan}")));
+        pointcloud.set_transform(Some(Transform {
+            rotation: Quaternion {
+                w: 1.0,
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            translation: Translation {
+                x: 10.0 * scan as f64,
+                y: 0.0,
+                z: 0.0,
+            },
+        }));
+        for i in 0..synthetic.points {
+            let (row, column) = (i / side, i % side);
+            let (x, y) = (column as f32 * 0.01, row as f32 * 0.01);
+            let mut values = vec![
+                RecordValue::Single(x),
+                RecordValue::Single(y),
+                RecordValue::Single((x * 3.0).sin() * (y * 2.0).cos()),
+                RecordValue::Single((i % 256) as f32 / 255.0),
+            ];
+            if synthetic.color {
+                values.extend([
+                    RecordValue::Integer((row % 256) as i64),
+                    RecordValue::Integer((column % 256) as i64),
+                    RecordValue::Integer(128),
+                ]);
+            }
+            pointcloud.add_point(values)?;
+        }
+        pointcloud.finalize()?;
+    }
+    writer.finalize()
+}
+
+/// Chunks logged to an in-memory recording.
+pub struct Recording {
+    chunks: Vec<Chunk>,
+}
+
+impl Recording {
+    /// Runs `log` with a recording stream and collects everything it logged.
+    pub fn capture<T>(log: impl FnOnce(&RecordingStream) -> T) -> (T, Self) {
+        let (rec, storage) = RecordingStreamBuilder::new("rerun_e57_test")
+            .memory()
+            .expect("memory recording");
+        let result = log(&rec);
+        rec.flush_blocking();
+        // Blueprints are sent to a store of their own.
+        let chunks = storage
+            .take()
+            .into_iter()
+            .filter_map(|msg| match msg {
+                LogMsg::ArrowMsg(store_id, msg) if store_id.kind == StoreKind::Recording => {
+                    Some(Chunk::from_arrow_msg(&msg).expect("valid chunk"))
+                }
+                _ => None,
+            })
+            .collect();
+        (result, Self { chunks })
+    }
+
+    /// Returns the paths of all entities something was logged to, e.g. `/e57_pointcloud`.
+    pub fn entities(&self) -> BTreeSet<String> {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.entity_path().to_string())
+            .collect()
+    }
+
+    /// Returns the entities below `prefix` that have a component `C`.
+    pub fn entities_with<C: Component>(&self, prefix: &str) -> BTreeSet<String> {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.entity_path().to_string().starts_with(prefix))
+            .filter(|chunk| chunk.component_names().any(|name| name == C::name()))
+            .map(|chunk| chunk.entity_path().to_string())
+            .collect()
+    }
+
+    /// Returns all values of the component `C` logged to `entity`, in the order they were
+    /// logged.
+    pub fn component<C: Component>(&self, entity: &str) -> Vec<C> {
+        let mut values = Vec::new();
+        for chunk in &self.chunks {
+            if chunk.entity_path().to_string() != entity {
+                continue;
+            }
+            for row in 0..chunk.num_rows() {
+                if let Some(batch) = chunk.component_batch::<C>(row) {
+                    values.extend(batch.expect("deserializable component"));
+                }
+            }
+        }
+        values
+    }
+}
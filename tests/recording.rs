@@ -0,0 +1,717 @@
+mod common;
+
+use common::{write_synthetic_e57, Recording, Synthetic};
+use rerun::components::{
+    ClearIsRecursive, Color, ImageFormat, LineStrip3D, Position2D, Position3D, Text, Translation3D,
+};
+use rerun_loader_e57::{
+    diff_files, load_e57, load_file, load_files, load_ptx, Center, Clustering, ColorBy,
+    LoadOptions, Section,
+};
+use std::path::Path;
+
+fn synthetic_file(synthetic: Synthetic) -> tempfile::NamedTempFile {
+    let file = tempfile::Builder::new().suffix(".e57").tempfile().unwrap();
+    write_synthetic_e57(file.path(), synthetic).unwrap();
+    file
+}
+
+/// Writes a PTX file of a colored scan with the given number of columns and rows, and an
+/// identity pose.
+fn ptx_file(columns: usize, rows: usize) -> tempfile::NamedTempFile {
+    let mut text = format!("{columns}\n{rows}\n0 0 0\n1 0 0\n0 1 0\n0 0 1\n");
+    text += "1 0 0 0\n0 1 0 0\n0 0 1 0\n0 0 0 1\n";
+    for i in 0..columns * rows {
+        text += &format!("{i} 1 0 0.5 {} 0 0\n", i % 256);
+    }
+    let file = tempfile::Builder::new().suffix(".ptx").tempfile().unwrap();
+    std::fs::write(file.path(), text).unwrap();
+    file
+}
+
+fn load(path: &Path, options: LoadOptions) -> Recording {
+    let options = LoadOptions {
+        quiet: true,
+        ..options
+    };
+    let (stats, recording) = Recording::capture(|rec| load_e57(path, &options, rec));
+    stats.unwrap();
+    recording
+}
+
+#[test]
+fn logs_every_scan_with_its_pose() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 100,
+        color: true,
+    });
+    let recording = load(file.path(), LoadOptions::default());
+
+    let entities = recording.entities();
+    for entity in [
+        "/e57_pointcloud",
+        "/e57_pointcloud/scan_0",
+        "/e57_pointcloud/scan_0/chunk_0",
+        "/e57_pointcloud/scan_1",
+        "/e57_pointcloud/scan_1/chunk_0",
+    ] {
+        assert!(
+            entities.contains(entity),
+            "{entity} missing in {entities:?}"
+        );
+    }
+    for scan in 0..2 {
+        let positions =
+            recording.component::<Position3D>(&format!("/e57_pointcloud/scan_{scan}/chunk_0"));
+        assert_eq!(positions.len(), 100);
+    }
+    assert_eq!(
+        recording.component::<Translation3D>("/e57_pointcloud/scan_1"),
+        [Translation3D::new(10.0, 0.0, 0.0)]
+    );
+}
+
+#[test]
+fn splits_scans_into_chunks() {
+    let file = synthetic_file(Synthetic {
+        scans: 1,
+        points: 1000,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            chunk_size: Some(300),
+            ..Default::default()
+        },
+    );
+
+    let chunks: Vec<usize> = (0..4)
+        .map(|i| {
+            recording
+                .component::<Position3D>(&format!("/e57_pointcloud/scan_0/chunk_{i}"))
+                .len()
+        })
+        .collect();
+    assert_eq!(chunks, [300, 300, 300, 100]);
+    assert_eq!(
+        recording
+            .entities_with::<Position3D>("/e57_pointcloud/scan_0/chunk_")
+            .len(),
+        4
+    );
+}
+
+#[test]
+fn keeps_rgb_colors_or_colors_by_intensity() {
+    let file = synthetic_file(Synthetic {
+        scans: 1,
+        points: 100,
+        color: true,
+    });
+    let recording = load(file.path(), LoadOptions::default());
+    let colors = recording.component::<Color>("/e57_pointcloud/scan_0/chunk_0");
+    // The points are on a 10x10 grid, with the row as red and the column as green.
+    assert_eq!(colors[13], Color::from_rgb(1, 3, 128));
+
+    let file = synthetic_file(Synthetic {
+        scans: 1,
+        points: 100,
+        color: false,
+    });
+    let recording = load(file.path(), LoadOptions::default());
+    let colors = recording.component::<Color>("/e57_pointcloud/scan_0/chunk_0");
+    let [r, g, b, _] = colors[50].to_array();
+    assert!(r == g && g == b && r > 0, "{colors:?}");
+}
+
+#[test]
+fn colors_scans_from_the_palette() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 100,
+        color: true,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            color_by: ColorBy::Scan,
+            scan_palette: true,
+            ..Default::default()
+        },
+    );
+    for (scan, color) in [
+        (0, Color::from_rgb(78, 121, 167)),
+        (1, Color::from_rgb(242, 142, 43)),
+    ] {
+        let colors = recording.component::<Color>(&format!("/e57_pointcloud/scan_{scan}/chunk_0"));
+        assert!(colors.iter().all(|c| *c == color), "{colors:?}");
+    }
+}
+
+#[test]
+fn logs_a_preview_before_the_full_scans() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 1000,
+        color: true,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            preview_first: true,
+            ..Default::default()
+        },
+    );
+    for scan in 0..2 {
+        let scan_path = format!("/e57_pointcloud/scan_{scan}");
+        let points = |entity: &str| recording.component::<Position3D>(entity).len();
+        assert_eq!(points(&format!("{scan_path}/preview/chunk_0")), 10);
+        assert_eq!(points(&format!("{scan_path}/chunk_0")), 1000);
+        // The preview is cleared once the full resolution points are logged.
+        assert_eq!(
+            recording
+                .component::<ClearIsRecursive>(&format!("{scan_path}/preview"))
+                .len(),
+            1
+        );
+    }
+}
+
+#[test]
+fn replays_repeated_loads_from_the_cache() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 100,
+        color: true,
+    });
+    let cache = tempfile::tempdir().unwrap();
+    let options = LoadOptions {
+        cache: Some(cache.path().to_owned()),
+        lod_levels: Some(2),
+        quiet: true,
+        ..Default::default()
+    };
+    let (loaded, first) = Recording::capture(|rec| load_file(file.path(), &options, rec));
+    assert_eq!(std::fs::read_dir(cache.path()).unwrap().count(), 1);
+    let (replayed, second) = Recording::capture(|rec| load_file(file.path(), &options, rec));
+    assert_eq!(replayed.unwrap(), loaded.unwrap());
+    assert_eq!(second.entities(), first.entities());
+    assert_eq!(
+        second.component::<Position3D>("/e57_pointcloud/scan_1/chunk_0"),
+        first.component::<Position3D>("/e57_pointcloud/scan_1/chunk_0")
+    );
+}
+
+#[test]
+fn skips_scans_logged_from_an_earlier_file() {
+    let first = synthetic_file(Synthetic {
+        scans: 2,
+        points: 100,
+        color: false,
+    });
+    let second = synthetic_file(Synthetic {
+        scans: 3,
+        points: 100,
+        color: false,
+    });
+    let options = LoadOptions {
+        dedup_scans: true,
+        quiet: true,
+        ..Default::default()
+    };
+    let paths = [first.path().to_owned(), second.path().to_owned()];
+    let (stats, recording) = Recording::capture(|rec| load_files(&paths, &options, rec));
+    let stats = stats.unwrap();
+    assert_eq!((stats.scans, stats.duplicate_scans), (3, 2));
+
+    // The scans of both files share their GUIDs, so only the last scan of the second is new.
+    let stem = second.path().file_stem().unwrap().to_string_lossy();
+    let entities = recording.entities();
+    for scan in 0..3 {
+        let chunk = format!("/e57_pointcloud/{stem}/scan_{scan}/chunk_0");
+        assert_eq!(
+            entities.contains(&chunk),
+            scan == 2,
+            "{chunk} in {entities:?}"
+        );
+    }
+}
+
+#[test]
+fn groups_scans_by_station() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            group_stations: true,
+            ..Default::default()
+        },
+    );
+    // The synthetic scans are 10 m apart, so each is a station of its own.
+    let entities = recording.entities();
+    for entity in [
+        "/e57_pointcloud/station_0/scan_0/chunk_0",
+        "/e57_pointcloud/station_1/scan_1/chunk_0",
+    ] {
+        assert!(
+            entities.contains(entity),
+            "{entity} missing in {entities:?}"
+        );
+    }
+}
+
+#[test]
+fn keeps_the_rows_and_columns_of_structured_scans() {
+    let file = ptx_file(4, 3);
+    let options = LoadOptions {
+        rows: Some(0..2),
+        columns: Some(1..3),
+        quiet: true,
+        ..Default::default()
+    };
+    let (stats, recording) = Recording::capture(|rec| load_ptx(file.path(), &options, rec));
+    assert_eq!(stats.unwrap().points, 4);
+    assert_eq!(
+        recording
+            .component::<Position3D>("/ptx_pointcloud/scan_0/chunk_0")
+            .len(),
+        4
+    );
+}
+
+#[test]
+fn reconstructs_panoramas_of_structured_scans() {
+    let file = ptx_file(4, 3);
+    let options = LoadOptions {
+        reconstruct_panorama: true,
+        quiet: true,
+        ..Default::default()
+    };
+    let (stats, recording) = Recording::capture(|rec| load_ptx(file.path(), &options, rec));
+    stats.unwrap();
+    let formats = recording.component::<ImageFormat>("/ptx_pointcloud/scan_0/panorama");
+    assert_eq!(
+        formats
+            .iter()
+            .map(|format| (format.width, format.height))
+            .collect::<Vec<_>>(),
+        [(4, 3)]
+    );
+}
+
+#[test]
+fn splits_ground_and_non_ground_points() {
+    let file = synthetic_file(Synthetic {
+        scans: 1,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            segment_ground: true,
+            ..Default::default()
+        },
+    );
+    // The synthetic scan is a 10 cm square, which is all ground.
+    let positions = recording.component::<Position3D>("/e57_pointcloud/scan_0/ground/chunk_0");
+    assert_eq!(positions.len(), 100);
+    let entities = recording.entities();
+    assert!(!entities.contains("/e57_pointcloud/scan_0/non_ground/chunk_0"));
+}
+
+#[test]
+fn detects_the_planes_of_a_scan() {
+    let file = synthetic_file(Synthetic {
+        scans: 1,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            detect_planes: Some(3),
+            seed: Some(0),
+            ..Default::default()
+        },
+    );
+    // The synthetic scan is close enough to flat to be a single plane.
+    let labels = recording.component::<Text>("/e57_pointcloud/scan_0/planes");
+    assert_eq!(
+        labels
+            .iter()
+            .map(|label| label.as_str())
+            .collect::<Vec<_>>(),
+        ["Plane 0: 100 points"]
+    );
+}
+
+#[test]
+fn splits_scans_into_clusters() {
+    let file = synthetic_file(Synthetic {
+        scans: 1,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            cluster: Some(Clustering {
+                eps: 0.05,
+                min_points: 3,
+            }),
+            ..Default::default()
+        },
+    );
+    // The points of the synthetic scan are at most 4 cm apart, so they are all one cluster.
+    let positions = recording.component::<Position3D>("/e57_pointcloud/scan_0/cluster_0/chunk_0");
+    assert_eq!(positions.len(), 100);
+    assert!(!recording
+        .entities()
+        .contains("/e57_pointcloud/scan_0/noise/chunk_0"));
+}
+
+#[test]
+fn logs_the_coverage_of_the_scans() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            coverage_heatmap: true,
+            ..Default::default()
+        },
+    );
+    // The scans are 10 m apart, so every voxel has the points of a single scan.
+    let legend = recording.component::<Text>("/e57_pointcloud/coverage");
+    assert!(legend[0].as_str().ends_with("to 1 (red)"), "{legend:?}");
+    let positions = recording.component::<Position3D>("/e57_pointcloud/coverage/voxels");
+    assert!(positions.iter().any(|p| p.x() < 1.0));
+    assert!(positions.iter().any(|p| p.x() > 9.0));
+}
+
+#[test]
+fn logs_the_voxels_added_by_a_second_file() {
+    let before = synthetic_file(Synthetic {
+        scans: 1,
+        points: 100,
+        color: false,
+    });
+    let after = synthetic_file(Synthetic {
+        scans: 2,
+        points: 100,
+        color: false,
+    });
+    let options = LoadOptions {
+        quiet: true,
+        ..Default::default()
+    };
+    let (stats, recording) =
+        Recording::capture(|rec| diff_files(before.path(), after.path(), &options, rec));
+    let stats = stats.unwrap();
+    // The second scan is 10 m away from the first one, which did not change.
+    assert!(stats.added > 0 && stats.unchanged > 0, "{stats:?}");
+    assert_eq!((stats.removed, stats.moved), (0, 0));
+    let positions = recording.component::<Position3D>("/diff/added");
+    assert_eq!(positions.len() as u64, stats.added);
+    assert!(positions.iter().all(|p| p.x() > 9.0));
+}
+
+#[test]
+fn finds_no_changes_between_the_same_file_around_a_center() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 100,
+        color: false,
+    });
+    let options = LoadOptions {
+        center: Some(Center::Auto),
+        quiet: true,
+        ..Default::default()
+    };
+    let (stats, _) = Recording::capture(|rec| diff_files(file.path(), file.path(), &options, rec));
+    let stats = stats.unwrap();
+    assert!(stats.unchanged > 0, "{stats:?}");
+    assert_eq!((stats.added, stats.removed, stats.moved), (0, 0, 0));
+}
+
+#[test]
+fn logs_sections_in_3d_and_2d() {
+    let file = synthetic_file(Synthetic {
+        scans: 1,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            section: Some(Section {
+                axis: 2,
+                offset: 0.0,
+                thickness: 0.02,
+            }),
+            ..Default::default()
+        },
+    );
+    // Only the first column of the synthetic scan is within 1 cm of z = 0.
+    let positions = recording.component::<Position3D>("/e57_pointcloud/scan_0/chunk_0");
+    assert_eq!(positions.len(), 10);
+    let projected = recording.component::<Position2D>("/section_2d/e57_pointcloud/scan_0");
+    assert_eq!(projected.len(), 10);
+    assert!(projected.iter().all(|p| p.x() == 0.0));
+}
+
+#[test]
+fn measures_the_scans() {
+    let file = synthetic_file(Synthetic {
+        scans: 1,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            measure: true,
+            ..Default::default()
+        },
+    );
+    // The grid of 9 by 9 cm is tilted along X, where it rises by 27 cm.
+    let labels = recording.component::<Text>("/e57_pointcloud/scan_0/measurements");
+    assert_eq!(
+        labels
+            .iter()
+            .map(|label| label.as_str())
+            .collect::<Vec<_>>(),
+        ["Scan 0: 0.28 x 0.09 x 0.00 m"]
+    );
+}
+
+#[test]
+fn logs_an_elevation_model_of_the_ground() {
+    let file = synthetic_file(Synthetic {
+        scans: 1,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            dem: Some(0.05),
+            ..Default::default()
+        },
+    );
+    // The whole grid of 9 by 9 cm is ground, in 2 by 2 cells.
+    let formats = recording.component::<ImageFormat>("/dem/e57_pointcloud");
+    assert_eq!(
+        formats
+            .iter()
+            .map(|format| (format.width, format.height))
+            .collect::<Vec<_>>(),
+        [(2, 2)]
+    );
+    // The points are not split without --segment-ground.
+    assert!(!recording
+        .entities()
+        .iter()
+        .any(|entity| entity.ends_with("/ground")));
+}
+
+#[test]
+fn names_entities_after_the_template() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            entity_template: Some("{prefix}/{scan_name}/part_{chunk}".parse().unwrap()),
+            ..Default::default()
+        },
+    );
+    // The scan origins are logged as `point` entities of the scans.
+    assert_eq!(
+        recording.entities_with::<Position3D>("/e57_pointcloud"),
+        [
+            "/e57_pointcloud/Scan_0/part_0",
+            "/e57_pointcloud/Scan_0/point",
+            "/e57_pointcloud/Scan_1/part_0",
+            "/e57_pointcloud/Scan_1/point"
+        ]
+        .map(String::from)
+        .into()
+    );
+}
+
+#[test]
+fn merges_scans_in_file_coordinates() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            merge_scans: true,
+            ..Default::default()
+        },
+    );
+
+    // The scan markers stay at the scans.
+    let merged: Vec<String> = recording
+        .entities_with::<Position3D>("/e57_pointcloud/")
+        .into_iter()
+        .filter(|entity| entity.contains("/chunk_"))
+        .collect();
+    assert!(merged
+        .iter()
+        .all(|entity| entity.starts_with("/e57_pointcloud/merged/")));
+    let positions: Vec<Position3D> = merged
+        .iter()
+        .flat_map(|entity| recording.component::<Position3D>(entity))
+        .collect();
+    assert_eq!(positions.len(), 200);
+    // The grid of each scan starts at its origin, which is 10 m along X for the second one.
+    for x in [0.0, 10.0] {
+        assert!(positions.contains(&Position3D::new(x, 0.0, 0.0)), "{x}");
+    }
+}
+
+#[test]
+fn skips_scans_with_too_few_points() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 50,
+        color: false,
+    });
+    let options = LoadOptions {
+        quiet: true,
+        min_points: Some(51),
+        ..Default::default()
+    };
+    let (stats, recording) = Recording::capture(|rec| load_e57(file.path(), &options, rec));
+    let stats = stats.unwrap();
+    assert_eq!((stats.scans, stats.skipped_scans), (0, 2));
+    assert!(recording
+        .entities_with::<Position3D>("/e57_pointcloud/scan_0/chunk_")
+        .is_empty());
+}
+
+#[test]
+fn distributes_max_points_over_scans() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 400,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            max_points: Some(101),
+            ..Default::default()
+        },
+    );
+    for scan in 0..2 {
+        let positions =
+            recording.component::<Position3D>(&format!("/e57_pointcloud/scan_{scan}/chunk_0"));
+        assert_eq!(positions.len(), 50);
+    }
+}
+
+#[test]
+fn slices_points_by_height() {
+    let file = synthetic_file(Synthetic {
+        scans: 1,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            slice_z: Some(0.1),
+            ..Default::default()
+        },
+    );
+
+    let slices = recording.entities_with::<Position3D>("/e57_pointcloud/scan_0/slice_");
+    assert!(slices.len() > 1, "{slices:?}");
+    let mut points = 0;
+    for entity in &slices {
+        let slice: f32 = entity
+            .trim_start_matches("/e57_pointcloud/scan_0/slice_")
+            .trim_end_matches("/chunk_0")
+            .parse()
+            .unwrap();
+        for position in recording.component::<Position3D>(entity) {
+            assert_eq!(
+                (position.z() / 0.1).floor(),
+                slice,
+                "{entity}: {position:?}"
+            );
+            points += 1;
+        }
+    }
+    assert_eq!(points, 100);
+}
+
+#[test]
+fn tiles_merged_points() {
+    let file = synthetic_file(Synthetic {
+        scans: 2,
+        points: 100,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            merge_scans: true,
+            tile_size: Some(5.0),
+            ..Default::default()
+        },
+    );
+
+    // The second scan is 10 m along X, two tiles away from the first one.
+    for tile in ["tile_0_0", "tile_2_0"] {
+        let positions: usize = recording
+            .entities_with::<Position3D>(&format!("/e57_pointcloud/merged/{tile}/"))
+            .iter()
+            .map(|entity| recording.component::<Position3D>(entity).len())
+            .sum();
+        assert_eq!(positions, 100, "{tile}");
+    }
+}
+
+#[test]
+fn connects_scan_positions_as_trajectory() {
+    let file = synthetic_file(Synthetic {
+        scans: 3,
+        points: 10,
+        color: false,
+    });
+    let recording = load(
+        file.path(),
+        LoadOptions {
+            show_trajectory: true,
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        recording.component::<LineStrip3D>("/e57_pointcloud/trajectory"),
+        [LineStrip3D::from_iter([
+            [0.0, 0.0, 0.0],
+            [10.0, 0.0, 0.0],
+            [20.0, 0.0, 0.0]
+        ])]
+    );
+}
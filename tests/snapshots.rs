@@ -0,0 +1,45 @@
+//! Snapshots of the JSON output of `--info` and `--summary-json`. After an intended change,
+//! update them with `INSTA_UPDATE=always cargo test --test snapshots` and review the diff.
+
+mod common;
+
+use common::{write_synthetic_e57, Recording, Synthetic};
+use rerun_loader_e57::{inspect_e57, load_e57, LoadOptions};
+use std::path::PathBuf;
+
+const SYNTHETIC: Synthetic = Synthetic {
+    scans: 2,
+    points: 100,
+    color: true,
+};
+
+fn synthetic_file() -> tempfile::NamedTempFile {
+    let file = tempfile::Builder::new().suffix(".e57").tempfile().unwrap();
+    write_synthetic_e57(file.path(), SYNTHETIC).unwrap();
+    file
+}
+
+#[test]
+fn info_json() {
+    let file = synthetic_file();
+    let mut info = inspect_e57(file.path()).unwrap();
+    // The temporary path differs between runs.
+    info.path = PathBuf::from("synthetic.e57");
+    insta::assert_json_snapshot!(info);
+}
+
+#[test]
+fn summary_json() {
+    let file = synthetic_file();
+    let options = LoadOptions {
+        quiet: true,
+        ..Default::default()
+    };
+    let (stats, _) = Recording::capture(|rec| load_e57(file.path(), &options, rec));
+    let mut stats = stats.unwrap();
+    for report in &mut stats.reports {
+        report.file = PathBuf::from("synthetic.e57");
+        report.duration = 0.0;
+    }
+    insta::assert_json_snapshot!(stats);
+}
@@ -0,0 +1,24 @@
+mod common;
+
+use common::{write_synthetic_e57, Synthetic};
+use e57::E57Reader;
+
+#[test]
+fn writes_synthetic_scans() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let synthetic = Synthetic {
+        scans: 2,
+        points: 1000,
+        color: true,
+    };
+    write_synthetic_e57(file.path(), synthetic).unwrap();
+
+    let reader = E57Reader::from_file(file.path()).unwrap();
+    let pointclouds = reader.pointclouds();
+    assert_eq!(pointclouds.len(), 2);
+    for pointcloud in &pointclouds {
+        assert_eq!(pointcloud.records, 1000);
+        assert!(pointcloud.has_color() && pointcloud.has_intensity());
+    }
+    assert_eq!(pointclouds[1].name.as_deref(), Some("Scan 1"));
+}